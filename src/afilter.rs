@@ -0,0 +1,859 @@
+use av::ChannelLayout;
+use av::util::format::{Sample, sample::Type as SampleType};
+use av::util::frame::Audio as AudioFrame;
+use parking_lot::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+// @ ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== @
+
+/// 均衡器各频段的中心频率（Hz）
+pub const EQ_BANDS: [f32; 10] = [
+    31.0, 62.0, 125.0, 250.0, 500.0, 1000.0, 2000.0, 4000.0, 8000.0, 16000.0,
+];
+
+/// 均衡器预设（各频段增益，dB）
+pub const EQ_PRESETS: &[(&str, [f32; 10])] = &[
+    ("flat", [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0]),
+    ("bass", [6.0, 5.0, 4.0, 2.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0]),
+    ("treble", [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 2.0, 4.0, 5.0, 6.0]),
+    (
+        "vocal",
+        [-4.0, -3.0, -2.0, 0.0, 2.0, 4.0, 4.0, 2.0, 0.0, -2.0],
+    ),
+    (
+        "loudness",
+        [5.0, 4.0, 2.0, 0.0, -1.0, -1.0, 0.0, 2.0, 4.0, 5.0],
+    ),
+    ("rock", [4.0, 3.0, 2.0, 0.0, -1.0, -1.0, 1.0, 3.0, 4.0, 4.0]),
+    (
+        "pop",
+        [-1.0, 1.0, 3.0, 4.0, 3.0, 0.0, -1.0, -1.0, -1.0, -1.0],
+    ),
+    (
+        "classical",
+        [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -2.0, -3.0, -3.0, -4.0],
+    ),
+    // 笔记本扬声器放不出低频，削掉低频把余量留给人声
+    (
+        "laptop",
+        [-12.0, -8.0, -4.0, 0.0, 1.0, 2.0, 3.0, 3.0, 2.0, 0.0],
+    ),
+];
+
+/// 解析均衡器设置：预设名称，或逗号分隔的 10 个频段增益（dB）
+pub fn parse_eq(s: &str) -> Option<[f32; 10]> {
+    let s = s.trim().to_lowercase();
+    if let Some((_, gains)) = EQ_PRESETS.iter().find(|(name, _)| *name == s) {
+        return Some(*gains);
+    }
+    let values = s
+        .split(',')
+        .map(|v| v.trim().parse::<f32>().ok().filter(|g| g.is_finite()))
+        .collect::<Option<Vec<_>>>()?;
+    let mut gains = [0.0; 10];
+    if values.len() != gains.len() {
+        return None;
+    }
+    for (g, v) in gains.iter_mut().zip(values) {
+        *g = v.clamp(-24.0, 24.0);
+    }
+    Some(gains)
+}
+
+/// 均衡器设置的名称（预设名或增益列表）
+pub fn eq_name(gains: &[f32; 10]) -> String {
+    match EQ_PRESETS.iter().find(|(_, g)| g == gains) {
+        Some((name, _)) => name.to_string(),
+        None => gains
+            .iter()
+            .map(|g| format!("{g}"))
+            .collect::<Vec<_>>()
+            .join(","),
+    }
+}
+
+/// 响度归一化模式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Normalize {
+    Off,
+    /// 使用 ReplayGain 音轨增益，没有标签时退回 EBU R128 实时测量
+    Track,
+    /// 使用 ReplayGain 专辑增益，没有时依次退回音轨增益和 EBU R128
+    Album,
+    /// EBU R128 实时测量
+    R128,
+}
+
+impl Normalize {
+    pub const ALL: &[Normalize] = &[
+        Normalize::Off,
+        Normalize::Track,
+        Normalize::Album,
+        Normalize::R128,
+    ];
+
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Normalize::Off => "off",
+            Normalize::Track => "track",
+            Normalize::Album => "album",
+            Normalize::R128 => "r128",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.trim().to_lowercase();
+        Self::ALL.iter().copied().find(|m| m.name() == name)
+    }
+}
+
+/// 下混模式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Downmix {
+    /// 交给 FFmpeg 的重采样器处理
+    Auto,
+    /// ITU 标准下混到立体声
+    Stereo,
+    /// 下混到立体声，同时提升中置声道（对白）并压低环绕声道
+    Center,
+    /// 下混到单声道
+    Mono,
+}
+
+impl Downmix {
+    pub const ALL: &[Downmix] = &[
+        Downmix::Auto,
+        Downmix::Stereo,
+        Downmix::Center,
+        Downmix::Mono,
+    ];
+
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Downmix::Auto => "auto",
+            Downmix::Stereo => "stereo",
+            Downmix::Center => "center",
+            Downmix::Mono => "mono",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.trim().to_lowercase();
+        Self::ALL.iter().copied().find(|m| m.name() == name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Settings {
+    eq: [f32; 10],
+    normalize: Normalize,
+    /// 归一化的目标响度（LUFS）
+    target: f32,
+    /// 夜间模式（动态范围压缩）
+    drc: bool,
+    downmix: Downmix,
+}
+
+impl Settings {
+    const fn new() -> Self {
+        Self {
+            eq: [0.0; 10],
+            normalize: Normalize::Off,
+            target: -18.0,
+            drc: false,
+            downmix: Downmix::Auto,
+        }
+    }
+}
+
+static SETTINGS: Mutex<Settings> = Mutex::new(Settings::new());
+/// 设置每次变化时递增，音频线程据此重建滤镜
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+fn update_settings(f: impl FnOnce(&mut Settings)) {
+    f(&mut SETTINGS.lock());
+    GENERATION.fetch_add(1, Ordering::SeqCst);
+}
+
+pub fn set_eq(gains: [f32; 10]) {
+    update_settings(|s| s.eq = gains);
+}
+
+pub fn set_normalize(normalize: Normalize) {
+    update_settings(|s| s.normalize = normalize);
+}
+
+pub fn set_normalize_target(lufs: f32) {
+    update_settings(|s| s.target = lufs.clamp(-40.0, 0.0));
+}
+
+pub fn set_drc(drc: bool) {
+    update_settings(|s| s.drc = drc);
+}
+
+pub fn set_downmix(downmix: Downmix) {
+    update_settings(|s| s.downmix = downmix);
+}
+
+/// 当前的下混模式，音频线程每帧只取一次，重采样和下混都用这个值
+pub fn downmix_mode() -> Downmix {
+    SETTINGS.lock().downmix
+}
+
+/// 关闭所有滤镜
+pub fn reset() {
+    update_settings(|s| *s = Settings::new());
+}
+
+// @ ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== @
+
+/// 容器中读取到的 ReplayGain 标签
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ReplayGain {
+    pub track_gain: Option<f32>,
+    pub track_peak: Option<f32>,
+    pub album_gain: Option<f32>,
+    pub album_peak: Option<f32>,
+}
+
+impl ReplayGain {
+    /// 从元数据标签中读取，如 `REPLAYGAIN_TRACK_GAIN = "-6.52 dB"`
+    pub fn from_tags(get: impl Fn(&str) -> Option<String>) -> Self {
+        let parse = |key: &str| {
+            get(key).and_then(|v| {
+                let v = v.trim();
+                let v = v.strip_suffix("dB").or(v.strip_suffix("db")).unwrap_or(v);
+                v.trim().parse::<f32>().ok().filter(|v| v.is_finite())
+            })
+        };
+        Self {
+            track_gain: parse("REPLAYGAIN_TRACK_GAIN"),
+            track_peak: parse("REPLAYGAIN_TRACK_PEAK"),
+            album_gain: parse("REPLAYGAIN_ALBUM_GAIN"),
+            album_peak: parse("REPLAYGAIN_ALBUM_PEAK"),
+        }
+    }
+
+    /// 按模式选出增益（dB）和峰值
+    fn select(&self, normalize: Normalize) -> Option<(f32, Option<f32>)> {
+        let track = self.track_gain.map(|g| (g, self.track_peak));
+        let album = self.album_gain.map(|g| (g, self.album_peak));
+        match normalize {
+            Normalize::Track => track.or(album),
+            Normalize::Album => album.or(track),
+            _ => None,
+        }
+    }
+}
+
+static REPLAYGAIN: Mutex<ReplayGain> = Mutex::new(ReplayGain {
+    track_gain: None,
+    track_peak: None,
+    album_gain: None,
+    album_peak: None,
+});
+
+/// 设置当前文件的 ReplayGain 标签（每个文件打开时调用）
+pub fn set_replaygain(rg: ReplayGain) {
+    *REPLAYGAIN.lock() = rg;
+}
+
+/// ReplayGain 2.0 的参考响度
+const REPLAYGAIN_REFERENCE: f32 = -18.0;
+
+// @ ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== @
+
+fn db_to_linear(db: f32) -> f32 {
+    10f32.powf(db / 20.0)
+}
+
+/// 二阶 IIR 滤波器系数（已按 a0 归一化）
+#[derive(Debug, Clone, Copy)]
+struct Biquad {
+    b0: f64,
+    b1: f64,
+    b2: f64,
+    a1: f64,
+    a2: f64,
+}
+
+impl Biquad {
+    fn normalized(b0: f64, b1: f64, b2: f64, a0: f64, a1: f64, a2: f64) -> Self {
+        Self {
+            b0: b0 / a0,
+            b1: b1 / a0,
+            b2: b2 / a0,
+            a1: a1 / a0,
+            a2: a2 / a0,
+        }
+    }
+
+    /// 峰值滤波器（RBJ Audio EQ Cookbook）
+    fn peaking(rate: f64, f0: f64, gain_db: f64, q: f64) -> Self {
+        let a = 10f64.powf(gain_db / 40.0);
+        let w0 = std::f64::consts::TAU * f0 / rate;
+        let alpha = w0.sin() / (2.0 * q);
+        let cos = w0.cos();
+        Self::normalized(
+            1.0 + alpha * a,
+            -2.0 * cos,
+            1.0 - alpha * a,
+            1.0 + alpha / a,
+            -2.0 * cos,
+            1.0 - alpha / a,
+        )
+    }
+
+    /// 高架滤波器
+    fn high_shelf(rate: f64, f0: f64, gain_db: f64, q: f64) -> Self {
+        let a = 10f64.powf(gain_db / 40.0);
+        let w0 = std::f64::consts::TAU * f0 / rate;
+        let alpha = w0.sin() / (2.0 * q);
+        let cos = w0.cos();
+        let sqrt_a = a.sqrt();
+        Self::normalized(
+            a * ((a + 1.0) + (a - 1.0) * cos + 2.0 * sqrt_a * alpha),
+            -2.0 * a * ((a - 1.0) + (a + 1.0) * cos),
+            a * ((a + 1.0) + (a - 1.0) * cos - 2.0 * sqrt_a * alpha),
+            (a + 1.0) - (a - 1.0) * cos + 2.0 * sqrt_a * alpha,
+            2.0 * ((a - 1.0) - (a + 1.0) * cos),
+            (a + 1.0) - (a - 1.0) * cos - 2.0 * sqrt_a * alpha,
+        )
+    }
+
+    /// 高通滤波器
+    fn high_pass(rate: f64, f0: f64, q: f64) -> Self {
+        let w0 = std::f64::consts::TAU * f0 / rate;
+        let alpha = w0.sin() / (2.0 * q);
+        let cos = w0.cos();
+        Self::normalized(
+            (1.0 + cos) / 2.0,
+            -(1.0 + cos),
+            (1.0 + cos) / 2.0,
+            1.0 + alpha,
+            -2.0 * cos,
+            1.0 - alpha,
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct BiquadState {
+    x1: f64,
+    x2: f64,
+    y1: f64,
+    y2: f64,
+}
+
+impl BiquadState {
+    fn process(&mut self, f: &Biquad, x: f64) -> f64 {
+        let y = f.b0 * x + f.b1 * self.x1 + f.b2 * self.x2 - f.a1 * self.y1 - f.a2 * self.y2;
+        self.x2 = self.x1;
+        self.x1 = x;
+        self.y2 = self.y1;
+        self.y1 = y;
+        y
+    }
+}
+
+// @ ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== @
+
+/// 直方图覆盖的响度范围：-70 LUFS（绝对门限）到 +10 LUFS，精度 0.1 LU
+const LOUDNESS_BINS: usize = 800;
+
+/// EBU R128 响度测量（400ms 块，75% 重叠，绝对门限 + 相对门限）
+struct LoudnessMeter {
+    k_weight: [Biquad; 2],
+    state: Vec<[BiquadState; 2]>,
+    /// 当前 100ms 子块的平方和
+    sub_sum: f64,
+    sub_len: usize,
+    sub_target: usize,
+    /// 最近 4 个子块的能量
+    ring: [f64; 4],
+    ring_len: usize,
+    ring_pos: usize,
+    hist_count: Vec<u32>,
+    hist_energy: Vec<f64>,
+}
+
+impl LoudnessMeter {
+    fn new(rate: u32, channels: usize) -> Self {
+        let rate_f = rate as f64;
+        Self {
+            k_weight: [
+                Biquad::high_shelf(
+                    rate_f,
+                    1681.974450955533,
+                    3.999843853973347,
+                    0.7071752369554196,
+                ),
+                Biquad::high_pass(rate_f, 38.13547087602444, 0.5003270373238773),
+            ],
+            state: vec![[BiquadState::default(); 2]; channels],
+            sub_sum: 0.0,
+            sub_len: 0,
+            sub_target: (rate as usize / 10).max(1),
+            ring: [0.0; 4],
+            ring_len: 0,
+            ring_pos: 0,
+            hist_count: vec![0; LOUDNESS_BINS],
+            hist_energy: vec![0.0; LOUDNESS_BINS],
+        }
+    }
+
+    fn push(&mut self, frame: &[f32]) {
+        for (x, state) in frame.iter().zip(self.state.iter_mut()) {
+            let y = state[0].process(&self.k_weight[0], *x as f64);
+            let y = state[1].process(&self.k_weight[1], y);
+            self.sub_sum += y * y;
+        }
+        self.sub_len += 1;
+        if self.sub_len < self.sub_target {
+            return;
+        }
+
+        self.ring[self.ring_pos] = self.sub_sum / self.sub_len as f64;
+        self.ring_pos = (self.ring_pos + 1) % self.ring.len();
+        self.ring_len = (self.ring_len + 1).min(self.ring.len());
+        self.sub_sum = 0.0;
+        self.sub_len = 0;
+
+        if self.ring_len == self.ring.len() {
+            let energy = self.ring.iter().sum::<f64>() / self.ring.len() as f64;
+            let loudness = -0.691 + 10.0 * energy.max(1e-12).log10();
+            if loudness > -70.0 {
+                let bin = (((loudness + 70.0) * 10.0) as usize).min(LOUDNESS_BINS - 1);
+                self.hist_count[bin] += 1;
+                self.hist_energy[bin] += energy;
+            }
+        }
+    }
+
+    /// 门限后的积分响度，数据不足 3 秒时返回 `None`
+    fn integrated(&self) -> Option<f32> {
+        let count = self.hist_count.iter().sum::<u32>();
+        if count < 30 {
+            return None;
+        }
+        let energy = self.hist_energy.iter().sum::<f64>();
+        let relative_gate = -0.691 + 10.0 * (energy / count as f64).log10() - 10.0;
+        let first_bin = ((relative_gate + 70.0) * 10.0).max(0.0) as usize;
+        let (mut count, mut energy) = (0u32, 0.0);
+        for bin in first_bin.min(LOUDNESS_BINS)..LOUDNESS_BINS {
+            count += self.hist_count[bin];
+            energy += self.hist_energy[bin];
+        }
+        if count == 0 {
+            return None;
+        }
+        Some((-0.691 + 10.0 * (energy / count as f64).log10()) as f32)
+    }
+}
+
+/// 前馈压缩器，各声道联动
+struct Compressor {
+    threshold: f32,
+    ratio: f32,
+    makeup: f32,
+    attack: f32,
+    release: f32,
+    /// 当前的增益衰减（dB，<= 0）
+    reduction: f32,
+}
+
+impl Compressor {
+    /// 夜间模式的参数：压低爆炸声，提升对白
+    fn night(rate: u32) -> Self {
+        let coef = |secs: f32| (-1.0 / (rate as f32 * secs)).exp();
+        Self {
+            threshold: -30.0,
+            ratio: 4.0,
+            makeup: 6.0,
+            attack: coef(0.010),
+            release: coef(0.250),
+            reduction: 0.0,
+        }
+    }
+
+    fn gain(&mut self, peak: f32) -> f32 {
+        let level = 20.0 * peak.max(1e-6).log10();
+        let target = if level > self.threshold {
+            (self.threshold - level) * (1.0 - 1.0 / self.ratio)
+        } else {
+            0.0
+        };
+        let coef = if target < self.reduction {
+            self.attack
+        } else {
+            self.release
+        };
+        self.reduction = target + coef * (self.reduction - target);
+        db_to_linear(self.reduction + self.makeup)
+    }
+}
+
+/// 峰值限制器，防止均衡器、归一化和下混造成削波
+struct Limiter {
+    ceiling: f32,
+    release: f32,
+    gain: f32,
+}
+
+impl Limiter {
+    fn new(rate: u32) -> Self {
+        Self {
+            ceiling: db_to_linear(-1.0),
+            release: 1.0 - (-1.0 / (rate as f32 * 0.050)).exp(),
+            gain: 1.0,
+        }
+    }
+
+    fn gain(&mut self, peak: f32) -> f32 {
+        if peak * self.gain > self.ceiling {
+            self.gain = self.ceiling / peak;
+        } else {
+            self.gain += (1.0 - self.gain) * self.release;
+        }
+        self.gain
+    }
+}
+
+// @ ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== @
+
+/// FFmpeg 原生声道顺序中各声道的掩码位（见 libavutil/channel_layout.h）
+mod ch {
+    pub const FRONT_LEFT: u64 = 0x1;
+    pub const FRONT_RIGHT: u64 = 0x2;
+    pub const FRONT_CENTER: u64 = 0x4;
+    pub const LOW_FREQUENCY: u64 = 0x8;
+    pub const BACK_LEFT: u64 = 0x10;
+    pub const BACK_RIGHT: u64 = 0x20;
+    pub const FRONT_LEFT_OF_CENTER: u64 = 0x40;
+    pub const FRONT_RIGHT_OF_CENTER: u64 = 0x80;
+    pub const BACK_CENTER: u64 = 0x100;
+    pub const SIDE_LEFT: u64 = 0x200;
+    pub const SIDE_RIGHT: u64 = 0x400;
+    pub const TOP_CENTER: u64 = 0x800;
+    pub const TOP_FRONT_LEFT: u64 = 0x1000;
+    pub const TOP_FRONT_CENTER: u64 = 0x2000;
+    pub const TOP_FRONT_RIGHT: u64 = 0x4000;
+    pub const TOP_BACK_LEFT: u64 = 0x8000;
+    pub const TOP_BACK_CENTER: u64 = 0x10000;
+    pub const TOP_BACK_RIGHT: u64 = 0x20000;
+    pub const LOW_FREQUENCY_2: u64 = 0x8_0000_0000;
+}
+
+/// 计算下混矩阵，返回每个输出声道对应各输入声道的增益
+fn downmix_matrix(mode: Downmix, src: ChannelLayout, out_channels: usize) -> Option<Vec<Vec<f32>>> {
+    if mode == Downmix::Auto || src.is_empty() || !(1..=2).contains(&out_channels) {
+        return None;
+    }
+    let mask = src.bits();
+    if mask.count_ones() as i32 != src.channels() {
+        return None; // 非原生顺序的声道布局
+    }
+    if src.channels() as usize <= out_channels && mode != Downmix::Mono {
+        return None;
+    }
+
+    const H: f32 = std::f32::consts::FRAC_1_SQRT_2;
+    let (center, surround, lfe) = match mode {
+        Downmix::Center => (1.414, 0.5, 0.0),
+        _ => (H, H, 0.0),
+    };
+
+    // (左, 右) 增益
+    let mut stereo = Vec::new();
+    for bit in (0..64).map(|i| 1u64 << i).filter(|b| mask & b != 0) {
+        let gains = match bit {
+            ch::FRONT_LEFT | ch::FRONT_LEFT_OF_CENTER => (1.0, 0.0),
+            ch::FRONT_RIGHT | ch::FRONT_RIGHT_OF_CENTER => (0.0, 1.0),
+            ch::FRONT_CENTER => (center, center),
+            ch::LOW_FREQUENCY | ch::LOW_FREQUENCY_2 => (lfe, lfe),
+            ch::BACK_LEFT | ch::SIDE_LEFT | ch::TOP_FRONT_LEFT | ch::TOP_BACK_LEFT => {
+                (surround, 0.0)
+            }
+            ch::BACK_RIGHT | ch::SIDE_RIGHT | ch::TOP_FRONT_RIGHT | ch::TOP_BACK_RIGHT => {
+                (0.0, surround)
+            }
+            ch::BACK_CENTER | ch::TOP_CENTER | ch::TOP_FRONT_CENTER | ch::TOP_BACK_CENTER => {
+                (surround * H, surround * H)
+            }
+            _ => (0.0, 0.0),
+        };
+        stereo.push(gains);
+    }
+
+    let left = stereo.iter().map(|g| g.0).collect::<Vec<_>>();
+    let right = stereo.iter().map(|g| g.1).collect::<Vec<_>>();
+    let mono = stereo.iter().map(|g| (g.0 + g.1) * 0.5).collect::<Vec<_>>();
+    Some(match (mode, out_channels) {
+        (Downmix::Mono, 2) => vec![mono.clone(), mono],
+        (_, 1) => vec![mono],
+        _ => vec![left, right],
+    })
+}
+
+// @ ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== @
+
+/// 音频滤镜链：下混 → 均衡器 → 响度归一化 → 动态范围压缩 → 限制器
+///
+/// 处理的都是重采样后的 packed f32 数据，每个音频线程持有一个实例
+pub struct AudioFilter {
+    generation: u64,
+    settings: Settings,
+    rate: u32,
+    channels: usize,
+    eq: Vec<Biquad>,
+    eq_state: Vec<Vec<BiquadState>>,
+    loudness: Option<LoudnessMeter>,
+    /// 当前的归一化增益（dB），平滑变化
+    norm_gain: f32,
+    compressor: Option<Compressor>,
+    limiter: Limiter,
+}
+
+impl AudioFilter {
+    pub fn new() -> Self {
+        Self {
+            generation: u64::MAX,
+            settings: Settings::new(),
+            rate: 0,
+            channels: 0,
+            eq: Vec::new(),
+            eq_state: Vec::new(),
+            loudness: None,
+            norm_gain: 0.0,
+            compressor: None,
+            limiter: Limiter::new(48000),
+        }
+    }
+
+    /// 重采样器应输出的声道布局：需要自行下混时保留源声道
+    pub fn resample_layout(
+        &self,
+        mode: Downmix,
+        src: ChannelLayout,
+        out: ChannelLayout,
+    ) -> ChannelLayout {
+        if downmix_matrix(mode, src, out.channels() as usize).is_some() {
+            src
+        } else {
+            out
+        }
+    }
+
+    fn rebuild(&mut self, rate: u32, channels: usize) {
+        let generation = GENERATION.load(Ordering::SeqCst);
+        if generation == self.generation && rate == self.rate && channels == self.channels {
+            return;
+        }
+        let settings = *SETTINGS.lock();
+        let format_changed = rate != self.rate || channels != self.channels;
+
+        if format_changed || settings.eq != self.settings.eq {
+            self.eq = EQ_BANDS
+                .iter()
+                .zip(settings.eq)
+                .filter(|(f0, gain)| *gain != 0.0 && **f0 < rate as f32 * 0.45)
+                .map(|(f0, gain)| Biquad::peaking(rate as f64, *f0 as f64, gain as f64, 1.41))
+                .collect();
+            self.eq_state = vec![vec![BiquadState::default(); self.eq.len()]; channels];
+        }
+        if format_changed || settings.normalize != self.settings.normalize {
+            self.loudness =
+                (settings.normalize != Normalize::Off).then(|| LoudnessMeter::new(rate, channels));
+        }
+        if format_changed || settings.drc != self.settings.drc {
+            self.compressor = settings.drc.then(|| Compressor::night(rate));
+        }
+        if format_changed {
+            self.limiter = Limiter::new(rate);
+        }
+
+        self.generation = generation;
+        self.settings = settings;
+        self.rate = rate;
+        self.channels = channels;
+    }
+
+    /// 目标归一化增益（dB）
+    fn target_norm_gain(&self) -> Option<f32> {
+        let settings = &self.settings;
+        if settings.normalize == Normalize::Off {
+            return None;
+        }
+        if let Some((gain, peak)) = REPLAYGAIN.lock().select(settings.normalize) {
+            let gain = gain + settings.target - REPLAYGAIN_REFERENCE;
+            // 有峰值信息时避免削波
+            let limit = peak
+                .filter(|p| *p > 0.0)
+                .map(|p| -20.0 * p.log10())
+                .unwrap_or(f32::MAX);
+            return Some(gain.min(limit));
+        }
+        let loudness = self.loudness.as_ref()?.integrated()?;
+        Some((settings.target - loudness).clamp(-20.0, 12.0))
+    }
+
+    fn is_active(&self) -> bool {
+        !self.eq.is_empty() || self.loudness.is_some() || self.compressor.is_some()
+    }
+
+    /// 处理一帧音频，`out_layout` 为输出设备的声道布局，`mode` 须与 `resample_layout` 用的一致
+    pub fn process(
+        &mut self,
+        frame: AudioFrame,
+        mode: Downmix,
+        out_layout: ChannelLayout,
+        rate: u32,
+    ) -> AudioFrame {
+        let matrix = downmix_matrix(mode, frame.channel_layout(), out_layout.channels() as usize);
+        let downmixed = matrix.is_some();
+        let mut frame = match matrix {
+            Some(matrix) => downmix(&frame, &matrix, out_layout, rate),
+            None => frame,
+        };
+
+        let channels = frame.channel_layout().channels() as usize;
+        if channels == 0 {
+            return frame;
+        }
+        self.rebuild(rate, channels);
+        if !self.is_active() && !downmixed {
+            return frame;
+        }
+
+        let len = frame.samples() * channels;
+        let data = frame.data_mut(0);
+        let samples = unsafe { std::slice::from_raw_parts_mut(data.as_mut_ptr() as *mut f32, len) };
+
+        // 均衡器
+        if !self.eq.is_empty() {
+            for sample_frame in samples.chunks_exact_mut(channels) {
+                for (x, state) in sample_frame.iter_mut().zip(self.eq_state.iter_mut()) {
+                    let mut y = *x as f64;
+                    for (f, s) in self.eq.iter().zip(state.iter_mut()) {
+                        y = s.process(f, y);
+                    }
+                    *x = y as f32;
+                }
+            }
+        }
+
+        // 响度归一化：先测量，再平滑地逼近目标增益
+        if let Some(meter) = self.loudness.as_mut() {
+            for sample_frame in samples.chunks_exact(channels) {
+                meter.push(sample_frame);
+            }
+        }
+        let target = self.target_norm_gain().unwrap_or(0.0);
+        let frames = (len / channels) as f32;
+        let coef = 1.0 - (-frames / (rate as f32 * 2.0)).exp();
+        self.norm_gain += (target - self.norm_gain) * coef;
+        let norm = db_to_linear(self.norm_gain);
+        hint_norm_gain(self.norm_gain());
+
+        for sample_frame in samples.chunks_exact_mut(channels) {
+            let mut gain = norm;
+            let peak = sample_frame.iter().fold(0.0f32, |m, v| m.max(v.abs())) * gain;
+            let mut peak_after = peak;
+            if let Some(compressor) = self.compressor.as_mut() {
+                let g = compressor.gain(peak);
+                gain *= g;
+                peak_after *= g;
+            }
+            gain *= self.limiter.gain(peak_after);
+            for x in sample_frame.iter_mut() {
+                *x *= gain;
+            }
+        }
+
+        frame
+    }
+
+    /// 当前归一化增益（dB），用于显示
+    pub fn norm_gain(&self) -> Option<f32> {
+        (self.settings.normalize != Normalize::Off).then_some(self.norm_gain)
+    }
+}
+
+impl Default for AudioFilter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn downmix(
+    src: &AudioFrame,
+    matrix: &[Vec<f32>],
+    out_layout: ChannelLayout,
+    rate: u32,
+) -> AudioFrame {
+    let src_channels = src.channel_layout().channels() as usize;
+    let out_channels = out_layout.channels() as usize;
+    let samples = src.samples();
+    let input = unsafe {
+        std::slice::from_raw_parts(src.data(0).as_ptr() as *const f32, samples * src_channels)
+    };
+
+    let mut out = AudioFrame::new(Sample::F32(SampleType::Packed), samples, out_layout);
+    out.set_rate(rate);
+    out.set_pts(src.pts());
+    let data = out.data_mut(0);
+    let output = unsafe {
+        std::slice::from_raw_parts_mut(data.as_mut_ptr() as *mut f32, samples * out_channels)
+    };
+
+    for (i, o) in input
+        .chunks_exact(src_channels)
+        .zip(output.chunks_exact_mut(out_channels))
+    {
+        for (o, row) in o.iter_mut().zip(matrix) {
+            *o = i.iter().zip(row).map(|(x, g)| x * g).sum();
+        }
+    }
+    out
+}
+
+// @ ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== @
+
+/// 当前归一化增益（dB），由音频线程更新
+static NORM_GAIN: Mutex<Option<f32>> = Mutex::new(None);
+
+pub fn hint_norm_gain(gain: Option<f32>) {
+    *NORM_GAIN.lock() = gain;
+}
+
+/// 滤镜状态的简短描述，用于叠加文本和 `/af`
+pub fn status() -> String {
+    let settings = *SETTINGS.lock();
+    let mut parts = Vec::new();
+    if settings.eq != [0.0; 10] {
+        parts.push(format!("eq={}", eq_name(&settings.eq)));
+    }
+    if settings.normalize != Normalize::Off {
+        let gain = NORM_GAIN
+            .lock()
+            .map(|g| format!("({g:+.1}dB)"))
+            .unwrap_or_default();
+        parts.push(format!(
+            "norm={}@{}LUFS{}",
+            settings.normalize.name(),
+            settings.target,
+            gain
+        ));
+    }
+    if settings.drc {
+        parts.push("drc=night".to_string());
+    }
+    if settings.downmix != Downmix::Auto {
+        parts.push(format!("downmix={}", settings.downmix.name()));
+    }
+    if parts.is_empty() {
+        "off".to_string()
+    } else {
+        parts.join(" ")
+    }
+}
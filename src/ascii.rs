@@ -0,0 +1,107 @@
+use parking_lot::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// @ ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== @
+
+/// 预设的亮度字符序列，从暗到亮
+pub const RAMPS: &[(&str, &str)] = &[
+    ("standard", " .:-=+*#%@"),
+    (
+        "detailed",
+        " .'`^\",:;Il!i><~+_-?][}{1)(|\\/tfjrxnuvczXYUJCLQ0OZmwqpdbkhao*#MW&8%B@$",
+    ),
+    ("simple", " .oO@"),
+    ("blocks", " ░▒▓█"),
+];
+
+/// 梯度幅值超过此值时输出边缘字符，亮度范围为 0 到 255
+const EDGE_THRESHOLD: f32 = 96.0;
+
+static RAMP: Mutex<Vec<char>> = Mutex::new(Vec::new());
+static EDGES: AtomicBool = AtomicBool::new(true);
+
+/// 解析预设名称，或直接使用至少两个字符的自定义序列
+pub fn parse_ramp(value: &str) -> Option<Vec<char>> {
+    if value.is_empty() {
+        return Some(RAMPS[0].1.chars().collect());
+    }
+    let name = value.trim().to_lowercase();
+    if let Some((_, ramp)) = RAMPS.iter().find(|(n, _)| *n == name) {
+        return Some(ramp.chars().collect());
+    }
+    let ramp: Vec<char> = value.chars().filter(|c| !c.is_control()).collect();
+    (ramp.len() >= 2).then_some(ramp)
+}
+
+pub fn set_ramp(ramp: Vec<char>) {
+    *RAMP.lock() = ramp;
+}
+
+pub fn set_edges(edges: bool) {
+    EDGES.store(edges, Ordering::SeqCst);
+}
+
+// @ ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== @
+
+/// 一帧内使用的字符设置，避免每个单元格都加锁
+pub struct Glyphs {
+    ramp: Vec<char>,
+    edges: bool,
+}
+
+impl Glyphs {
+    pub fn current() -> Self {
+        let ramp = RAMP.lock().clone();
+        let ramp = if ramp.len() < 2 {
+            RAMPS[0].1.chars().collect()
+        } else {
+            ramp
+        };
+        Self {
+            ramp,
+            edges: EDGES.load(Ordering::SeqCst),
+        }
+    }
+
+    /// 为 `(x, y)` 处的像素选择字符
+    /// - `lum` 是 `w` × `h` 的亮度图，行距为 `w`
+    /// - 边缘明显时按梯度方向选择 `/ \ | _ -`，否则按亮度取序列中的字符
+    pub fn pick(&self, lum: &[u8], w: usize, h: usize, x: usize, y: usize) -> char {
+        let at = |dx: isize, dy: isize| {
+            let x = (x as isize + dx).clamp(0, w as isize - 1) as usize;
+            let y = (y as isize + dy).clamp(0, h as isize - 1) as usize;
+            lum[y * w + x] as f32
+        };
+        if self.edges {
+            // Sobel 算子，y 轴向下
+            let gx =
+                at(1, -1) + 2.0 * at(1, 0) + at(1, 1) - at(-1, -1) - 2.0 * at(-1, 0) - at(-1, 1);
+            let gy =
+                at(-1, 1) + 2.0 * at(0, 1) + at(1, 1) - at(-1, -1) - 2.0 * at(0, -1) - at(1, -1);
+            if gx.hypot(gy) / 4.0 > EDGE_THRESHOLD {
+                return edge_glyph(gx, gy, at(0, -1), at(0, 0), at(0, 1));
+            }
+        }
+        let index = lum[y * w + x] as usize * self.ramp.len() / 256;
+        self.ramp[index]
+    }
+}
+
+/// 边缘方向与梯度方向垂直
+/// - 水平边缘更靠近下方的像素时用 `_`，否则用 `-`
+fn edge_glyph(gx: f32, gy: f32, up: f32, center: f32, down: f32) -> char {
+    // 梯度方向折叠到 0 到 180 度
+    let mut angle = gy.atan2(gx).to_degrees();
+    if angle < 0.0 {
+        angle += 180.0;
+    }
+    match angle {
+        a if !(22.5..157.5).contains(&a) => '|',
+        a if a < 67.5 => '/',
+        a if a < 112.5 => match (down - center).abs() > (center - up).abs() {
+            true => '_',
+            false => '-',
+        },
+        _ => '\\',
+    }
+}
@@ -5,14 +5,17 @@ use av::{ChannelLayout, software::resampling::context::Context as Resampler};
 use cpal::SampleFormat;
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
 use parking_lot::{Condvar, Mutex};
-use std::collections::VecDeque;
+use std::collections::{BTreeMap, VecDeque};
 use std::mem::MaybeUninit;
 use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering};
 use std::time::Duration;
 
+use crate::afilter::{self, AudioFilter};
+use crate::avsync::{self, MasterClock};
 use crate::ffmpeg::{AUDIO_TIME_BASE, DECODER_WAKEUP, DECODER_WAKEUP_MUTEX};
+use crate::playlist::PLAYLIST;
 use crate::term::TERM_QUIT;
-use crate::{avsync, ffmpeg};
+use crate::visualizer;
 
 static PLAYED_SAMPLES: AtomicU64 = AtomicU64::new(0);
 static AUDIO_SAMPLERATE: AtomicU64 = AtomicU64::new(0);
@@ -45,12 +48,26 @@ pub fn hint_seeked() {
     HINT_SEEKED.store(true, Ordering::SeqCst);
 }
 
+/// 当前曲目的编号，用于区分缓冲区中上一曲留下的数据
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// 开始播放新的曲目，缓冲区中之前的数据不再推进播放时间
+pub fn begin_item() {
+    GENERATION.fetch_add(1, Ordering::SeqCst);
+}
+
 struct AudioFrameWrapper {
     ts: Duration,
     af: AudioFrame,
     cons: usize,
     prev_ts: Option<Duration>,
     next_ts: Option<Duration>,
+    /// 所属曲目的编号
+    generation: u64,
+    /// 是否为交叉淡化时留给下一曲叠加的尾巴
+    tail: bool,
+    /// 已经叠加了下一曲的采样数
+    mixed: usize,
 }
 
 impl AudioFrameWrapper {
@@ -61,6 +78,9 @@ impl AudioFrameWrapper {
             cons: 0,
             prev_ts: None,
             next_ts: None,
+            generation: GENERATION.load(Ordering::SeqCst),
+            tail: false,
+            mixed: 0,
         }
     }
 
@@ -85,26 +105,13 @@ impl AudioFrameWrapper {
         self.cons += n;
     }
 
-    fn calc_volume(&self) -> f32 {
-        let data = self.af.data(0);
-        let nb_samples = self.af.samples();
-        let channels = self.af.channel_layout().channels() as usize;
-        let len = nb_samples * channels;
-        let slice = unsafe { std::slice::from_raw_parts(data.as_ptr() as *const f32, len) };
-        let mut max = 0.0f32;
-        for &v in slice.iter() {
-            let av = v.abs();
-            if av > max {
-                max = av;
-            }
-        }
-        max * max
+    fn samples_mut(&mut self) -> &mut [f32] {
+        let len = self.full_len();
+        let data = self.af.data_mut(0);
+        unsafe { std::slice::from_raw_parts_mut(data.as_mut_ptr() as *mut f32, len) }
     }
 }
 
-pub static AUDIO_VOLUME_STATISTICS: Mutex<VecDeque<f32>> = Mutex::new(VecDeque::new());
-pub const AUDIO_VOLUME_STATISTICS_LEN: usize = 128;
-
 static AUDIO_BUFFER: Mutex<VecDeque<AudioFrameWrapper>> = Mutex::new(VecDeque::new());
 static AUDIO_CONSUMED: Condvar = Condvar::new();
 
@@ -122,11 +129,145 @@ pub fn get_volume() -> f32 {
     f32::from_bits(VOLUME.load(Ordering::Relaxed))
 }
 
+pub fn set_volume(volume: f32) {
+    let volume = volume.clamp(0.0, 2.0);
+    VOLUME.store(volume.to_bits(), Ordering::Relaxed);
+    VOLUME_K.store((volume * volume).to_bits(), Ordering::Relaxed);
+}
+
 pub fn adjust_volume(delta: f32) {
-    let mut cur = f32::from_bits(VOLUME.load(Ordering::Relaxed));
-    cur = (cur + delta).clamp(0.0, 2.0);
-    VOLUME.store(cur.to_bits(), Ordering::Relaxed);
-    VOLUME_K.store((cur * cur).to_bits(), Ordering::Relaxed);
+    set_volume(get_volume() + delta);
+}
+
+/// 指定的音频输出设备名称，`None` 表示使用默认设备
+static OUTPUT_DEVICE: Mutex<Option<String>> = Mutex::new(None);
+/// 当前正在使用的音频输出设备名称，`None` 表示没有可用的设备
+static CURRENT_DEVICE: Mutex<Option<String>> = Mutex::new(None);
+/// 请求重建音频输出流（切换设备或设备断开）
+static REBUILD_STREAM: AtomicBool = AtomicBool::new(false);
+
+/// 设置音频输出设备，播放中会立即切换
+pub fn set_output_device(name: Option<String>) {
+    let name = name.filter(|n| !n.is_empty());
+    let mut lock = OUTPUT_DEVICE.lock();
+    if *lock != name {
+        *lock = name;
+        REBUILD_STREAM.store(true, Ordering::SeqCst);
+    }
+}
+
+/// 当前正在使用的音频输出设备名称
+pub fn current_device_name() -> Option<String> {
+    CURRENT_DEVICE.lock().clone()
+}
+
+fn device_name(device: &cpal::Device) -> Option<String> {
+    device
+        .description()
+        .ok()
+        .map(|desc| desc.name().to_string())
+}
+
+/// 列出所有音频输出设备的名称
+pub fn output_device_names() -> Vec<String> {
+    let host = cpal::default_host();
+    host.output_devices()
+        .map(|devices| devices.filter_map(|d| device_name(&d)).collect())
+        .unwrap_or_default()
+}
+
+/// 默认音频输出设备的名称
+pub fn default_device_name() -> Option<String> {
+    cpal::default_host()
+        .default_output_device()
+        .and_then(|d| device_name(&d))
+}
+
+/// 查找音频输出设备，找不到指定设备时回退到默认设备
+fn find_output_device(host: &cpal::Host) -> Option<cpal::Device> {
+    if let Some(name) = OUTPUT_DEVICE.lock().clone() {
+        let found = host
+            .output_devices()
+            .ok()
+            .and_then(|mut devices| devices.find(|d| device_name(d).is_some_and(|n| n == name)));
+        if found.is_some() {
+            return found;
+        }
+        warning_f16n!("Audio device not found, using default: {}", name);
+    }
+    host.default_output_device()
+}
+
+/// 没有单独设置的设备使用的音频延迟（秒）
+static DEFAULT_AUDIO_DELAY: Mutex<f64> = Mutex::new(0.0);
+/// 各输出设备的音频延迟（秒）
+static DEVICE_AUDIO_DELAYS: Mutex<BTreeMap<String, f64>> = Mutex::new(BTreeMap::new());
+
+/// 设置各设备的音频延迟，并应用到当前设备
+pub fn set_audio_delays(default: f64, delays: BTreeMap<String, f64>) {
+    *DEFAULT_AUDIO_DELAY.lock() = default;
+    *DEVICE_AUDIO_DELAYS.lock() = delays;
+    apply_device_audio_delay();
+}
+
+/// 把当前设备的音频延迟交给同步模块
+fn apply_device_audio_delay() {
+    let delay = current_device_name()
+        .and_then(|name| DEVICE_AUDIO_DELAYS.lock().get(&name).copied())
+        .unwrap_or(*DEFAULT_AUDIO_DELAY.lock());
+    avsync::set_audio_delay(delay);
+}
+
+/// 设置当前设备的音频延迟（秒），并保存到配置文件
+pub fn set_audio_delay(secs: f64) {
+    let secs = (secs.clamp(-5.0, 5.0) * 1000.0).round() / 1000.0;
+    avsync::set_audio_delay(secs);
+    match current_device_name() {
+        Some(name) => {
+            DEVICE_AUDIO_DELAYS.lock().insert(name.clone(), secs);
+            #[cfg(feature = "config")]
+            if let Err(err) = crate::config::set_device_audio_delay(&name, secs) {
+                warning_f16n!("Failed to save config: {}", err);
+            }
+        }
+        None => *DEFAULT_AUDIO_DELAY.lock() = secs,
+    }
+    info_f16n!("Audio Delay: {}ms", (secs * 1000.0).round() as i64);
+}
+
+pub fn adjust_audio_delay(delta: f64) {
+    set_audio_delay(avsync::audio_delay() + delta);
+}
+
+/// 是否处于延迟校准模式：每秒开头播放提示音并闪白画面
+static CALIBRATING: AtomicBool = AtomicBool::new(false);
+
+pub fn toggle_calibration() -> bool {
+    !CALIBRATING.fetch_xor(true, Ordering::SeqCst)
+}
+
+pub fn is_calibrating() -> bool {
+    CALIBRATING.load(Ordering::SeqCst)
+}
+
+/// 校准提示音（以及画面闪烁）在每秒开头持续的时长（秒）
+pub const CALIBRATION_PULSE: f64 = 0.1;
+
+/// 校准模式下用提示音替换音频：每秒开头一段 1kHz 正弦波
+fn write_calibration_beep(frame: &mut AudioFrame, start: Duration, rate: u32) {
+    let channels = frame.channel_layout().channels() as usize;
+    let len = frame.samples() * channels;
+    let data = frame.data_mut(0);
+    let samples = unsafe { std::slice::from_raw_parts_mut(data.as_mut_ptr() as *mut f32, len) };
+    for (i, sample_frame) in samples.chunks_exact_mut(channels).enumerate() {
+        let t = start.as_secs_f64() + i as f64 / rate as f64;
+        let v = if t.fract() < CALIBRATION_PULSE {
+            (std::f64::consts::TAU * 1000.0 * t).sin() as f32 * 0.5
+        } else {
+            0.0
+        };
+        sample_frame.fill(v);
+    }
 }
 
 macro_rules! data_callback {
@@ -146,9 +287,12 @@ macro_rules! data_callback {
             let mut add = None;
             let mut i = 0;
             let mut buf = AUDIO_BUFFER.lock();
+            let mut tap = visualizer::pcm_tap(channels as usize, sr as u32);
+            let generation = GENERATION.load(Ordering::SeqCst);
             while let Some(mut wrap) = buf.pop_front() {
-                let wrap_ptr = &mut wrap as *mut AudioFrameWrapper;
-                if let Some(d) = wrap.timestamp() {
+                // 上一曲剩下的数据照常播放，但不推进当前曲目的播放时间
+                let current = wrap.generation == generation;
+                if let Some(d) = wrap.timestamp().filter(|_| current) {
                     dur = Some(d);
                     add = None;
                 }
@@ -173,6 +317,9 @@ macro_rules! data_callback {
                 assert!(slice_begin % channels as usize == 0);
                 if prev_skiped || next_skiped {
                     for (j, &v) in wrap.slice().iter().enumerate() {
+                        if let Some(tap) = tap.as_mut() {
+                            tap.push(v);
+                        }
                         let k = (slice_begin + j) as f32 / slice_full_len as f32;
                         let mut v = v * volume_k;
                         if prev_skiped {
@@ -186,19 +333,22 @@ macro_rules! data_callback {
                         if i == data.len() {
                             let n = j + 1;
                             wrap.consume(n);
-                            add = Some(n as u64 / channels as u64);
+                            add = current.then_some(n as u64 / channels as u64);
                             buf.push_front(wrap);
                             break;
                         }
                     }
                 } else {
                     for (j, &v) in wrap.slice().iter().enumerate() {
+                        if let Some(tap) = tap.as_mut() {
+                            tap.push(v);
+                        }
                         data[i] = ($expr)(v * volume_k);
                         i += 1;
                         if i == data.len() {
                             let n = j + 1;
                             wrap.consume(n);
-                            add = Some(n as u64 / channels as u64);
+                            add = current.then_some(n as u64 / channels as u64);
                             buf.push_front(wrap);
                             break;
                         }
@@ -207,12 +357,6 @@ macro_rules! data_callback {
                 if i == data.len() {
                     break;
                 }
-                let vol = unsafe { (*wrap_ptr).calc_volume() };
-                let mut stat = AUDIO_VOLUME_STATISTICS.lock();
-                while stat.len() >= AUDIO_VOLUME_STATISTICS_LEN {
-                    stat.pop_front();
-                }
-                stat.push_back(vol);
             }
             assert!(i <= data.len() && i % channels as usize == 0);
             AUDIO_BUFFER_LEN.fetch_sub(i / channels as usize, Ordering::SeqCst);
@@ -235,7 +379,15 @@ macro_rules! build_output_stream {
         $device.build_output_stream(
             config,
             data_callback!(channels, $ty, $default, $expr),
-            |_| { /* ignore */ },
+            |err| {
+                // 设备断开（如蓝牙耳机）时重建输出流
+                if matches!(
+                    err,
+                    cpal::StreamError::DeviceNotAvailable | cpal::StreamError::StreamInvalidated
+                ) {
+                    REBUILD_STREAM.store(true, Ordering::SeqCst);
+                }
+            },
             None,
         )
     }};
@@ -277,7 +429,7 @@ fn build_cpal_stream(
         SampleFormat::U32 => build_output_stream!(device, config, u32, 2147483648, |v: f32| {
             (unorm!(v) * u32::MAX as f32) as u32
         }),
-        _ => unimplemented!(
+        _ => anyhow::bail!(
             "{}",
             f16n!("Unsupported sample format: {:?}", config.sample_format())
         ),
@@ -291,56 +443,251 @@ fn build_cpal_stream(
 pub static AUDIO_FRAME: Mutex<Option<AudioFrame>> = Mutex::new(None);
 pub static AUDIO_FRAME_SIG: Condvar = Condvar::new();
 
-pub fn audio_main() {
+/// 输出设备声道数对应的声道布局
+fn channel_layout_for(channels: u16) -> Option<ChannelLayout> {
+    match channels {
+        1 => Some(ChannelLayout::MONO),
+        2 => Some(ChannelLayout::STEREO),
+        3 => Some(ChannelLayout::SURROUND),
+        4 => Some(ChannelLayout::QUAD),
+        5 => Some(ChannelLayout::_4POINT1),
+        6 => Some(ChannelLayout::_5POINT1),
+        7 => Some(ChannelLayout::_6POINT1),
+        8 => Some(ChannelLayout::_7POINT1),
+        _ => None,
+    }
+}
+
+/// 已打开的音频输出
+struct AudioOutput {
+    /// 持有输出流，drop 时停止播放
+    #[allow(unused)]
+    stream: cpal::Stream,
+    channels: u16,
+    sample_rate: u32,
+}
+
+impl AudioOutput {
+    fn channel_layout(&self) -> ChannelLayout {
+        // 打开时已经检查过声道数
+        channel_layout_for(self.channels).unwrap_or(ChannelLayout::STEREO)
+    }
+}
+
+/// 跨曲目保留的音频输出，切换曲目时不必重新打开设备
+static OUTPUT: Mutex<Option<AudioOutput>> = Mutex::new(None);
+
+/// 打开音频输出设备并开始播放
+fn open_output() -> Result<AudioOutput> {
     let host = cpal::default_host();
-    let device = host
-        .default_output_device()
-        .context(l10n!("No default output audio device"))
-        .unwrap();
-    let config = device.default_output_config().unwrap();
-    let target_channels = config.channels();
-    let target_sample_fmt = Sample::F32(SampleType::Packed);
-    let target_sample_rate = config.sample_rate();
-    let cpal_stream = build_cpal_stream(&device, &config).unwrap();
-    if target_sample_rate == 0 {
-        error_l10n!("Invalid audio sample rate: 0");
-        error_l10n!("Quiting audio thread");
-        ffmpeg::notify_quit();
-        return;
+    let device = find_output_device(&host).context(l10n!("No default output audio device"))?;
+    let config = device.default_output_config()?;
+    let channels = config.channels();
+    let sample_rate = config.sample_rate();
+    if sample_rate == 0 {
+        anyhow::bail!("{}", l10n!("Invalid audio sample rate: 0"));
     }
-    PLAYED_SAMPLES.store(0, Ordering::SeqCst);
-    AUDIO_SAMPLERATE.store(target_sample_rate as u64, Ordering::SeqCst);
+    if channel_layout_for(channels).is_none() {
+        anyhow::bail!("{}", f16n!("Unsupported channel count: {}", channels));
+    }
+    let stream = build_cpal_stream(&device, &config)?;
+
+    // 输出格式可能变化，缓冲区里已经转换好的数据不能再用
     AUDIO_BUFFER.lock().clear();
     AUDIO_BUFFER_LEN.store(0, Ordering::SeqCst);
-    cpal_stream.play().unwrap();
-    set_vtime(Duration::ZERO);
-
-    let target_channel_layout = match target_channels {
-        1 => ChannelLayout::MONO,
-        2 => ChannelLayout::STEREO,
-        3 => ChannelLayout::SURROUND,
-        4 => ChannelLayout::QUAD,
-        5 => ChannelLayout::_4POINT1,
-        6 => ChannelLayout::_5POINT1,
-        7 => ChannelLayout::_6POINT1,
-        8 => ChannelLayout::_7POINT1,
-        _ => {
-            error_f16n!("Unsupported channel count: {}", target_channels);
-            error_l10n!("Quiting audio thread");
-            ffmpeg::notify_quit();
-            return;
+    AUDIO_SAMPLERATE.store(sample_rate as u64, Ordering::SeqCst);
+    stream.play()?;
+
+    *CURRENT_DEVICE.lock() = device_name(&device);
+    apply_device_audio_delay();
+    Ok(AudioOutput {
+        stream,
+        channels,
+        sample_rate,
+    })
+}
+
+/// 打开音频输出，失败时退回到仅视频播放（由墙上时钟驱动同步）
+fn open_output_or_fallback() -> Option<AudioOutput> {
+    match open_output() {
+        Ok(output) => {
+            avsync::set_audio_output(true);
+            Some(output)
+        }
+        Err(err) => {
+            *CURRENT_DEVICE.lock() = None;
+            warning_f16n!("Audio output unavailable, playing without sound: {}", err);
+            avsync::set_audio_output(false);
+            None
+        }
+    }
+}
+
+/// 没有音频输出时，按同步时钟丢弃音频帧
+fn wait_until_played(ts: Duration) {
+    const LEAD: Duration = Duration::from_millis(100);
+    while TERM_QUIT.load(Ordering::SeqCst) == false
+        && !avsync::decode_ended()
+        && !HINT_SEEKED.load(Ordering::SeqCst)
+        && !REBUILD_STREAM.load(Ordering::SeqCst)
+    {
+        if avsync::played_time_or_zero() + LEAD >= ts {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
+
+// @ ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== @
+
+/// 交叉淡化时长（秒），0 表示关闭
+static CROSSFADE: Mutex<f64> = Mutex::new(0.0);
+/// 上一曲留在缓冲区里的淡出尾巴的时长（秒），等待下一曲叠加
+static CROSSFADE_TAIL: Mutex<Option<f64>> = Mutex::new(None);
+
+pub fn set_crossfade(secs: f64) {
+    *CROSSFADE.lock() = secs.clamp(0.0, 10.0);
+}
+
+/// 当前曲目结尾是否要和下一曲交叉淡化
+/// - 只对没有视频的文件生效，否则会截掉画面的结尾
+fn crossfade_for_current() -> Option<f64> {
+    let secs = *CROSSFADE.lock();
+    let total = avsync::total_duration().as_secs_f64();
+    let has_next = PLAYLIST.lock().peek_next().is_some();
+    (secs > 0.0 && total > secs * 2.0 && !avsync::has_video() && has_next).then_some(secs)
+}
+
+/// 按采样的播放时间（秒）给帧乘上增益
+fn apply_gain(frame: &mut AudioFrame, start: Duration, rate: u32, gain: impl Fn(f64) -> f32) {
+    let channels = frame.channel_layout().channels() as usize;
+    let len = frame.samples() * channels;
+    let data = frame.data_mut(0);
+    let samples = unsafe { std::slice::from_raw_parts_mut(data.as_mut_ptr() as *mut f32, len) };
+    for (i, sample_frame) in samples.chunks_exact_mut(channels).enumerate() {
+        let k = gain(start.as_secs_f64() + i as f64 / rate as f64);
+        sample_frame.iter_mut().for_each(|v| *v *= k);
+    }
+}
+
+/// 把新曲目开头的采样叠加到缓冲区里上一曲淡出的尾巴上，返回已经叠加的采样数
+/// - 从头开始叠加的帧改记为新曲目的帧，使用新曲目的时间戳
+fn mix_into_tail(
+    buf: &mut VecDeque<AudioFrameWrapper>,
+    samples: &[f32],
+    channels: usize,
+    rate: u32,
+    ts: Duration,
+) -> usize {
+    let generation = GENERATION.load(Ordering::SeqCst);
+    let mut done = 0;
+    for wrap in buf.iter_mut().filter(|w| w.tail) {
+        if done == samples.len() {
+            break;
+        }
+        let start = wrap.mixed.max(wrap.cons);
+        let full = wrap.full_len();
+        if start >= full {
+            continue;
+        }
+        if start == 0 {
+            wrap.generation = generation;
+            wrap.ts = ts + Duration::from_secs_f64((done / channels) as f64 / rate as f64);
+            wrap.prev_ts = None;
+            wrap.next_ts = None;
+        }
+        let n = (full - start).min(samples.len() - done);
+        let data = wrap.samples_mut();
+        for (d, v) in data[start..start + n]
+            .iter_mut()
+            .zip(&samples[done..done + n])
+        {
+            *d += *v;
+        }
+        wrap.mixed = start + n;
+        done += n;
+    }
+    done
+}
+
+/// 去掉帧开头的 `skip` 个采样（交错计数）
+fn skip_samples(frame: &AudioFrame, skip: usize, channels: usize) -> AudioFrame {
+    let remaining = frame.samples() - skip / channels;
+    let mut out = AudioFrame::new(frame.format(), remaining, frame.channel_layout());
+    out.set_rate(frame.rate());
+    let len = frame.samples() * channels;
+    let src = unsafe { std::slice::from_raw_parts(frame.data(0).as_ptr() as *const f32, len) };
+    let data = out.data_mut(0);
+    let dst = unsafe {
+        std::slice::from_raw_parts_mut(data.as_mut_ptr() as *mut f32, remaining * channels)
+    };
+    dst.copy_from_slice(&src[skip..]);
+    out
+}
+
+// @ ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== @
+
+/// 音频漂移在此范围内不做重采样校正（秒）
+const AUDIO_DRIFT_DEAD_ZONE: f64 = 0.01;
+/// 每帧校正的漂移比例
+const AUDIO_DRIFT_GAIN: f64 = 0.1;
+/// 重采样校正时最大的速度偏差
+const AUDIO_MAX_STRETCH: f64 = 0.05;
+
+pub fn audio_main() {
+    let target_sample_fmt = Sample::F32(SampleType::Packed);
+
+    PLAYED_SAMPLES.store(0, Ordering::SeqCst);
+    // 沿用上一曲的输出流，缓冲区里剩下的数据会无缝接上
+    let mut output = OUTPUT.lock().take();
+    if REBUILD_STREAM.swap(false, Ordering::SeqCst) {
+        drop(output.take());
+    }
+    let mut output = match output {
+        Some(output) => {
+            avsync::set_audio_output(true);
+            Some(output)
         }
+        None => open_output_or_fallback(),
     };
+    if output.is_some() {
+        set_vtime(Duration::ZERO);
+    }
+
+    // 上一曲留下了淡出的尾巴时，新曲目的开头淡入并叠加上去
+    let mut fade_in = CROSSFADE_TAIL.lock().take();
+    let mut fade_out = None;
 
     let mut resampler = MaybeUninit::uninit();
 
     let mut resampler_format = None;
     let mut resampler_layout = None;
     let mut resampler_rate = None;
+    let mut resampler_target = None;
+    let mut compensating = false;
+
+    let mut filter = AudioFilter::new();
 
     let mut last_frametime = None;
 
     while TERM_QUIT.load(Ordering::SeqCst) == false {
+        if REBUILD_STREAM.swap(false, Ordering::SeqCst) {
+            // 记下当前播放位置，新的输出流从这里继续
+            let position = avsync::played_time_or_zero();
+            drop(output.take());
+            output = open_output_or_fallback();
+            fade_out = None;
+            if output.is_some() {
+                set_vtime(position);
+                info_f16n!(
+                    "Audio Device: {}",
+                    current_device_name().unwrap_or_default()
+                );
+            }
+            resampler_format = None;
+            last_frametime = None;
+        }
+
         let frame = {
             let mut lock = AUDIO_FRAME.lock();
             while lock.is_none() && TERM_QUIT.load(Ordering::SeqCst) == false {
@@ -369,11 +716,30 @@ pub fn audio_main() {
         if HINT_SEEKED.swap(false, Ordering::SeqCst) {
             AUDIO_BUFFER.lock().clear();
             AUDIO_BUFFER_LEN.store(0, Ordering::SeqCst);
+            fade_in = None;
+            fade_out = None;
+        }
+
+        let Some(out) = output.as_ref() else {
+            wait_until_played(frametime);
+            continue;
+        };
+
+        // 音频不是主时钟时，丢弃已经过时的帧
+        let frame_duration =
+            Duration::from_secs_f64(frame.samples() as f64 / frame.rate().max(1) as f64);
+        if avsync::frame_too_late(frametime, frame_duration, MasterClock::Audio) {
+            continue;
         }
 
+        // 需要自行下混时，重采样器保留源声道布局
+        let out_layout = out.channel_layout();
+        let downmix = afilter::downmix_mode();
+        let target_layout = filter.resample_layout(downmix, frame.channel_layout(), out_layout);
         if Some(frame.format()) != resampler_format
             || Some(frame.channel_layout()) != resampler_layout
             || Some(frame.rate()) != resampler_rate
+            || Some(target_layout) != resampler_target
         {
             resampler = MaybeUninit::new(
                 Resampler::get(
@@ -381,8 +747,8 @@ pub fn audio_main() {
                     frame.channel_layout(),
                     frame.rate(),
                     target_sample_fmt,
-                    target_channel_layout,
-                    target_sample_rate,
+                    target_layout,
+                    out.sample_rate,
                 )
                 .context(l10n!("Could not create resampler"))
                 .unwrap(),
@@ -390,6 +756,29 @@ pub fn audio_main() {
             resampler_format = Some(frame.format());
             resampler_layout = Some(frame.channel_layout());
             resampler_rate = Some(frame.rate());
+            resampler_target = Some(target_layout);
+            compensating = false;
+        }
+
+        // 音频不是主时钟时，通过重采样微调播放速度来追赶同步时钟
+        if let Some(drift) = avsync::audio_drift() {
+            let out_samples = (frame.samples() as u64 * out.sample_rate as u64
+                / frame.rate().max(1) as u64) as i32;
+            let delta = if drift.abs() > AUDIO_DRIFT_DEAD_ZONE {
+                let max = out_samples as f64 * AUDIO_MAX_STRETCH;
+                (drift * AUDIO_DRIFT_GAIN * out.sample_rate as f64).clamp(-max, max) as i32
+            } else {
+                0
+            };
+            if delta != 0 || compensating {
+                let distance = if delta != 0 { out_samples } else { 0 };
+                let resampler = unsafe { resampler.assume_init_mut() };
+                unsafe { av::ffi::swr_set_compensation(resampler.as_mut_ptr(), delta, distance) };
+                compensating = delta != 0;
+                if compensating {
+                    avsync::record_audio_stretch();
+                }
+            }
         }
 
         let mut converted = AudioFrame::empty();
@@ -397,23 +786,89 @@ pub fn audio_main() {
             .run(&frame, &mut converted)
             .context(l10n!("resampler run failed"))
             .unwrap();
+        let mut converted = filter.process(converted, downmix, out_layout, out.sample_rate);
+        if is_calibrating() {
+            write_calibration_beep(&mut converted, frametime, out.sample_rate);
+        }
+
+        // 进入结尾的淡出区间后，剩下的部分不再等待播放，直接留在缓冲区里给下一曲叠加
+        let total = avsync::total_duration();
+        if fade_out.is_none()
+            && let Some(secs) = crossfade_for_current()
+            && frametime + Duration::from_secs_f64(secs) >= total
+        {
+            fade_out = Some(secs);
+        }
+        if let Some(secs) = fade_out {
+            let end = total.as_secs_f64();
+            apply_gain(&mut converted, frametime, out.sample_rate, |t| {
+                ((end - t) / secs).clamp(0.0, 1.0) as f32
+            });
+        }
+        let mut frametime = frametime;
+        let mut buf = AUDIO_BUFFER.lock();
+        if let Some(secs) = fade_in {
+            apply_gain(&mut converted, frametime, out.sample_rate, |t| {
+                (t / secs).clamp(0.0, 1.0) as f32
+            });
+            let channels = out.channels as usize;
+            let len = converted.samples() * channels;
+            let samples = unsafe {
+                std::slice::from_raw_parts(converted.data(0).as_ptr() as *const f32, len)
+            };
+            let done = mix_into_tail(&mut buf, samples, channels, out.sample_rate, frametime);
+            if done == len {
+                continue;
+            } else if done > 0 {
+                converted = skip_samples(&converted, done, channels);
+                frametime +=
+                    Duration::from_secs_f64((done / channels) as f64 / out.sample_rate as f64);
+            }
+        }
 
         AUDIO_BUFFER_LEN.fetch_add(converted.samples(), Ordering::SeqCst);
 
-        let mut buf = AUDIO_BUFFER.lock();
         buf.back_mut().map(|w| w.next_ts = Some(frametime));
         buf.push_back(AudioFrameWrapper::new(frametime, converted));
-        buf.back_mut().map(|w| w.prev_ts = last_frametime);
+        buf.back_mut().map(|w| {
+            w.prev_ts = last_frametime;
+            w.tail = fade_out.is_some();
+        });
         last_frametime = Some(frametime);
 
         let buflen = || AUDIO_BUFFER_LEN.load(Ordering::SeqCst);
         let maxbuf = || (CPAL_BUFFER_LEN.load(Ordering::SeqCst) * 2).max(1024);
-        while buflen() > maxbuf() && TERM_QUIT.load(Ordering::SeqCst) == false {
+        while fade_out.is_none()
+            && buflen() > maxbuf()
+            && TERM_QUIT.load(Ordering::SeqCst) == false
+            && !REBUILD_STREAM.load(Ordering::SeqCst)
+        {
             AUDIO_CONSUMED.wait_for(&mut buf, Duration::from_millis(20));
         }
     }
 
-    while AUDIO_BUFFER.lock().len() > 0 && TERM_QUIT.load(Ordering::SeqCst) == false {
+    // 自然播放到结尾且还有下一曲时不等缓冲区播完，下一曲的数据直接接在后面
+    let gapless = output.is_some()
+        && crate::ffmpeg::reached_eof()
+        && TERM_QUIT.load(Ordering::SeqCst) == false
+        && PLAYLIST.lock().peek_next().is_some();
+    if gapless {
+        *CROSSFADE_TAIL.lock() = fade_out;
+    } else if fade_out.is_some() {
+        // 被打断时不必再播放淡出的尾巴
+        AUDIO_BUFFER.lock().clear();
+        AUDIO_BUFFER_LEN.store(0, Ordering::SeqCst);
+    }
+    while !gapless
+        && output.is_some()
+        && AUDIO_BUFFER.lock().len() > 0
+        && TERM_QUIT.load(Ordering::SeqCst) == false
+        && !REBUILD_STREAM.load(Ordering::SeqCst)
+    {
         std::thread::sleep(Duration::from_millis(100));
     }
+
+    if TERM_QUIT.load(Ordering::SeqCst) == false {
+        *OUTPUT.lock() = output;
+    }
 }
@@ -34,6 +34,87 @@ impl InnerState {
     }
 }
 
+/// 主时钟，其他时钟向它靠拢
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MasterClock {
+    /// 以音频播放进度为准，画面通过丢帧/重复帧追赶
+    Audio,
+    /// 以画面显示进度为准，音频通过重采样微调速度追赶
+    Video,
+    /// 以墙上时钟为准，音频和画面都向它靠拢
+    External,
+}
+
+impl MasterClock {
+    pub const ALL: &[MasterClock] = &[
+        MasterClock::Audio,
+        MasterClock::Video,
+        MasterClock::External,
+    ];
+
+    pub const fn name(&self) -> &'static str {
+        match self {
+            MasterClock::Audio => "audio",
+            MasterClock::Video => "video",
+            MasterClock::External => "external",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.trim().to_lowercase();
+        Self::ALL.iter().copied().find(|m| m.name() == name)
+    }
+}
+
+/// 漂移直方图各区间的上界（毫秒），最后一个区间没有上界
+pub const DRIFT_BUCKETS: [f64; 8] = [-100.0, -40.0, -15.0, -5.0, 5.0, 15.0, 40.0, 100.0];
+
+/// 同步统计
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SyncStats {
+    /// 音画漂移（音频 - 画面）的直方图，见 [`DRIFT_BUCKETS`]
+    pub histogram: [u32; DRIFT_BUCKETS.len() + 1],
+    /// 平滑校正的次数
+    pub corrections: u64,
+    /// 漂移过大直接跳到主时钟的次数
+    pub resyncs: u64,
+    /// 丢弃的视频帧和音频帧
+    pub dropped: u64,
+    /// 重复显示（等待）的视频帧
+    pub repeated: u64,
+    /// 通过重采样拉伸或压缩的音频帧
+    pub stretched: u64,
+}
+
+impl SyncStats {
+    const fn new() -> Self {
+        Self {
+            histogram: [0; DRIFT_BUCKETS.len() + 1],
+            corrections: 0,
+            resyncs: 0,
+            dropped: 0,
+            repeated: 0,
+            stretched: 0,
+        }
+    }
+
+    fn record_drift(&mut self, secs: f64) {
+        let ms = secs * 1000.0;
+        let bucket = DRIFT_BUCKETS
+            .iter()
+            .position(|&edge| ms < edge)
+            .unwrap_or(DRIFT_BUCKETS.len());
+        self.histogram[bucket] += 1;
+    }
+}
+
+/// 漂移在此范围内不做校正（秒）
+const DEAD_ZONE: f64 = 0.005;
+/// 漂移超过此值时直接跳到主时钟（秒）
+const RESYNC_THRESHOLD: f64 = 0.25;
+/// 平滑校正时同步时钟的最大速度偏差
+const MAX_SLEW: f64 = 0.05;
+
 struct AVSyncState {
     duration: Duration,
 
@@ -46,9 +127,23 @@ struct AVSyncState {
     has_audio: bool,
     has_video: bool,
 
+    /// 音频输出是否可用，不可用时音频时钟不参与同步
+    audio_output: bool,
+
     sync: Option<InnerState>,
     audio: Option<InnerState>,
     video: Option<InnerState>,
+
+    /// 上次校正同步时钟的时间
+    last_tick: Option<Instant>,
+    /// 是否正在平滑校正
+    slewing: bool,
+    /// seek 之后等待主时钟的第一次更新，届时直接对齐
+    resync_pending: bool,
+    /// 上一个显示的视频帧（显示时间，帧时间）
+    last_video_frame: Option<(Instant, Duration)>,
+
+    stats: SyncStats,
 }
 
 impl AVSyncState {
@@ -59,9 +154,15 @@ impl AVSyncState {
             decode_end: false,
             has_audio,
             has_video,
+            audio_output: true,
             sync: None,
             audio: None,
             video: None,
+            last_tick: None,
+            slewing: false,
+            resync_pending: true,
+            last_video_frame: None,
+            stats: SyncStats::new(),
         }
     }
 
@@ -94,12 +195,24 @@ impl AVSyncState {
             if let Some(video) = self.video.as_mut() {
                 video.resume(now)
             }
+            self.last_tick = Some(now);
+            self.last_video_frame = None;
         }
     }
 
     fn set_time(&mut self, vtime: Duration) {
         self.sync.replace(InnerState::new(vtime));
-        self.tick();
+        self.last_tick = Some(Instant::now());
+        self.slewing = false;
+    }
+
+    /// seek 之后旧的音频/视频时钟已经失效，等待新的更新
+    fn seek(&mut self, vtime: Duration) {
+        self.set_time(vtime);
+        self.audio = None;
+        self.video = None;
+        self.resync_pending = true;
+        self.last_video_frame = None;
     }
 
     fn set_audio_time(&mut self, vtime: Duration) {
@@ -108,44 +221,145 @@ impl AVSyncState {
     }
 
     fn set_video_time(&mut self, vtime: Duration) {
+        let now = Instant::now();
+        // 帧显示的间隔明显长于帧时间的间隔，说明上一帧被重复显示了
+        if let Some((shown, ts)) = self.last_video_frame
+            && !self.paused
+        {
+            let wall = now.duration_since(shown).as_secs_f64();
+            let media = vtime.as_secs_f64() - ts.as_secs_f64();
+            if media > 0.0 && wall > media * 1.5 && wall < 1.0 {
+                self.stats.repeated += 1;
+            }
+        }
+        self.last_video_frame = Some((now, vtime));
+
         self.video.replace(InnerState::new(vtime));
         self.tick();
+
+        let reference = self.audio_clock().or_else(|| self.clock(self.sync));
+        if let Some(reference) = reference {
+            let drift = reference.as_secs_f64() - vtime.as_secs_f64();
+            self.stats.record_drift(drift);
+        }
+    }
+
+    fn clock(&self, state: Option<InnerState>) -> Option<Duration> {
+        let state = state?;
+        Some(if self.paused {
+            state.playedtime
+        } else {
+            state.vstarttime.elapsed()
+        })
     }
 
-    /// 临时用的同步逻辑
+    /// 实际听到的音频时间（已计入音频延迟）
+    fn audio_clock(&self) -> Option<Duration> {
+        if !self.audio_output {
+            return None;
+        }
+        self.clock(self.audio).map(|t| shift_time(t, audio_delay()))
+    }
+
+    /// 实际使用的主时钟：首选的时钟不可用时依次退回
+    fn effective_master(&self) -> MasterClock {
+        let audio = self.has_audio && self.audio_output;
+        match master_clock() {
+            MasterClock::Audio if audio => MasterClock::Audio,
+            MasterClock::Audio | MasterClock::Video if self.has_video => MasterClock::Video,
+            MasterClock::Video if audio => MasterClock::Audio,
+            _ => MasterClock::External,
+        }
+    }
+
+    fn master_time(&self) -> Option<Duration> {
+        match self.effective_master() {
+            MasterClock::Audio => self.audio_clock(),
+            MasterClock::Video => self.clock(self.video),
+            MasterClock::External => None,
+        }
+    }
+
+    /// 让同步时钟向主时钟靠拢：小的漂移平滑校正，大的漂移直接跳过去
     fn tick(&mut self) {
         if self.paused || self.sync.is_none() {
             return;
         }
+        let now = Instant::now();
+        let dt = self
+            .last_tick
+            .map_or(0.0, |t| now.duration_since(t).as_secs_f64());
+        self.last_tick = Some(now);
+
+        let Some(master) = self.master_time() else {
+            return;
+        };
         let time = self.sync.map(|s| s.vstarttime.elapsed()).unwrap();
-        let atime = self.audio.map(|s| s.vstarttime.elapsed());
-        let vtime = self.video.map(|s| s.vstarttime.elapsed());
-        match (atime, vtime) {
-            (Some(atime), Some(vtime)) => {
-                let adiff = (time.as_secs_f64() - atime.as_secs_f64()).abs();
-                let vdiff = (time.as_secs_f64() - vtime.as_secs_f64()).abs();
-                if adiff > 0.02 {
-                    self.set_time(atime);
-                }
-                let _ = vdiff;
-            }
-            (Some(atime), None) => {
-                let adiff = (time.as_secs_f64() - atime.as_secs_f64()).abs();
-                if adiff > 0.02 {
-                    self.set_time(atime);
-                }
-            }
-            (None, Some(vtime)) => {
-                let vdiff = (time.as_secs_f64() - vtime.as_secs_f64()).abs();
-                if vdiff > 0.02 {
-                    self.set_time(vtime);
-                }
+        let drift = master.as_secs_f64() - time.as_secs_f64();
+
+        if self.resync_pending || drift.abs() > RESYNC_THRESHOLD {
+            if !self.resync_pending {
+                self.stats.resyncs += 1;
             }
-            (None, None) => {}
+            self.resync_pending = false;
+            self.set_time(master);
+            return;
+        }
+
+        if drift.abs() < DEAD_ZONE {
+            self.slewing = false;
+            return;
+        }
+        if !self.slewing {
+            self.slewing = true;
+            self.stats.corrections += 1;
+        }
+
+        // 把起点往前移，时钟就走得快一些，反之亦然
+        let max = dt * MAX_SLEW;
+        let step = drift.clamp(-max, max);
+        let sync = self.sync.as_mut().unwrap();
+        let shift = Duration::from_secs_f64(step.abs());
+        if step > 0.0 {
+            sync.vstarttime = sync
+                .vstarttime
+                .checked_sub(shift)
+                .unwrap_or(sync.vstarttime);
+        } else {
+            sync.vstarttime += shift;
         }
     }
 }
 
+/// 把时间平移 `secs` 秒，结果不小于零
+fn shift_time(time: Duration, secs: f64) -> Duration {
+    Duration::from_secs_f64((time.as_secs_f64() + secs).max(0.0))
+}
+
+/// 音频延迟（秒）
+/// - 正数表示音频推迟（画面提前显示），负数表示画面推迟
+/// - 蓝牙耳机等输出延迟较大的设备通常需要负数
+static AUDIO_DELAY: Mutex<f64> = Mutex::new(0.0);
+
+pub fn set_audio_delay(secs: f64) {
+    *AUDIO_DELAY.lock() = secs;
+}
+
+pub fn audio_delay() -> f64 {
+    *AUDIO_DELAY.lock()
+}
+
+/// 首选的主时钟
+static MASTER_CLOCK: Mutex<MasterClock> = Mutex::new(MasterClock::Audio);
+
+pub fn set_master_clock(master: MasterClock) {
+    *MASTER_CLOCK.lock() = master;
+}
+
+pub fn master_clock() -> MasterClock {
+    *MASTER_CLOCK.lock()
+}
+
 static STATE: Mutex<AVSyncState> = Mutex::new(AVSyncState::new(Duration::ZERO, false, false));
 
 /// 重置 AV 同步状态
@@ -197,6 +411,19 @@ pub fn has_video() -> bool {
     STATE.lock().has_video
 }
 
+/// 设置音频输出是否可用
+pub fn set_audio_output(available: bool) {
+    let mut state = STATE.lock();
+    state.audio_output = available;
+    if !available {
+        state.audio = None;
+    }
+}
+
+pub fn has_audio_output() -> bool {
+    STATE.lock().audio_output
+}
+
 macro_rules! played_time {
     ($fn1:ident, $fn2:ident, $mb:ident) => {
         pub fn $fn1() -> Duration {
@@ -221,7 +448,7 @@ played_time!(video_played_time_or_zero, video_played_time_or_none, video);
 
 /// 提示已经 seek 到指定时间点
 pub fn hint_seeked(ts: Duration) {
-    STATE.lock().set_time(ts);
+    STATE.lock().seek(ts);
 }
 
 /// 提示同步模块，尝试同步音频播放时间
@@ -233,3 +460,49 @@ pub fn hint_audio_played_time(ts: Duration) {
 pub fn hint_video_played_time(ts: Duration) {
     STATE.lock().set_video_time(ts);
 }
+
+/// 实际使用的主时钟
+pub fn effective_master_clock() -> MasterClock {
+    STATE.lock().effective_master()
+}
+
+pub fn stats() -> SyncStats {
+    STATE.lock().stats
+}
+
+/// 音频相对同步时钟的漂移（秒，正数表示音频超前），音频是主时钟时返回 `None`
+pub fn audio_drift() -> Option<f64> {
+    let mut state = STATE.lock();
+    state.tick();
+    if state.paused || state.effective_master() == MasterClock::Audio {
+        return None;
+    }
+    let audio = state.audio_clock()?;
+    let sync = state.clock(state.sync)?;
+    Some(audio.as_secs_f64() - sync.as_secs_f64())
+}
+
+/// 帧是否已经落后同步时钟太多，应该丢弃（主时钟的帧永远不丢）
+/// - `frame_duration`：帧的时长，落后超过两帧（至少 40ms）才丢弃
+pub fn frame_too_late(ts: Duration, frame_duration: Duration, master: MasterClock) -> bool {
+    let mut state = STATE.lock();
+    state.tick();
+    if state.effective_master() == master {
+        return false;
+    }
+    let Some(sync) = state.clock(state.sync) else {
+        return false;
+    };
+    let threshold = (frame_duration * 2).max(Duration::from_millis(40));
+    if ts + threshold < sync {
+        state.stats.dropped += 1;
+        true
+    } else {
+        false
+    }
+}
+
+/// 记录一次通过重采样进行的音频校正
+pub fn record_audio_stretch() {
+    STATE.lock().stats.stretched += 1;
+}
@@ -0,0 +1,210 @@
+use av::format::context::Input;
+use parking_lot::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use crate::avsync;
+use crate::ffmpeg;
+use crate::stdin::{self, Key};
+
+// @ ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== @
+
+/// 在章节开头这么久以内按“上一章”时跳到前一章，否则回到本章开头
+const PREV_RESTART_THRESHOLD: Duration = Duration::from_secs(3);
+
+#[derive(Clone)]
+pub struct Chapter {
+    pub start: Duration,
+    pub title: String,
+}
+
+/// 当前播放文件的章节，按开始时间排序
+static CHAPTERS: Mutex<Vec<Chapter>> = Mutex::new(Vec::new());
+
+/// 从容器中读取章节，没有标题的章节用序号代替
+pub fn load(ictx: &Input) {
+    let to_duration = |ts: i64, tb: av::Rational| {
+        if tb.1 == 0 {
+            return Duration::ZERO;
+        }
+        let sec = ts as f64 * tb.0 as f64 / tb.1 as f64;
+        Duration::from_secs_f64(sec.max(0.0))
+    };
+
+    let mut chapters = ictx
+        .chapters()
+        .enumerate()
+        .map(|(i, ch)| {
+            let tb = ch.time_base();
+            let title = ch.metadata().get("title").map(|t| t.trim().to_string());
+            let title = title
+                .filter(|t| !t.is_empty())
+                .unwrap_or_else(|| f16n!("Chapter {}", i + 1));
+            Chapter {
+                start: to_duration(ch.start(), tb),
+                title,
+            }
+        })
+        .collect::<Vec<_>>();
+    chapters.sort_by_key(|c| c.start);
+
+    *CHAPTERS.lock() = chapters;
+    *CHAPTERS_SELECTED_INDEX.lock() = -1;
+}
+
+pub fn clear() {
+    CHAPTERS.lock().clear();
+    *CHAPTERS_SELECTED_INDEX.lock() = -1;
+}
+
+pub fn list() -> Vec<Chapter> {
+    CHAPTERS.lock().clone()
+}
+
+pub fn count() -> usize {
+    CHAPTERS.lock().len()
+}
+
+/// 当前播放位置所在的章节下标
+pub fn current() -> Option<usize> {
+    let time = avsync::played_time_or_zero();
+    let chapters = CHAPTERS.lock();
+    chapters.iter().rposition(|c| c.start <= time)
+}
+
+pub fn status() -> String {
+    let time = avsync::played_time_or_zero();
+    let chapters = CHAPTERS.lock();
+    let index = chapters.iter().rposition(|c| c.start <= time);
+    match index {
+        _ if chapters.is_empty() => "N/A".to_string(),
+        Some(i) => format!("{}/{} {}", i + 1, chapters.len(), chapters[i].title),
+        None => format!("-/{}", chapters.len()),
+    }
+}
+
+/// 跳到第 `index` 个章节（从 0 开始）
+pub fn seek_to(index: usize) -> bool {
+    let Some(start) = CHAPTERS.lock().get(index).map(|c| c.start) else {
+        return false;
+    };
+    ffmpeg::seek_request_absolute(start.as_secs_f64());
+    true
+}
+
+pub fn next() {
+    let next = current().map_or(0, |i| i + 1);
+    if !seek_to(next) && count() > 0 {
+        info_l10n!("Already at the last chapter");
+    }
+}
+
+/// 离本章开头较远时回到本章开头，否则跳到上一章
+pub fn prev() {
+    let Some(index) = current() else {
+        seek_to(0);
+        return;
+    };
+    // 章节可能已被切换文件清空，下标不再有效
+    let Some(start) = CHAPTERS.lock().get(index).map(|c| c.start) else {
+        return;
+    };
+    let into = avsync::played_time_or_zero().saturating_sub(start);
+    if into > PREV_RESTART_THRESHOLD || index == 0 {
+        seek_to(index);
+    } else {
+        seek_to(index - 1);
+    }
+}
+
+/// 按序号（从 1 开始）或标题查找章节，标题不区分大小写，先精确匹配再前缀匹配
+pub fn find(query: &str) -> Option<usize> {
+    let query = query.trim();
+    let chapters = CHAPTERS.lock();
+    if let Ok(n) = query.parse::<usize>() {
+        return (1..=chapters.len()).contains(&n).then(|| n - 1);
+    }
+    let query = query.to_lowercase();
+    let titles = chapters
+        .iter()
+        .map(|c| c.title.to_lowercase())
+        .collect::<Vec<_>>();
+    titles
+        .iter()
+        .position(|t| *t == query)
+        .or_else(|| titles.iter().position(|t| t.starts_with(&query)))
+}
+
+// @ ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== @
+
+pub static SHOW_CHAPTERS: AtomicBool = AtomicBool::new(false);
+pub static CHAPTERS_SELECTED_INDEX: Mutex<isize> = Mutex::new(-1);
+
+pub fn toggle_show_chapters() {
+    SHOW_CHAPTERS.fetch_xor(true, Ordering::SeqCst);
+    *CHAPTERS_SELECTED_INDEX.lock() = -1;
+}
+
+fn move_selection(delta: isize) {
+    let len = count() as isize;
+    if len == 0 {
+        return;
+    }
+    let mut lock = CHAPTERS_SELECTED_INDEX.lock();
+    let base = if *lock >= 0 {
+        *lock
+    } else {
+        current().map_or(-1, |i| i as isize)
+    };
+    *lock = (base + delta).clamp(0, len - 1);
+}
+
+pub fn register_keypress_callbacks() {
+    stdin::register_keypress_callback(Key::Normal('q'), |_, _| {
+        if !SHOW_CHAPTERS.load(Ordering::SeqCst) {
+            return false;
+        }
+        SHOW_CHAPTERS.store(false, Ordering::SeqCst);
+        true
+    });
+
+    let cb = |_, _| {
+        if !SHOW_CHAPTERS.load(Ordering::SeqCst) {
+            return false;
+        }
+        let index = *CHAPTERS_SELECTED_INDEX.lock();
+        if index >= 0 {
+            seek_to(index as usize);
+            SHOW_CHAPTERS.store(false, Ordering::SeqCst);
+        }
+        true
+    };
+    stdin::register_keypress_callback(Key::Normal(' '), cb);
+    stdin::register_keypress_callback(Key::Enter, cb);
+
+    let cb = |_, _| {
+        if !SHOW_CHAPTERS.load(Ordering::SeqCst) {
+            return false;
+        }
+        move_selection(-1);
+        true
+    };
+    stdin::register_keypress_callback(Key::Normal('w'), cb);
+    stdin::register_keypress_callback(Key::Up, cb);
+
+    let cb = |_, _| {
+        if !SHOW_CHAPTERS.load(Ordering::SeqCst) {
+            return false;
+        }
+        move_selection(1);
+        true
+    };
+    stdin::register_keypress_callback(Key::Normal('s'), cb);
+    stdin::register_keypress_callback(Key::Down, cb);
+
+    let cb = |_, _| SHOW_CHAPTERS.load(Ordering::SeqCst);
+    stdin::register_keypress_callback(Key::Normal('a'), cb);
+    stdin::register_keypress_callback(Key::Left, cb);
+    stdin::register_keypress_callback(Key::Normal('d'), cb);
+    stdin::register_keypress_callback(Key::Right, cb);
+}
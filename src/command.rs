@@ -6,6 +6,9 @@ use crate::term::TERM_DEFAULT_FG;
 use crate::util::Color;
 use crate::{avsync, ffmpeg, term, ui::helper as uihelper};
 
+#[cfg(feature = "config")]
+use crate::config;
+
 static COMMAND_MODE: AtomicBool = AtomicBool::new(false);
 static COMMAND_BUFFER: Mutex<String> = Mutex::new(String::new());
 static COMMAND_CANDIDATES: Mutex<Vec<String>> = Mutex::new(Vec::new());
@@ -122,10 +125,32 @@ pub fn register_commands() {
     register_command("lang", cmd_lang, Some(complete_lang));
     register_command("seek", cmd_seek, Some(complete_seek));
     register_command("volume", cmd_volume, Some(complete_volume));
+    #[cfg(feature = "audio")]
+    register_command("audio", cmd_audio, Some(complete_audio));
+    #[cfg(feature = "audio")]
+    register_command("af", cmd_af, Some(complete_af));
+    #[cfg(feature = "audio")]
+    register_command("audiodelay", cmd_audiodelay, Some(complete_audiodelay));
+    #[cfg(feature = "video")]
+    register_command("vf", cmd_vf, Some(complete_vf));
+    #[cfg(feature = "video")]
+    register_command("view", cmd_view, Some(complete_view));
+    #[cfg(feature = "video")]
+    register_command("tile", cmd_tile, Some(complete_tile));
+    #[cfg(feature = "video")]
+    register_command("compare", cmd_compare, Some(complete_compare));
+    register_command("chapter", cmd_chapter, Some(complete_chapter));
+    register_command("sync", cmd_sync, Some(complete_sync));
     register_command("pause", cmd_pause, None);
     register_command("resume", cmd_resume, None);
     register_command("toggle", cmd_toggle, None);
     register_command("next", cmd_next, None);
+    #[cfg(feature = "config")]
+    {
+        register_command("set", cmd_set, Some(complete_set));
+        register_command("get", cmd_get, Some(complete_config_key));
+        register_command("reset", cmd_reset, Some(complete_config_key));
+    }
     register_command("quit", cmd_quit, None);
     register_command("exit", cmd_quit, None);
 }
@@ -158,64 +183,9 @@ fn cmd_lang(args: &[&str]) {
     };
     #[cfg(feature = "i18n")]
     {
-        let arg = arg.to_lowercase();
-        let selected = match arg.as_str() {
-            "en-us" | "en" => {
-                static_l10n::lang!("en-us");
-                "en-us"
-            }
-            "zh-cn" | "zh" => {
-                static_l10n::lang!("zh-cn");
-                "zh-cn"
-            }
-            "zh-tw" => {
-                static_l10n::lang!("zh-tw");
-                "zh-tw"
-            }
-            "ko-kr" | "ko" => {
-                static_l10n::lang!("ko-kr");
-                "ko-kr"
-            }
-            "pt-br" | "pt" => {
-                static_l10n::lang!("pt-br");
-                "pt-br"
-            }
-            "ru-ru" | "ru" => {
-                static_l10n::lang!("ru-ru");
-                "ru-ru"
-            }
-            "it-it" | "it" => {
-                static_l10n::lang!("it-it");
-                "it-it"
-            }
-            "tr-tr" | "tr" => {
-                static_l10n::lang!("tr-tr");
-                "tr-tr"
-            }
-            "vi-vn" | "vi" => {
-                static_l10n::lang!("vi-vn");
-                "vi-vn"
-            }
-            "ja-jp" | "ja" => {
-                static_l10n::lang!("ja-jp");
-                "ja-jp"
-            }
-            "fr-fr" | "fr" => {
-                static_l10n::lang!("fr-fr");
-                "fr-fr"
-            }
-            "de-de" | "de" => {
-                static_l10n::lang!("de-de");
-                "de-de"
-            }
-            "es-es" | "es" => {
-                static_l10n::lang!("es-es");
-                "es-es"
-            }
-            _ => {
-                error_f16n!("lang: unsupported language: {}", arg);
-                return;
-            }
+        let Some(selected) = crate::set_lang(arg) else {
+            error_f16n!("lang: unsupported language: {}", arg);
+            return;
         };
         info_f16n!("Language set to {}", selected);
     }
@@ -264,6 +234,312 @@ fn cmd_volume(args: &[&str]) {
     }
 }
 
+#[cfg(feature = "audio")]
+fn cmd_audio(args: &[&str]) {
+    match args.first() {
+        Some(&"devices") => {
+            let current = crate::audio::current_device_name();
+            let names = crate::audio::output_device_names();
+            if names.is_empty() {
+                warning_l10n!("No audio output devices found");
+            }
+            for name in names {
+                let mark = if current.as_ref() == Some(&name) {
+                    "*"
+                } else {
+                    " "
+                };
+                info!("{} {}", mark, name);
+            }
+        }
+        Some(&"device") => {
+            // 设备名称中可能含有空格，不带名称时使用默认设备
+            let name = args[1..].join(" ");
+            crate::audio::set_output_device(Some(name));
+        }
+        Some(arg) => error_f16n!("audio: invalid argument: {}", arg),
+        None => error_l10n!("audio: missing argument"),
+    }
+}
+
+#[cfg(feature = "audio")]
+fn cmd_af(args: &[&str]) {
+    use crate::afilter::{self, Downmix, Normalize};
+    let Some(&sub) = args.first() else {
+        info_f16n!("Audio Filter: {}", afilter::status());
+        return;
+    };
+    if sub == "off" {
+        afilter::reset();
+        info_f16n!("Audio Filter: {}", afilter::status());
+        return;
+    }
+    let Some(&value) = args.get(1) else {
+        error_l10n!("af: missing argument");
+        return;
+    };
+    match sub {
+        "eq" => match afilter::parse_eq(value) {
+            Some(gains) => afilter::set_eq(gains),
+            None => {
+                error_f16n!("Invalid equalizer setting: {}", value);
+                return;
+            }
+        },
+        "norm" => match Normalize::from_name(value) {
+            Some(mode) => afilter::set_normalize(mode),
+            None => {
+                error_f16n!("Unknown normalization mode: {}", value);
+                return;
+            }
+        },
+        "target" => match value.parse::<f32>() {
+            Ok(lufs) if (-40.0..=0.0).contains(&lufs) => afilter::set_normalize_target(lufs),
+            _ => {
+                error_l10n!("Target loudness must be between -40 and 0 LUFS");
+                return;
+            }
+        },
+        "drc" => match value {
+            "on" | "true" => afilter::set_drc(true),
+            "off" | "false" => afilter::set_drc(false),
+            _ => {
+                error_f16n!("af: invalid argument: {}", value);
+                return;
+            }
+        },
+        "downmix" => match Downmix::from_name(value) {
+            Some(mode) => afilter::set_downmix(mode),
+            None => {
+                error_f16n!("Unknown downmix mode: {}", value);
+                return;
+            }
+        },
+        _ => {
+            error_f16n!("af: invalid argument: {}", sub);
+            return;
+        }
+    }
+    info_f16n!("Audio Filter: {}", afilter::status());
+}
+
+#[cfg(feature = "video")]
+fn cmd_vf(args: &[&str]) {
+    use crate::vfilter::{self, Crop, Deinterlace, VideoEq};
+    let Some(&sub) = args.first() else {
+        info_f16n!("Video Filter: {}", vfilter::status());
+        return;
+    };
+    match sub {
+        "off" => vfilter::reset(),
+        "hflip" => vfilter::toggle_hflip(),
+        "vflip" => vfilter::toggle_vflip(),
+        _ => {
+            let Some(&value) = args.get(1) else {
+                error_l10n!("vf: missing argument");
+                return;
+            };
+            match sub {
+                "crop" => match Crop::parse(value) {
+                    Some(crop) => vfilter::set_crop(crop),
+                    None => {
+                        error_f16n!("Invalid crop (expected auto, off or w:h[:x:y]): {}", value);
+                        return;
+                    }
+                },
+                "rotate" => match vfilter::parse_rotate(value) {
+                    Some(rotate) => vfilter::set_rotate(rotate),
+                    None => {
+                        error_f16n!(
+                            "Invalid rotation (expected auto, 0, 90, 180 or 270): {}",
+                            value
+                        );
+                        return;
+                    }
+                },
+                "deint" => match Deinterlace::from_name(value) {
+                    Some(mode) => vfilter::set_deinterlace(mode),
+                    None => {
+                        error_f16n!("vf: invalid argument: {}", value);
+                        return;
+                    }
+                },
+                "eq" => {
+                    let joined = args[1..].join(":");
+                    let value = if value == "off" { "" } else { &joined };
+                    match VideoEq::parse(value) {
+                        Some(eq) => vfilter::set_eq(eq),
+                        None => {
+                            error_f16n!("Invalid video eq setting: {}", value);
+                            return;
+                        }
+                    }
+                }
+                _ => {
+                    error_f16n!("vf: invalid argument: {}", sub);
+                    return;
+                }
+            }
+        }
+    }
+    info_f16n!("Video Filter: {}", vfilter::status());
+}
+
+#[cfg(feature = "video")]
+fn cmd_view(args: &[&str]) {
+    use crate::view::{self, Aspect};
+    match args {
+        [] => (),
+        ["reset"] => view::reset(),
+        ["zoom", value] => match value.parse::<f32>() {
+            Ok(zoom) if zoom.is_finite() => view::set_zoom(zoom),
+            _ => {
+                error_f16n!("view: invalid argument: {}", value);
+                return;
+            }
+        },
+        [value] => match Aspect::parse(value) {
+            Some(aspect) => view::set_aspect(aspect),
+            None => {
+                error_f16n!(
+                    "Invalid aspect (expected fit, fill, stretch or w:h): {}",
+                    value
+                );
+                return;
+            }
+        },
+        _ => {
+            error_f16n!("view: invalid argument: {}", args.join(" "));
+            return;
+        }
+    }
+    info_f16n!("View: {}", view::status());
+}
+
+#[cfg(feature = "video")]
+fn cmd_tile(args: &[&str]) {
+    use crate::tiles::{self, Layout};
+    let parse_index = |value: &str| value.parse::<usize>().ok();
+    match args {
+        [] => (),
+        ["add", path @ ..] if !path.is_empty() => tiles::add(&path.join(" ")),
+        ["clear"] => tiles::clear(),
+        ["layout", value] => match Layout::from_name(value) {
+            Some(layout) => tiles::set_layout(layout),
+            None => {
+                error_f16n!("Unknown tile layout: {}", value);
+                return;
+            }
+        },
+        ["remove", index] => {
+            if !parse_index(index).is_some_and(tiles::remove) {
+                error_f16n!("No such tile: {}", index);
+                return;
+            }
+        }
+        ["offset", index, secs] => {
+            let Some(secs) = secs.parse::<f64>().ok().filter(|s| s.is_finite()) else {
+                error_f16n!("tile: invalid argument: {}", secs);
+                return;
+            };
+            if !parse_index(index).is_some_and(|i| tiles::set_offset(i, secs)) {
+                error_f16n!("No such tile: {}", index);
+                return;
+            }
+        }
+        ["audio", index] => {
+            if !parse_index(index).is_some_and(tiles::swap_audio) {
+                error_f16n!("No such tile: {}", index);
+            }
+            return;
+        }
+        _ => {
+            error_f16n!("tile: invalid argument: {}", args.join(" "));
+            return;
+        }
+    }
+    info_f16n!("Tiles: {}", tiles::status());
+}
+
+#[cfg(feature = "video")]
+fn cmd_compare(args: &[&str]) {
+    use crate::compare::{self, Mode};
+    match args {
+        [] => (),
+        ["off"] => compare::close(),
+        ["split", value] => match value.parse::<f32>() {
+            Ok(split) if split.is_finite() => compare::set_split(split),
+            _ => {
+                error_f16n!("compare: invalid argument: {}", value);
+                return;
+            }
+        },
+        ["offset", value] => {
+            let Some(secs) = value.parse::<f64>().ok().filter(|s| s.is_finite()) else {
+                error_f16n!("compare: invalid argument: {}", value);
+                return;
+            };
+            if !compare::set_offset(secs) {
+                error_l10n!("Compare mode is off, open B with /compare <path>");
+                return;
+            }
+        }
+        // 其他参数是 B 的路径
+        [value] => match Mode::from_name(value) {
+            Some(mode) => compare::set_mode(mode),
+            None => compare::open(value),
+        },
+        path => compare::open(&path.join(" ")),
+    }
+    info_f16n!("Compare(b/v): {}", compare::status());
+}
+
+#[cfg(feature = "audio")]
+fn cmd_audiodelay(args: &[&str]) {
+    match args.first() {
+        None => {
+            let delay = (avsync::audio_delay() * 1000.0).round() as i64;
+            info_f16n!("Audio Delay: {}ms", delay);
+        }
+        Some(&"calibrate") => {
+            if crate::audio::toggle_calibration() {
+                info_l10n!("Calibrating: adjust with [/] until the beep matches the flash");
+            } else {
+                info_l10n!("Calibration finished");
+            }
+        }
+        Some(arg) => match arg.parse::<f64>() {
+            Ok(secs) if secs.is_finite() => crate::audio::set_audio_delay(secs),
+            _ => error_f16n!("audiodelay: invalid argument: {}", arg),
+        },
+    }
+}
+
+fn cmd_sync(args: &[&str]) {
+    use avsync::MasterClock;
+    if let Some(arg) = args.first() {
+        let Some(master) = MasterClock::from_name(arg) else {
+            error_f16n!("Unknown master clock: {}", arg);
+            return;
+        };
+        avsync::set_master_clock(master);
+    }
+    let stats = avsync::stats();
+    info_f16n!(
+        "Master Clock: {} (using {})",
+        avsync::master_clock().name(),
+        avsync::effective_master_clock().name()
+    );
+    info_f16n!(
+        "Sync Corrections: {} (resync {}, dropped {}, repeated {}, stretched {})",
+        stats.corrections,
+        stats.resyncs,
+        stats.dropped,
+        stats.repeated,
+        stats.stretched
+    );
+}
+
 fn cmd_pause(_args: &[&str]) {
     avsync::pause();
 }
@@ -276,6 +552,22 @@ fn cmd_toggle(_args: &[&str]) {
     avsync::switch_pause_state();
 }
 
+fn cmd_chapter(args: &[&str]) {
+    use crate::chapters;
+    if args.is_empty() {
+        info_f16n!("Chapter: {}", chapters::status());
+        return;
+    }
+    // 标题里可能有空格
+    let query = args.join(" ");
+    match chapters::find(&query) {
+        Some(index) => {
+            chapters::seek_to(index);
+        }
+        None => error_f16n!("No such chapter: {}", query),
+    }
+}
+
 fn cmd_next(_args: &[&str]) {
     ffmpeg::notify_quit();
 }
@@ -284,6 +576,43 @@ fn cmd_quit(_args: &[&str]) {
     term::request_quit();
 }
 
+#[cfg(feature = "config")]
+fn cmd_set(args: &[&str]) {
+    let Some(key) = args.first() else {
+        error_l10n!("set: missing argument");
+        return;
+    };
+    // 值中可能含有空格（如设备名称）
+    let value = args[1..].join(" ");
+    match config::set(key, &value) {
+        Ok(()) => info!("{} = {}", key, config::get(key).unwrap_or(value)),
+        Err(err) => error!("set: {}", err),
+    }
+}
+
+#[cfg(feature = "config")]
+fn cmd_get(args: &[&str]) {
+    let keys = if args.is_empty() { config::KEYS } else { args };
+    for key in keys {
+        match config::get(key) {
+            Ok(value) => info!("{} = {}", key, value),
+            Err(err) => error!("get: {}", err),
+        }
+    }
+}
+
+#[cfg(feature = "config")]
+fn cmd_reset(args: &[&str]) {
+    let Some(key) = args.first() else {
+        error_l10n!("reset: missing argument");
+        return;
+    };
+    match config::reset(key).and_then(|()| config::get(key)) {
+        Ok(value) => info!("{} = {}", key, value),
+        Err(err) => error!("reset: {}", err),
+    }
+}
+
 fn complete_seek(_args: &[&str], prefix: &str) -> Vec<String> {
     let suggestions = ["-30", "-5", "+5", "+30", "0", "60", "120"];
     filter_suggestions(prefix, &suggestions)
@@ -294,12 +623,184 @@ fn complete_volume(_args: &[&str], prefix: &str) -> Vec<String> {
     filter_suggestions(prefix, &suggestions)
 }
 
+#[cfg(feature = "config")]
+fn complete_set(args: &[&str], prefix: &str) -> Vec<String> {
+    match args {
+        [] => filter_suggestions(prefix, config::KEYS),
+        [key] => {
+            let values = config::value_suggestions(key);
+            let values = values.iter().map(|v| v.as_str()).collect::<Vec<_>>();
+            filter_suggestions(prefix, &values)
+        }
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(feature = "config")]
+fn complete_config_key(args: &[&str], prefix: &str) -> Vec<String> {
+    if args.is_empty() {
+        filter_suggestions(prefix, config::KEYS)
+    } else {
+        Vec::new()
+    }
+}
+
+#[cfg(feature = "audio")]
+fn complete_audio(args: &[&str], prefix: &str) -> Vec<String> {
+    match args {
+        [] => filter_suggestions(prefix, &["devices", "device"]),
+        ["device", ..] => {
+            // 补全时把已输入的部分拼回完整的设备名称
+            let typed = args[1..]
+                .iter()
+                .map(|s| format!("{s} "))
+                .collect::<String>();
+            crate::audio::output_device_names()
+                .into_iter()
+                .filter_map(|name| name.strip_prefix(&typed).map(|s| s.to_string()))
+                .filter(|rest| rest.starts_with(prefix))
+                .collect()
+        }
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(feature = "audio")]
+fn complete_af(args: &[&str], prefix: &str) -> Vec<String> {
+    use crate::afilter::{Downmix, EQ_PRESETS, Normalize};
+    match args {
+        [] => filter_suggestions(prefix, &["eq", "norm", "target", "drc", "downmix", "off"]),
+        ["eq"] => {
+            let presets = EQ_PRESETS.iter().map(|(name, _)| *name).collect::<Vec<_>>();
+            filter_suggestions(prefix, &presets)
+        }
+        ["norm"] => {
+            let modes = Normalize::ALL.iter().map(|m| m.name()).collect::<Vec<_>>();
+            filter_suggestions(prefix, &modes)
+        }
+        ["target"] => filter_suggestions(prefix, &["-23", "-18", "-16", "-14"]),
+        ["drc"] => filter_suggestions(prefix, &["on", "off"]),
+        ["downmix"] => {
+            let modes = Downmix::ALL.iter().map(|m| m.name()).collect::<Vec<_>>();
+            filter_suggestions(prefix, &modes)
+        }
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(feature = "video")]
+fn complete_vf(args: &[&str], prefix: &str) -> Vec<String> {
+    match args {
+        [] => filter_suggestions(
+            prefix,
+            &["crop", "rotate", "hflip", "vflip", "deint", "eq", "off"],
+        ),
+        ["crop"] => filter_suggestions(prefix, &["auto", "off"]),
+        ["rotate"] => filter_suggestions(prefix, &["auto", "0", "90", "180", "270"]),
+        ["deint"] => filter_suggestions(prefix, &["auto", "on", "off"]),
+        ["eq", ..] => filter_suggestions(
+            prefix,
+            &["brightness=", "contrast=", "saturation=", "gamma=", "off"],
+        ),
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(feature = "video")]
+fn complete_view(args: &[&str], prefix: &str) -> Vec<String> {
+    match args {
+        [] => {
+            let mut items = crate::view::Aspect::NAMES.to_vec();
+            items.extend(["zoom", "reset"]);
+            filter_suggestions(prefix, &items)
+        }
+        ["zoom"] => filter_suggestions(prefix, &["1", "1.5", "2", "4"]),
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(feature = "video")]
+fn complete_tile(args: &[&str], prefix: &str) -> Vec<String> {
+    use crate::tiles::{self, Layout};
+    let indices = (1..=tiles::count())
+        .map(|i| i.to_string())
+        .collect::<Vec<_>>();
+    let indices = indices.iter().map(String::as_str).collect::<Vec<_>>();
+    match args {
+        [] => filter_suggestions(
+            prefix,
+            &["add", "remove", "clear", "layout", "offset", "audio"],
+        ),
+        ["add"] => {
+            let items = crate::playlist::PLAYLIST.lock().get_items().clone();
+            let items = items.iter().map(String::as_str).collect::<Vec<_>>();
+            filter_suggestions(prefix, &items)
+        }
+        ["layout"] => {
+            let layouts = Layout::ALL.iter().map(|l| l.name()).collect::<Vec<_>>();
+            filter_suggestions(prefix, &layouts)
+        }
+        ["remove" | "offset" | "audio"] => filter_suggestions(prefix, &indices),
+        ["offset", _] => filter_suggestions(prefix, &["-1", "-0.5", "0", "0.5", "1"]),
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(feature = "video")]
+fn complete_compare(args: &[&str], prefix: &str) -> Vec<String> {
+    match args {
+        [] => {
+            let mut items = crate::compare::Mode::ALL
+                .iter()
+                .map(|m| m.name())
+                .collect::<Vec<_>>();
+            items.extend(["split", "offset", "off"]);
+            let playlist = crate::playlist::PLAYLIST.lock().get_items().clone();
+            items.extend(playlist.iter().map(String::as_str));
+            filter_suggestions(prefix, &items)
+        }
+        ["split"] => filter_suggestions(prefix, &["0.25", "0.5", "0.75"]),
+        ["offset"] => filter_suggestions(prefix, &["-0.04", "0", "0.04"]),
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(feature = "audio")]
+fn complete_audiodelay(args: &[&str], prefix: &str) -> Vec<String> {
+    if args.is_empty() {
+        filter_suggestions(prefix, &["calibrate", "-0.2", "-0.1", "0", "0.1"])
+    } else {
+        Vec::new()
+    }
+}
+
+/// 按序号或标题补全，标题按空格分成多个参数，只补全还没输入的部分
+fn complete_chapter(args: &[&str], prefix: &str) -> Vec<String> {
+    let list = crate::chapters::list();
+    let mut items = Vec::new();
+    if args.is_empty() {
+        items.extend((1..=list.len()).map(|i| i.to_string()));
+    }
+    for chapter in &list {
+        let words = chapter.title.split_whitespace().collect::<Vec<_>>();
+        if words.len() > args.len() && words[..args.len()] == *args {
+            items.push(words[args.len()..].join(" "));
+        }
+    }
+    let items = items.iter().map(String::as_str).collect::<Vec<_>>();
+    filter_suggestions(prefix, &items)
+}
+
+fn complete_sync(args: &[&str], prefix: &str) -> Vec<String> {
+    if args.is_empty() {
+        filter_suggestions(prefix, &["audio", "video", "external"])
+    } else {
+        Vec::new()
+    }
+}
+
 fn complete_lang(_args: &[&str], prefix: &str) -> Vec<String> {
-    let suggestions = [
-        "en-us", "zh-cn", "zh-tw", "ja-jp", "fr-fr", "de-de", "es-es", "ko-kr", "pt-br", "ru-ru",
-        "it-it", "tr-tr", "vi-vn",
-    ];
-    filter_suggestions(prefix, &suggestions)
+    filter_suggestions(prefix, crate::LANGS)
 }
 
 fn filter_suggestions(prefix: &str, items: &[&str]) -> Vec<String> {
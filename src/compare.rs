@@ -0,0 +1,364 @@
+use parking_lot::Mutex;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use crate::render::{self, ContextWrapper};
+use crate::stdin::{self, Key, MouseAction};
+use crate::tiles::Tile;
+use crate::util::Color;
+use crate::vfilter;
+use crate::video::VIDEO_FRAMETIME;
+
+// @ ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== @
+
+/// 对比方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// 分割线左边是 A，右边是 B
+    Wipe,
+    /// 整个画面显示 A 或 B，按键切换
+    Flip,
+    /// 放大后的差值图像
+    Diff,
+}
+
+impl Mode {
+    pub const ALL: &[Mode] = &[Mode::Wipe, Mode::Flip, Mode::Diff];
+
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Mode::Wipe => "wipe",
+            Mode::Flip => "flip",
+            Mode::Diff => "diff",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.trim().to_lowercase();
+        Self::ALL.iter().copied().find(|m| m.name() == name)
+    }
+}
+
+/// 差值图像的放大倍数
+const DIFF_GAIN: u32 = 8;
+/// SSIM 的窗口大小（像素）
+const SSIM_BLOCK: usize = 8;
+const SSIM_C1: f64 = (0.01 * 255.0) * (0.01 * 255.0);
+const SSIM_C2: f64 = (0.03 * 255.0) * (0.03 * 255.0);
+
+/// 对比的另一个输入（B），主画面是 A
+static INPUT_B: Mutex<Option<Arc<Tile>>> = Mutex::new(None);
+static MODE: Mutex<Mode> = Mutex::new(Mode::Wipe);
+/// 分割线的位置，占画面宽度的比例
+static SPLIT: Mutex<f32> = Mutex::new(0.5);
+/// 翻转方式下是否显示 B
+static SHOW_B: AtomicBool = AtomicBool::new(false);
+
+#[derive(Clone, Copy)]
+struct Metrics {
+    /// B 的帧时间，同一帧不重复计算
+    time: Duration,
+    psnr: f64,
+    ssim: f64,
+}
+
+static METRICS: Mutex<Option<Metrics>> = Mutex::new(None);
+
+fn changed() {
+    render::RENDER_CONTEXT.lock().force_flush_next();
+    render::api_request_frame();
+}
+
+pub fn active() -> bool {
+    INPUT_B.lock().is_some()
+}
+
+/// 打开 B，替换之前的 B
+pub fn open(path: &str) {
+    let tile = Tile::spawn(path, 0.0, true);
+    if let Some(old) = INPUT_B.lock().replace(tile) {
+        old.stop();
+    }
+    METRICS.lock().take();
+    changed();
+}
+
+pub fn close() {
+    if let Some(old) = INPUT_B.lock().take() {
+        old.stop();
+    }
+    METRICS.lock().take();
+    changed();
+}
+
+pub fn set_mode(mode: Mode) {
+    *MODE.lock() = mode;
+    changed();
+}
+
+pub fn cycle_mode() {
+    let mode = {
+        let mut lock = MODE.lock();
+        let index = Mode::ALL.iter().position(|&m| m == *lock).unwrap_or(0);
+        *lock = Mode::ALL[(index + 1) % Mode::ALL.len()];
+        *lock
+    };
+    changed();
+    info_f16n!("Compare: {}", mode.name());
+}
+
+/// 在 A 和 B 之间切换，切换到翻转方式
+pub fn flip() {
+    *MODE.lock() = Mode::Flip;
+    SHOW_B.fetch_xor(true, Ordering::SeqCst);
+    changed();
+}
+
+pub fn set_split(split: f32) {
+    *SPLIT.lock() = split.clamp(0.0, 1.0);
+    changed();
+}
+
+/// 设置 B 相对 A 的时间偏移，返回 B 是否已打开
+pub fn set_offset(offset: f64) -> bool {
+    let Some(tile) = INPUT_B.lock().clone() else {
+        return false;
+    };
+    tile.set_offset(offset);
+    METRICS.lock().take();
+    changed();
+    true
+}
+
+/// 用于叠加文本的对比状态
+pub fn status() -> String {
+    let Some(tile) = INPUT_B.lock().clone() else {
+        return "OFF".to_string();
+    };
+    let mode = *MODE.lock();
+    let mut parts = vec![mode.name().to_string()];
+    match mode {
+        Mode::Wipe => parts.push(format!("{:.0}%", *SPLIT.lock() * 100.0)),
+        Mode::Flip => parts.push(
+            if SHOW_B.load(Ordering::SeqCst) {
+                "B"
+            } else {
+                "A"
+            }
+            .into(),
+        ),
+        Mode::Diff => parts.push(format!("x{DIFF_GAIN}")),
+    }
+    parts.push(format!("B={}", tile.path()));
+    if tile.offset() != 0.0 {
+        parts.push(format!("{:+.3}s", tile.offset()));
+    }
+    match *METRICS.lock() {
+        Some(m) if m.psnr.is_finite() => {
+            parts.push(format!("PSNR {:.2} dB, SSIM {:.4}", m.psnr, m.ssim))
+        }
+        Some(m) => parts.push(format!("PSNR inf, SSIM {:.4}", m.ssim)),
+        None if vfilter::active() => parts.push("PSNR -, SSIM - (vf)".to_string()),
+        None => parts.push("PSNR -, SSIM -".to_string()),
+    }
+    parts.join(" ")
+}
+
+// @ ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== @
+
+fn luma(c: Color) -> f64 {
+    0.299 * c.r as f64 + 0.587 * c.g as f64 + 0.114 * c.b as f64
+}
+
+/// RGB 三个通道合起来的 PSNR，两帧完全相同时为无穷大
+fn psnr(a: &[Color], b: &[Color], width: usize, height: usize, pitch: usize) -> f64 {
+    let mut sum = 0u64;
+    for y in 0..height {
+        let (ra, rb) = (
+            &a[y * pitch..y * pitch + width],
+            &b[y * width..(y + 1) * width],
+        );
+        for (p, q) in ra.iter().zip(rb) {
+            let d = |x: u8, y: u8| (x as i64 - y as i64).pow(2) as u64;
+            sum += d(p.r, q.r) + d(p.g, q.g) + d(p.b, q.b);
+        }
+    }
+    let mse = sum as f64 / (width * height * 3) as f64;
+    match mse {
+        0.0 => f64::INFINITY,
+        mse => 10.0 * (255.0 * 255.0 / mse).log10(),
+    }
+}
+
+/// 亮度的 SSIM，按不重叠的 `SSIM_BLOCK` 方块计算后取平均
+fn ssim(a: &[Color], b: &[Color], width: usize, height: usize, pitch: usize) -> f64 {
+    let (mut total, mut count) = (0.0, 0);
+    for by in (0..height.saturating_sub(SSIM_BLOCK - 1)).step_by(SSIM_BLOCK) {
+        for bx in (0..width.saturating_sub(SSIM_BLOCK - 1)).step_by(SSIM_BLOCK) {
+            let (mut sa, mut sb, mut saa, mut sbb, mut sab) = (0.0, 0.0, 0.0, 0.0, 0.0);
+            for y in by..by + SSIM_BLOCK {
+                for x in bx..bx + SSIM_BLOCK {
+                    let (p, q) = (luma(a[y * pitch + x]), luma(b[y * width + x]));
+                    sa += p;
+                    sb += q;
+                    saa += p * p;
+                    sbb += q * q;
+                    sab += p * q;
+                }
+            }
+            let n = (SSIM_BLOCK * SSIM_BLOCK) as f64;
+            let (ma, mb) = (sa / n, sb / n);
+            let (va, vb, cov) = (saa / n - ma * ma, sbb / n - mb * mb, sab / n - ma * mb);
+            total += (2.0 * ma * mb + SSIM_C1) * (2.0 * cov + SSIM_C2)
+                / ((ma * ma + mb * mb + SSIM_C1) * (va + vb + SSIM_C2));
+            count += 1;
+        }
+    }
+    match count {
+        0 => 1.0,
+        count => total / count as f64,
+    }
+}
+
+/// 按对比方式合成 A（`frame`）和 B，没有打开 B 时返回 `None`
+/// - B 拉伸到与 A 相同的大小，逐像素对应
+/// - B 的帧时间与 A 相同时计算 PSNR 和 SSIM，指标按终端输出的分辨率计算；
+///   A 经过了滤镜（裁剪、旋转等）时两者不再逐像素对应，不计算指标
+pub fn process(frame: &[Color], width: usize, height: usize, pitch: usize) -> Option<Vec<Color>> {
+    let tile = INPUT_B.lock().clone()?;
+    tile.set_area((width, height));
+    let image = tile.image();
+    // B 还没有解码出来，或还是之前的大小，暂时只显示 A
+    let b = match image.as_ref() {
+        Some(image) if (image.width, image.height) == (width, height) => image,
+        _ => return None,
+    };
+
+    let half_frame = Duration::from_micros(VIDEO_FRAMETIME.load(Ordering::SeqCst) / 2);
+    let synced = b.time.abs_diff(tile.target_time()) <= half_frame;
+    let filtered = vfilter::active();
+    if filtered {
+        METRICS.lock().take();
+    }
+    let measured = METRICS.lock().is_some_and(|m| m.time == b.time);
+    if synced && !measured && !filtered {
+        METRICS.lock().replace(Metrics {
+            time: b.time,
+            psnr: psnr(frame, &b.data, width, height, pitch),
+            ssim: ssim(frame, &b.data, width, height, pitch),
+        });
+    }
+
+    let mode = *MODE.lock();
+    let mut out = Vec::with_capacity(width * height);
+    match mode {
+        Mode::Wipe => {
+            let split = (*SPLIT.lock() * width as f32).round() as usize;
+            // 分割线在像素模式下也要看得见
+            let line = (width / 320).max(1);
+            for y in 0..height {
+                let (ra, rb) = (&frame[y * pitch..y * pitch + width], &b.data[y * width..]);
+                out.extend_from_slice(&ra[..split.min(width)]);
+                out.extend_from_slice(&rb[split.min(width)..width]);
+                let row = &mut out[y * width..];
+                for c in row
+                    .iter_mut()
+                    .skip(split.saturating_sub(line / 2))
+                    .take(line)
+                {
+                    *c = Color::new(255, 255, 255);
+                }
+            }
+        }
+        Mode::Flip if SHOW_B.load(Ordering::SeqCst) => out.extend_from_slice(&b.data),
+        Mode::Flip => return None,
+        Mode::Diff => {
+            let d = |x: u8, y: u8| ((x.abs_diff(y) as u32 * DIFF_GAIN).min(255)) as u8;
+            for y in 0..height {
+                let (ra, rb) = (&frame[y * pitch..y * pitch + width], &b.data[y * width..]);
+                out.extend(
+                    ra.iter()
+                        .zip(rb)
+                        .map(|(p, q)| Color::new(d(p.r, q.r), d(p.g, q.g), d(p.b, q.b))),
+                );
+            }
+        }
+    }
+    Some(out)
+}
+
+/// 标出画面显示的是 A 还是 B
+pub fn render_compare(wrap: &mut ContextWrapper) {
+    if !active() || wrap.video_cells_width < 8 || wrap.video_cells_height < 4 {
+        return;
+    }
+    let y = (wrap.padding_top + wrap.video_cells_height / 2) as isize;
+    let left = wrap.padding_left as isize;
+    let labels = match *MODE.lock() {
+        Mode::Wipe => {
+            let split = *SPLIT.lock() * wrap.video_cells_width as f32;
+            let x = left + split.round() as isize;
+            vec![(" A ", x - 4), (" B ", x + 2)]
+        }
+        Mode::Flip if SHOW_B.load(Ordering::SeqCst) => vec![(" B ", left + 1)],
+        Mode::Flip => vec![(" A ", left + 1)],
+        Mode::Diff => vec![(" A-B ", left + 1)],
+    };
+    let (fg, bg) = (Color::new(255, 255, 255), Color::new(0, 0, 0));
+    for (text, x) in labels {
+        let w = text.chars().count();
+        crate::ui::helper::putat(wrap, text, x, y, w, 1, x, y, Some(fg), Some(bg), false);
+    }
+}
+
+// @ ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== @
+
+pub fn register_input_callbacks() {
+    stdin::register_keypress_callback(Key::Normal('b'), |_, _| {
+        match active() {
+            true => flip(),
+            false => info_l10n!("Compare mode is off, open B with /compare <path>"),
+        }
+        true
+    });
+    stdin::register_keypress_callback(Key::Normal('v'), |_, _| {
+        match active() {
+            true => cycle_mode(),
+            false => info_l10n!("Compare mode is off, open B with /compare <path>"),
+        }
+        true
+    });
+
+    // 在分割线附近按下左键后拖动分割线
+    static DRAGGING: AtomicBool = AtomicBool::new(false);
+    stdin::register_mouse_callback(|_, m| {
+        if !active() || *MODE.lock() != Mode::Wipe {
+            return false;
+        }
+        let (left, width) = {
+            let ctx = render::RENDER_CONTEXT.lock();
+            (ctx.padding_left as f32, ctx.video_cells_width.max(1) as f32)
+        };
+        let split = |x: i32| (x as f32 + 0.5 - left) / width;
+        match (DRAGGING.load(Ordering::SeqCst), m.action) {
+            (false, MouseAction::LeftDown) => {
+                let at = left + *SPLIT.lock() * width;
+                if (m.pos.0 as f32 - at).abs() > 1.5 {
+                    return false;
+                }
+                DRAGGING.store(true, Ordering::SeqCst);
+                true
+            }
+            (true, _) if m.left => {
+                set_split(split(m.pos.0));
+                true
+            }
+            (true, _) => {
+                DRAGGING.store(false, Ordering::SeqCst);
+                true
+            }
+            _ => false,
+        }
+    });
+}
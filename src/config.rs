@@ -1,12 +1,22 @@
 use anyhow::Result;
-use data_classes::derive::*;
 use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs::File;
 use std::io::{Read, Write};
 use std::path::Path;
 use std::sync::LazyLock;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
 
+use crate::ascii;
+use crate::avsync::{self, MasterClock};
+use crate::dither::{self, ColorMatch, Dither, SixelPalette};
 use crate::playlist::PLAYLIST;
+use crate::render::RENDER_CONTEXT;
+use crate::term::TERM_QUIT;
+use crate::util::{ChromaMode, Color, ColorMode, glob_match};
+use crate::{logging, ui};
 
 #[cfg(windows)]
 const CONFIG_DIR: LazyLock<String> = LazyLock::new(|| {
@@ -30,6 +40,8 @@ const CONFIG_DIR: LazyLock<String> = LazyLock::new(|| {
 });
 
 const CONFIG_FILE: &str = "tvid.toml";
+/// 媒体目录下的配置文件，对该目录中的文件生效
+const DIR_CONFIG_FILE: &str = ".tvid.toml";
 const PLAYLIST_FILE: &str = "playlist.txt";
 const PLAYLIST_SUBDIR: &str = "playlists";
 
@@ -41,16 +53,214 @@ pub static CONFIG: Mutex<Config> = Mutex::new(Config::new());
 static ORIG_CONFIG: Mutex<Config> = Mutex::new(Config::new());
 static TOML_SOURCE: Mutex<Option<String>> = Mutex::new(None);
 
-#[data(default, serde)]
+/// 配置文件中的 `[profile.<name>]` 段
+static PROFILES: Mutex<Vec<Profile>> = Mutex::new(Vec::new());
+/// 通过 `--profile` 选中的配置段
+static ACTIVE_PROFILE: Mutex<Option<String>> = Mutex::new(None);
+/// 上一次应用的实际配置，只重新应用有变化的配置项，避免覆盖运行时的调整
+static APPLIED_CONFIG: Mutex<Option<Config>> = Mutex::new(None);
+/// 命令行参数等临时覆盖的配置项，优先级最高且不写回配置文件
+static OVERRIDES: Mutex<Vec<(String, String)>> = Mutex::new(Vec::new());
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
 pub struct Config {
     /// 音量，范围 0-200
-    #[default = 100]
     #[serde(default)]
     pub volume: u32,
     /// 是否循环播放播放列表
-    #[default = false]
     #[serde(default)]
     pub looping: bool,
+    /// 界面语言，空字符串表示跟随系统
+    #[serde(default)]
+    pub lang: String,
+    /// 颜色模式，空字符串表示按探测到的终端能力自动选择
+    #[serde(default)]
+    pub color_mode: String,
+    /// 色度模式，空字符串表示默认
+    #[serde(default)]
+    pub chroma_mode: String,
+    /// 调色板受限时的抖动算法（none、bayer、floyd-steinberg、blue-noise），空字符串表示 blue-noise
+    #[serde(default)]
+    pub dither: String,
+    /// 匹配调色板颜色的色差公式（rgb、oklab、ciede2000），空字符串表示 oklab
+    #[serde(default)]
+    pub color_match: String,
+    /// Sixel 调色板（adaptive、xterm），空字符串表示 adaptive
+    #[serde(default)]
+    pub sixel_palette: String,
+    /// ASCII 艺术模式的字符序列，预设名称（standard、detailed、simple、blocks）或从暗到亮的自定义字符
+    #[serde(default)]
+    pub ascii_ramp: String,
+    /// ASCII 艺术模式是否在边缘处使用 `/ \ | _ -`
+    #[serde(default)]
+    pub ascii_edges: bool,
+    /// 每秒最多输出的字节数，0 表示只按实测的输出速度限制
+    #[serde(default)]
+    pub output_budget: u64,
+    /// 颜色变化不超过此值（每个通道 0 到 255）的单元格不重新输出，0 表示每次变化都输出
+    #[serde(default)]
+    pub color_threshold: u32,
+    /// 小步快进/快退的秒数
+    #[serde(default)]
+    pub seek_small: f64,
+    /// 大步快进/快退的秒数
+    #[serde(default)]
+    pub seek_large: f64,
+    /// 字幕延迟（秒），正数表示字幕推迟显示
+    #[serde(default)]
+    pub subtitle_delay: f64,
+    /// 字幕颜色（`#RRGGBB`），空字符串表示自动对比色
+    #[serde(default)]
+    pub subtitle_color: String,
+    /// 是否显示叠加文本
+    #[serde(default)]
+    pub show_overlay: bool,
+    /// 是否显示进度条
+    #[serde(default)]
+    pub show_progressbar: bool,
+    /// 按像素计的进度条高度
+    #[serde(default)]
+    pub progressbar_height: f32,
+    /// 消息显示时长（秒）
+    #[serde(default)]
+    pub message_timeout: f64,
+    /// 音频输出设备名称，空字符串表示默认设备
+    #[serde(default)]
+    pub audio_device: String,
+    /// 音频延迟（秒），用于没有在 `audio_delays` 中单独设置的设备
+    #[serde(default)]
+    pub audio_delay: f64,
+    /// 主时钟（audio、video、external），空字符串表示音频
+    #[serde(default)]
+    pub sync_master: String,
+    /// 是否显示音频可视化
+    #[serde(default)]
+    pub show_visualizer: bool,
+    /// 音频可视化样式（volume、spectrum、spectrogram、oscilloscope、vectorscope、vu），空字符串表示 volume
+    #[serde(default)]
+    pub visualizer: String,
+    /// 均衡器：预设名称或 10 个逗号分隔的频段增益（dB），空字符串表示关闭
+    #[serde(default)]
+    pub af_eq: String,
+    /// 响度归一化模式（off、track、album、r128），空字符串表示关闭
+    #[serde(default)]
+    pub af_normalize: String,
+    /// 响度归一化的目标响度（LUFS）
+    #[serde(default)]
+    pub af_normalize_target: f64,
+    /// 夜间模式（动态范围压缩）
+    #[serde(default)]
+    pub af_drc: bool,
+    /// 下混模式（auto、stereo、center、mono），空字符串表示自动
+    #[serde(default)]
+    pub af_downmix: String,
+    /// 纯音频曲目之间的交叉淡化时长（秒），0 表示关闭
+    #[serde(default)]
+    pub crossfade: f64,
+    /// 视频解码线程数，0 表示自动
+    #[serde(default)]
+    pub vd_threads: usize,
+    /// 多线程解码方式（auto、frame、slice），空字符串表示自动
+    #[serde(default)]
+    pub vd_thread_type: String,
+    /// 跳过环路滤波的帧（default、noref、bidir、nointra、nokey、all），空字符串表示 default
+    #[serde(default)]
+    pub vd_skip_loop_filter: String,
+    /// 跳过解码的帧，取值同 `vd_skip_loop_filter`
+    #[serde(default)]
+    pub vd_skip_frame: String,
+    /// 按 2 的幂缩小解码分辨率（0–3 或 auto），空字符串表示自动
+    #[serde(default)]
+    pub vd_lowres: String,
+    /// 跳帧过多时自动降低解码质量
+    #[serde(default)]
+    pub vd_auto_degrade: bool,
+    /// 视频缩放算法（auto、point、fast_bilinear、bilinear、bicubic、area、lanczos），空字符串表示自动
+    #[serde(default)]
+    pub scaler: String,
+    /// 裁剪（off、auto 或 w:h[:x:y]），空字符串表示关闭
+    #[serde(default)]
+    pub vf_crop: String,
+    /// 顺时针旋转角度（0、90、180、270），空字符串表示按视频的旋转信息
+    #[serde(default)]
+    pub vf_rotate: String,
+    /// 是否水平翻转
+    #[serde(default)]
+    pub vf_hflip: bool,
+    /// 是否垂直翻转
+    #[serde(default)]
+    pub vf_vflip: bool,
+    /// 反交错（off、auto、on），空字符串表示 auto
+    #[serde(default)]
+    pub vf_deinterlace: String,
+    /// 亮度、对比度、饱和度和伽马，如 `brightness=0.05:contrast=1.1`，空字符串表示不调整
+    #[serde(default)]
+    pub vf_eq: String,
+    /// 画面适配方式（fit、fill、stretch），或强制的宽高比如 `4:3`、`2.35:1`，空字符串表示 fit
+    #[serde(default)]
+    pub aspect: String,
+    /// 分屏布局（pip、side、grid），空字符串表示 side
+    #[serde(default)]
+    pub tile_layout: String,
+    /// 各音频输出设备的延迟（秒），按设备名称保存
+    #[serde(default)]
+    pub audio_delays: BTreeMap<String, f64>,
+}
+
+/// 所有可通过 `set_entry` 修改的配置项
+pub const KEYS: &[&str] = &[
+    "volume",
+    "looping",
+    "lang",
+    "color_mode",
+    "chroma_mode",
+    "dither",
+    "color_match",
+    "sixel_palette",
+    "ascii_ramp",
+    "ascii_edges",
+    "output_budget",
+    "color_threshold",
+    "seek_small",
+    "seek_large",
+    "subtitle_delay",
+    "subtitle_color",
+    "show_overlay",
+    "show_progressbar",
+    "progressbar_height",
+    "message_timeout",
+    "audio_device",
+    "audio_delay",
+    "sync_master",
+    "show_visualizer",
+    "visualizer",
+    "af_eq",
+    "af_normalize",
+    "af_normalize_target",
+    "af_drc",
+    "af_downmix",
+    "crossfade",
+    "vd_threads",
+    "vd_thread_type",
+    "vd_skip_loop_filter",
+    "vd_skip_frame",
+    "vd_lowres",
+    "vd_auto_degrade",
+    "scaler",
+    "vf_crop",
+    "vf_rotate",
+    "vf_hflip",
+    "vf_vflip",
+    "vf_deinterlace",
+    "vf_eq",
+    "aspect",
+    "tile_layout",
+];
+
+impl Default for Config {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Config {
@@ -58,10 +268,56 @@ impl Config {
         Self {
             volume: 100,
             looping: false,
+            lang: String::new(),
+            color_mode: String::new(),
+            chroma_mode: String::new(),
+            dither: String::new(),
+            color_match: String::new(),
+            sixel_palette: String::new(),
+            ascii_ramp: String::new(),
+            ascii_edges: true,
+            output_budget: 0,
+            color_threshold: 2,
+            seek_small: 5.0,
+            seek_large: 30.0,
+            subtitle_delay: 0.0,
+            subtitle_color: String::new(),
+            show_overlay: true,
+            show_progressbar: true,
+            progressbar_height: 16.0,
+            message_timeout: 5.0,
+            audio_device: String::new(),
+            audio_delay: 0.0,
+            sync_master: String::new(),
+            show_visualizer: false,
+            visualizer: String::new(),
+            af_eq: String::new(),
+            af_normalize: String::new(),
+            af_normalize_target: -18.0,
+            af_drc: false,
+            af_downmix: String::new(),
+            crossfade: 0.0,
+            vd_threads: 0,
+            vd_thread_type: String::new(),
+            vd_skip_loop_filter: String::new(),
+            vd_skip_frame: String::new(),
+            vd_lowres: String::new(),
+            vd_auto_degrade: true,
+            scaler: String::new(),
+            vf_crop: String::new(),
+            vf_rotate: String::new(),
+            vf_hflip: false,
+            vf_vflip: false,
+            vf_deinterlace: String::new(),
+            vf_eq: String::new(),
+            aspect: String::new(),
+            tile_layout: String::new(),
+            audio_delays: BTreeMap::new(),
         }
     }
 
     pub fn set_entry(&mut self, key: &str, value: &str) -> Result<()> {
+        let value = value.trim();
         match key {
             "volume" => {
                 let v = value.parse::<u32>()?;
@@ -75,6 +331,273 @@ impl Config {
                 let b = value.parse::<bool>()?;
                 self.looping = b;
             }
+            "lang" => {
+                if !value.is_empty() && !crate::LANGS.contains(&value) {
+                    anyhow::bail!("{}", f16n!("Unsupported language: {}", value));
+                }
+                self.lang = value.to_string();
+            }
+            "color_mode" => {
+                if !value.is_empty() && ColorMode::from_name(value).is_none() {
+                    anyhow::bail!("{}", f16n!("Unknown color mode: {}", value));
+                }
+                self.color_mode = value.to_string();
+            }
+            "chroma_mode" => {
+                if !value.is_empty() && ChromaMode::from_name(value).is_none() {
+                    anyhow::bail!("{}", f16n!("Unknown chroma mode: {}", value));
+                }
+                self.chroma_mode = value.to_string();
+            }
+            "dither" => {
+                if !value.is_empty() && Dither::from_name(value).is_none() {
+                    anyhow::bail!("{}", f16n!("Unknown dither mode: {}", value));
+                }
+                self.dither = value.to_string();
+            }
+            "color_match" => {
+                if !value.is_empty() && ColorMatch::from_name(value).is_none() {
+                    anyhow::bail!("{}", f16n!("Unknown color match: {}", value));
+                }
+                self.color_match = value.to_string();
+            }
+            "sixel_palette" => {
+                if !value.is_empty() && SixelPalette::from_name(value).is_none() {
+                    anyhow::bail!("{}", f16n!("Unknown sixel palette: {}", value));
+                }
+                self.sixel_palette = value.to_string();
+            }
+            "ascii_ramp" => {
+                if ascii::parse_ramp(value).is_none() {
+                    anyhow::bail!("{}", f16n!("Unknown ASCII ramp: {}", value));
+                }
+                self.ascii_ramp = value.to_string();
+            }
+            "ascii_edges" => {
+                self.ascii_edges = value.parse::<bool>()?;
+            }
+            "output_budget" => {
+                self.output_budget = value.parse::<u64>()?;
+            }
+            "color_threshold" => {
+                self.color_threshold = value.parse::<u32>()?;
+            }
+            "seek_small" | "seek_large" => {
+                let v = value.parse::<f64>()?;
+                if !(v > 0.0 && v.is_finite()) {
+                    anyhow::bail!("{}", l10n!("Seek step must be a positive number"));
+                }
+                if key == "seek_small" {
+                    self.seek_small = v;
+                } else {
+                    self.seek_large = v;
+                }
+            }
+            "subtitle_delay" => {
+                let v = value.parse::<f64>()?;
+                if !v.is_finite() {
+                    anyhow::bail!("{}", f16n!("Invalid number: {}", value));
+                }
+                self.subtitle_delay = v;
+            }
+            "subtitle_color" => {
+                if !value.is_empty() && Color::from_hex(value).is_none() {
+                    anyhow::bail!("{}", f16n!("Invalid color (expected #RRGGBB): {}", value));
+                }
+                self.subtitle_color = value.to_string();
+            }
+            "show_overlay" => {
+                self.show_overlay = value.parse::<bool>()?;
+            }
+            "show_progressbar" => {
+                self.show_progressbar = value.parse::<bool>()?;
+            }
+            "progressbar_height" => {
+                let v = value.parse::<f32>()?;
+                if !(v >= 0.0 && v.is_finite()) {
+                    anyhow::bail!("{}", f16n!("Invalid number: {}", value));
+                }
+                self.progressbar_height = v;
+            }
+            "message_timeout" => {
+                let v = value.parse::<f64>()?;
+                if !(v > 0.0 && v.is_finite()) {
+                    anyhow::bail!("{}", f16n!("Invalid number: {}", value));
+                }
+                self.message_timeout = v;
+            }
+            "audio_device" => {
+                self.audio_device = value.to_string();
+            }
+            "audio_delay" => {
+                let v = value.parse::<f64>()?;
+                if !(-5.0..=5.0).contains(&v) {
+                    anyhow::bail!("{}", l10n!("Audio delay must be between -5 and 5 seconds"));
+                }
+                self.audio_delay = v;
+            }
+            "sync_master" => {
+                if !value.is_empty() && MasterClock::from_name(value).is_none() {
+                    anyhow::bail!("{}", f16n!("Unknown master clock: {}", value));
+                }
+                self.sync_master = value.to_string();
+            }
+            "show_visualizer" => {
+                self.show_visualizer = value.parse::<bool>()?;
+            }
+            "visualizer" => {
+                #[cfg(feature = "audio")]
+                if !value.is_empty() && crate::visualizer::Visualizer::from_name(value).is_none() {
+                    anyhow::bail!("{}", f16n!("Unknown visualizer: {}", value));
+                }
+                self.visualizer = value.to_string();
+            }
+            "af_eq" => {
+                #[cfg(feature = "audio")]
+                if !value.is_empty() && crate::afilter::parse_eq(value).is_none() {
+                    anyhow::bail!("{}", f16n!("Invalid equalizer setting: {}", value));
+                }
+                self.af_eq = value.to_string();
+            }
+            "af_normalize" => {
+                #[cfg(feature = "audio")]
+                if !value.is_empty() && crate::afilter::Normalize::from_name(value).is_none() {
+                    anyhow::bail!("{}", f16n!("Unknown normalization mode: {}", value));
+                }
+                self.af_normalize = value.to_string();
+            }
+            "af_normalize_target" => {
+                let v = value.parse::<f64>()?;
+                if !(-40.0..=0.0).contains(&v) {
+                    anyhow::bail!(
+                        "{}",
+                        l10n!("Target loudness must be between -40 and 0 LUFS")
+                    );
+                }
+                self.af_normalize_target = v;
+            }
+            "af_drc" => {
+                self.af_drc = value.parse::<bool>()?;
+            }
+            "af_downmix" => {
+                #[cfg(feature = "audio")]
+                if !value.is_empty() && crate::afilter::Downmix::from_name(value).is_none() {
+                    anyhow::bail!("{}", f16n!("Unknown downmix mode: {}", value));
+                }
+                self.af_downmix = value.to_string();
+            }
+            "crossfade" => {
+                let v = value.parse::<f64>()?;
+                if !(0.0..=10.0).contains(&v) {
+                    anyhow::bail!("{}", l10n!("Crossfade must be between 0 and 10 seconds"));
+                }
+                self.crossfade = v;
+            }
+            "vd_threads" => {
+                let v = value.parse::<usize>()?;
+                if v > 64 {
+                    anyhow::bail!("{}", l10n!("Decoder threads must be between 0 and 64"));
+                }
+                self.vd_threads = v;
+            }
+            "vd_thread_type" => {
+                #[cfg(feature = "video")]
+                if !value.is_empty() && crate::vdecoder::ThreadType::from_name(value).is_none() {
+                    anyhow::bail!("{}", f16n!("Unknown thread type: {}", value));
+                }
+                self.vd_thread_type = value.to_string();
+            }
+            "vd_skip_loop_filter" | "vd_skip_frame" => {
+                #[cfg(feature = "video")]
+                if !value.is_empty() && crate::vdecoder::Skip::from_name(value).is_none() {
+                    anyhow::bail!("{}", f16n!("Unknown skip mode: {}", value));
+                }
+                if key == "vd_skip_frame" {
+                    self.vd_skip_frame = value.to_string();
+                } else {
+                    self.vd_skip_loop_filter = value.to_string();
+                }
+            }
+            "vd_lowres" => {
+                #[cfg(feature = "video")]
+                if crate::vdecoder::parse_lowres(value).is_none() {
+                    anyhow::bail!("{}", l10n!("lowres must be auto or between 0 and 3"));
+                }
+                self.vd_lowres = value.to_string();
+            }
+            "vd_auto_degrade" => {
+                self.vd_auto_degrade = value.parse::<bool>()?;
+            }
+            "scaler" => {
+                #[cfg(feature = "video")]
+                if !value.is_empty() && crate::video::ScaleAlgo::from_name(value).is_none() {
+                    anyhow::bail!("{}", f16n!("Unknown scaler: {}", value));
+                }
+                self.scaler = value.to_string();
+            }
+            "vf_crop" => {
+                #[cfg(feature = "video")]
+                if crate::vfilter::Crop::parse(value).is_none() {
+                    anyhow::bail!(
+                        "{}",
+                        f16n!("Invalid crop (expected auto, off or w:h[:x:y]): {}", value)
+                    );
+                }
+                self.vf_crop = value.to_string();
+            }
+            "vf_rotate" => {
+                #[cfg(feature = "video")]
+                if crate::vfilter::parse_rotate(value).is_none() {
+                    anyhow::bail!(
+                        "{}",
+                        f16n!(
+                            "Invalid rotation (expected auto, 0, 90, 180 or 270): {}",
+                            value
+                        )
+                    );
+                }
+                self.vf_rotate = value.to_string();
+            }
+            "vf_hflip" => {
+                self.vf_hflip = value.parse::<bool>()?;
+            }
+            "vf_vflip" => {
+                self.vf_vflip = value.parse::<bool>()?;
+            }
+            "vf_deinterlace" => {
+                #[cfg(feature = "video")]
+                if !value.is_empty() && crate::vfilter::Deinterlace::from_name(value).is_none() {
+                    anyhow::bail!("{}", f16n!("Unknown deinterlace mode: {}", value));
+                }
+                self.vf_deinterlace = value.to_string();
+            }
+            "vf_eq" => {
+                #[cfg(feature = "video")]
+                if crate::vfilter::VideoEq::parse(value).is_none() {
+                    anyhow::bail!("{}", f16n!("Invalid video eq setting: {}", value));
+                }
+                self.vf_eq = value.to_string();
+            }
+            "aspect" => {
+                #[cfg(feature = "video")]
+                if crate::view::Aspect::parse(value).is_none() {
+                    anyhow::bail!(
+                        "{}",
+                        f16n!(
+                            "Invalid aspect (expected fit, fill, stretch or w:h): {}",
+                            value
+                        )
+                    );
+                }
+                self.aspect = value.to_string();
+            }
+            "tile_layout" => {
+                #[cfg(feature = "video")]
+                if !value.is_empty() && crate::tiles::Layout::from_name(value).is_none() {
+                    anyhow::bail!("{}", f16n!("Unknown tile layout: {}", value));
+                }
+                self.tile_layout = value.to_string();
+            }
             _ => {
                 anyhow::bail!("{}", f16n!("Unknown config key: {}", key));
             }
@@ -82,6 +605,22 @@ impl Config {
         Ok(())
     }
 
+    pub fn get_entry(&self, key: &str) -> Result<String> {
+        let doc = toml_edit::ser::to_document(self)?;
+        let Some(value) = doc.get(key).and_then(|v| v.as_value()) else {
+            anyhow::bail!("{}", f16n!("Unknown config key: {}", key));
+        };
+        Ok(match value.as_str() {
+            Some(s) => s.to_string(),
+            None => value.to_string().trim().to_string(),
+        })
+    }
+
+    pub fn reset_entry(&mut self, key: &str) -> Result<()> {
+        let default = Config::new().get_entry(key)?;
+        self.set_entry(key, &default)
+    }
+
     pub fn write_to(&self, wr: &mut dyn Write) -> Result<()> {
         let mut src_opt = TOML_SOURCE.lock();
         let src = if let Some(s) = src_opt.take() {
@@ -91,8 +630,26 @@ impl Config {
         };
 
         let mut doc: toml_edit::DocumentMut = src.parse()?;
+        let default_doc = default_document()?;
         for (k, v) in toml_edit::ser::to_document(self)?.iter() {
-            doc[k] = v.clone();
+            match (doc.get_mut(k), v.as_value()) {
+                // 已有的值：只替换值本身，保留行内注释与格式
+                (Some(toml_edit::Item::Value(old)), Some(new)) => {
+                    let decor = old.decor().clone();
+                    *old = new.clone();
+                    *old.decor_mut() = decor;
+                }
+                (Some(old), _) => *old = v.clone(),
+                // 用户文件中缺失的项：连同默认配置中的注释一起补上
+                (None, _) => match default_doc.get_key_value(k) {
+                    Some((key, _)) => {
+                        doc.insert_formatted(key, v.clone());
+                    }
+                    None => {
+                        doc.insert(k, v.clone());
+                    }
+                },
+            }
         }
 
         let out = doc.to_string();
@@ -104,21 +661,650 @@ impl Config {
     }
 }
 
+fn default_document() -> Result<toml_edit::DocumentMut> {
+    Ok(std::str::from_utf8(DEFAULT_CONFIG_DATA)?.parse()?)
+}
+
+/// 将 `src` 中的顶层配置项覆盖到 `dst`
+fn merge_document(dst: &mut toml_edit::DocumentMut, src: &toml_edit::DocumentMut) {
+    for (k, v) in src.iter() {
+        dst[k] = v.clone();
+    }
+}
+
 fn load_config(file: File) -> Result<()> {
     let mut s = String::new();
     let mut f = file;
     f.read_to_string(&mut s)?;
+    parse_config(s)
+}
+
+fn parse_config(s: String) -> Result<()> {
+    // 以默认配置为底，缺失的项使用默认值
+    let user_doc: toml_edit::DocumentMut = s.parse()?;
+    let mut doc = default_document()?;
+    merge_document(&mut doc, &user_doc);
+
+    *PROFILES.lock() = parse_profiles(&user_doc);
 
     // 保存文档源以便后续写入保持注释
-    *TOML_SOURCE.lock() = Some(s.clone());
+    *TOML_SOURCE.lock() = Some(s);
 
     // 使用 toml_edit 的 serde 支持反序列化整个文档到 Config
-    let cfg: Config = toml_edit::de::from_str(&s)?;
+    let cfg: Config = toml_edit::de::from_document(doc)?;
     *CONFIG.lock() = cfg;
 
     Ok(())
 }
 
+// @ ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== @
+
+/// 配置段
+/// - `match`：路径通配符（字符串或数组），含 `/` 时匹配完整路径，否则只匹配文件名
+/// - `extensions`：扩展名列表
+/// - 其余键与顶层配置项相同，覆盖顶层配置
+struct Profile {
+    name: String,
+    patterns: Vec<String>,
+    extensions: Vec<String>,
+    entries: Vec<(String, toml_edit::Item)>,
+}
+
+impl Profile {
+    fn is_auto(&self) -> bool {
+        !self.patterns.is_empty() || !self.extensions.is_empty()
+    }
+
+    fn matches(&self, path: &str) -> bool {
+        let full = std::fs::canonicalize(path)
+            .map(|p| p.to_string_lossy().into_owned())
+            .unwrap_or_else(|_| path.to_string())
+            .replace('\\', "/");
+        let name = full.rsplit('/').next().unwrap_or(&full);
+        let ext = Path::new(name)
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or("");
+        let ext_matched = self
+            .extensions
+            .iter()
+            .any(|e| e.trim_start_matches('.').eq_ignore_ascii_case(ext));
+        let glob_matched = self.patterns.iter().any(|p| {
+            if p.contains('/') {
+                glob_match(p, &full)
+            } else {
+                glob_match(p, name)
+            }
+        });
+        ext_matched || glob_matched
+    }
+}
+
+fn string_list(item: Option<&toml_edit::Item>) -> Vec<String> {
+    match item.and_then(|i| i.as_value()) {
+        Some(toml_edit::Value::String(s)) => vec![s.value().clone()],
+        Some(toml_edit::Value::Array(a)) => a
+            .iter()
+            .filter_map(|v| v.as_str())
+            .map(|s| s.to_string())
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// 取出配置表中的普通配置项，忽略未知的键
+fn config_entries(
+    table: &dyn toml_edit::TableLike,
+    skip: &[&str],
+) -> Vec<(String, toml_edit::Item)> {
+    let mut entries = Vec::new();
+    for (k, v) in table.iter() {
+        if skip.contains(&k) {
+            continue;
+        }
+        if KEYS.contains(&k) {
+            entries.push((k.to_string(), v.clone()));
+        } else {
+            warning_f16n!("Unknown config key: {}", k);
+        }
+    }
+    entries
+}
+
+fn parse_profiles(doc: &toml_edit::DocumentMut) -> Vec<Profile> {
+    let Some(profiles) = doc.get("profile").and_then(|p| p.as_table_like()) else {
+        return Vec::new();
+    };
+    profiles
+        .iter()
+        .filter_map(|(name, item)| {
+            let table = item.as_table_like()?;
+            Some(Profile {
+                name: name.to_string(),
+                patterns: string_list(table.get("match")),
+                extensions: string_list(table.get("extensions")),
+                entries: config_entries(table, &["match", "extensions"]),
+            })
+        })
+        .collect()
+}
+
+/// 所有配置段的名称
+pub fn profile_names() -> Vec<String> {
+    PROFILES.lock().iter().map(|p| p.name.clone()).collect()
+}
+
+/// 选中配置段（`--profile`），对之后播放的所有文件生效
+pub fn set_profile(name: Option<&str>) -> Result<()> {
+    if let Some(name) = name {
+        if !PROFILES.lock().iter().any(|p| p.name == name) {
+            anyhow::bail!("{}", f16n!("Unknown profile: {}", name));
+        }
+    }
+    *ACTIVE_PROFILE.lock() = name.map(|n| n.to_string());
+    Ok(())
+}
+
+/// 读取媒体所在目录下的 `.tvid.toml`
+fn load_dir_config(path: &str) -> Result<Vec<(String, toml_edit::Item)>> {
+    let Some(dir) = Path::new(path).parent() else {
+        return Ok(Vec::new());
+    };
+    let file = dir.join(DIR_CONFIG_FILE);
+    if !file.is_file() {
+        return Ok(Vec::new());
+    }
+    let doc: toml_edit::DocumentMut = std::fs::read_to_string(&file)?.parse()?;
+    Ok(config_entries(doc.as_table(), &["profile"]))
+}
+
+/// 计算播放 `path` 时实际生效的配置
+/// 优先级（从低到高）：顶层配置、自动匹配的配置段、目录下的 `.tvid.toml`、`--profile` 选中的配置段、临时覆盖项
+fn effective_config(path: Option<&str>) -> Result<Config> {
+    let mut doc = toml_edit::ser::to_document(&*CONFIG.lock())?;
+    let profiles = PROFILES.lock();
+
+    if let Some(path) = path {
+        for profile in profiles.iter().filter(|p| p.is_auto() && p.matches(path)) {
+            for (k, v) in &profile.entries {
+                doc[k.as_str()] = v.clone();
+            }
+        }
+
+        match load_dir_config(path) {
+            Ok(entries) => {
+                for (k, v) in entries {
+                    doc[k.as_str()] = v;
+                }
+            }
+            Err(err) => warning_f16n!("Failed to load {}: {}", DIR_CONFIG_FILE, err),
+        }
+    }
+
+    if let Some(name) = ACTIVE_PROFILE.lock().as_deref() {
+        if let Some(profile) = profiles.iter().find(|p| p.name == name) {
+            for (k, v) in &profile.entries {
+                doc[k.as_str()] = v.clone();
+            }
+        }
+    }
+
+    let mut cfg: Config = toml_edit::de::from_document(doc)?;
+    for (k, v) in OVERRIDES.lock().iter() {
+        cfg.set_entry(k, v)?;
+    }
+    Ok(cfg)
+}
+
+/// 临时覆盖配置项（如命令行参数），不写回配置文件
+pub fn set_override(key: &str, value: &str) -> Result<()> {
+    Config::new().set_entry(key, value)?;
+    let mut overrides = OVERRIDES.lock();
+    overrides.retain(|(k, _)| k != key);
+    overrides.push((key.to_string(), value.to_string()));
+    Ok(())
+}
+
+fn current_path() -> Option<String> {
+    PLAYLIST.lock().current().cloned()
+}
+
+/// 播放 `path` 时覆盖顶层配置项 `key` 的来源（优先级最高的一个），没有时返回 `None`
+fn overriding_source(key: &str, path: Option<&str>) -> Option<String> {
+    let profiles = PROFILES.lock();
+    let has_key = |entries: &[(String, toml_edit::Item)]| entries.iter().any(|(k, _)| k == key);
+
+    if let Some(name) = ACTIVE_PROFILE.lock().as_deref()
+        && profiles
+            .iter()
+            .any(|p| p.name == name && has_key(&p.entries))
+    {
+        return Some(format!("[profile.{name}]"));
+    }
+    let path = path?;
+    if load_dir_config(path).is_ok_and(|entries| has_key(&entries)) {
+        let dir = Path::new(path).parent()?;
+        return Some(dir.join(DIR_CONFIG_FILE).display().to_string());
+    }
+    profiles
+        .iter()
+        .rev()
+        .find(|p| p.is_auto() && p.matches(path) && has_key(&p.entries))
+        .map(|p| format!("[profile.{}]", p.name))
+}
+
+/// 修改或恢复顶层配置后，实际生效的值被配置段或目录配置覆盖时提示
+fn warn_if_overridden(key: &str) {
+    let path = current_path();
+    let Ok(effective) = effective_config(path.as_deref()).and_then(|c| c.get_entry(key)) else {
+        return;
+    };
+    let Ok(configured) = CONFIG.lock().get_entry(key) else {
+        return;
+    };
+    if effective != configured
+        && let Some(source) = overriding_source(key, path.as_deref())
+    {
+        warning_f16n!("{} is overridden by {}", key, source);
+    }
+}
+
+/// 修改配置项，保存到配置文件并立即生效
+pub fn set(key: &str, value: &str) -> Result<()> {
+    CONFIG.lock().set_entry(key, value)?;
+    OVERRIDES.lock().retain(|(k, _)| k != key);
+    save_config_file(None)?;
+    apply();
+    warn_if_overridden(key);
+    Ok(())
+}
+
+/// 读取当前实际生效的配置项
+pub fn get(key: &str) -> Result<String> {
+    effective_config(current_path().as_deref())?.get_entry(key)
+}
+
+/// 将配置项恢复为默认值，保存到配置文件并立即生效
+pub fn reset(key: &str) -> Result<()> {
+    CONFIG.lock().reset_entry(key)?;
+    OVERRIDES.lock().retain(|(k, _)| k != key);
+    save_config_file(None)?;
+    apply();
+    warn_if_overridden(key);
+    Ok(())
+}
+
+/// 保存某个音频输出设备的延迟（`audio_delays` 表）
+#[cfg(feature = "audio")]
+pub fn set_device_audio_delay(device: &str, secs: f64) -> Result<()> {
+    CONFIG.lock().audio_delays.insert(device.to_string(), secs);
+    save_config_file(None)
+}
+
+/// 配置项的候选值，用于命令补全
+pub fn value_suggestions(key: &str) -> Vec<String> {
+    let list = |items: &[&str]| items.iter().map(|s| s.to_string()).collect();
+    match key {
+        "volume" => list(&["0", "50", "100", "150", "200"]),
+        "looping" | "show_overlay" | "show_progressbar" | "show_visualizer" | "af_drc"
+        | "vd_auto_degrade" | "vf_hflip" | "vf_vflip" | "ascii_edges" => list(&["true", "false"]),
+        "lang" => list(crate::LANGS),
+        "output_budget" => list(&["0", "262144", "1048576", "4194304"]),
+        "color_threshold" => list(&["0", "2", "4", "8"]),
+        "color_mode" => ColorMode::ALL
+            .iter()
+            .map(|m| m.name().to_string())
+            .collect(),
+        "chroma_mode" => ChromaMode::ALL
+            .iter()
+            .map(|m| m.name().to_string())
+            .collect(),
+        "dither" => Dither::ALL.iter().map(|m| m.name().to_string()).collect(),
+        "color_match" => ColorMatch::ALL
+            .iter()
+            .map(|m| m.name().to_string())
+            .collect(),
+        "sixel_palette" => SixelPalette::ALL
+            .iter()
+            .map(|m| m.name().to_string())
+            .collect(),
+        "ascii_ramp" => ascii::RAMPS.iter().map(|(n, _)| n.to_string()).collect(),
+        "seek_small" => list(&["1", "5", "10"]),
+        "seek_large" => list(&["30", "60", "300"]),
+        "subtitle_delay" => list(&["-1", "-0.5", "0", "0.5", "1"]),
+        "subtitle_color" => list(&["#ffffff", "#ffff00", "#00ffff"]),
+        "progressbar_height" => list(&["8", "16", "32"]),
+        "message_timeout" => list(&["3", "5", "10"]),
+        #[cfg(feature = "audio")]
+        "audio_device" => crate::audio::output_device_names(),
+        #[cfg(feature = "audio")]
+        "visualizer" => crate::visualizer::Visualizer::ALL
+            .iter()
+            .map(|m| m.name().to_string())
+            .collect(),
+        #[cfg(feature = "audio")]
+        "af_eq" => crate::afilter::EQ_PRESETS
+            .iter()
+            .map(|(n, _)| n.to_string())
+            .collect(),
+        #[cfg(feature = "audio")]
+        "af_normalize" => crate::afilter::Normalize::ALL
+            .iter()
+            .map(|m| m.name().to_string())
+            .collect(),
+        "sync_master" => MasterClock::ALL
+            .iter()
+            .map(|m| m.name().to_string())
+            .collect(),
+        "audio_delay" => list(&["-0.2", "-0.1", "0", "0.1"]),
+        "af_normalize_target" => list(&["-23", "-18", "-16", "-14"]),
+        #[cfg(feature = "audio")]
+        "af_downmix" => crate::afilter::Downmix::ALL
+            .iter()
+            .map(|m| m.name().to_string())
+            .collect(),
+        "crossfade" => list(&["0", "2", "5", "10"]),
+        "vd_threads" => list(&["0", "1", "2", "4", "8"]),
+        #[cfg(feature = "video")]
+        "vd_thread_type" => crate::vdecoder::ThreadType::ALL
+            .iter()
+            .map(|m| m.name().to_string())
+            .collect(),
+        #[cfg(feature = "video")]
+        "vd_skip_loop_filter" | "vd_skip_frame" => crate::vdecoder::Skip::ALL
+            .iter()
+            .map(|m| m.name().to_string())
+            .collect(),
+        "vd_lowres" => list(&["auto", "0", "1", "2", "3"]),
+        #[cfg(feature = "video")]
+        "scaler" => crate::video::ScaleAlgo::ALL
+            .iter()
+            .map(|m| m.name().to_string())
+            .collect(),
+        "vf_crop" => list(&["off", "auto"]),
+        "vf_rotate" => list(&["auto", "0", "90", "180", "270"]),
+        #[cfg(feature = "video")]
+        "vf_deinterlace" => crate::vfilter::Deinterlace::ALL
+            .iter()
+            .map(|m| m.name().to_string())
+            .collect(),
+        "vf_eq" => list(&["brightness=0.05:contrast=1.1", "saturation=1.3"]),
+        #[cfg(feature = "video")]
+        "aspect" => list(crate::view::Aspect::NAMES),
+        #[cfg(feature = "video")]
+        "tile_layout" => crate::tiles::Layout::ALL
+            .iter()
+            .map(|m| m.name().to_string())
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+// @ ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== @
+
+/// 将当前配置应用到运行时状态
+pub fn apply() {
+    let cfg = effective_config(current_path().as_deref()).unwrap_or_else(|err| {
+        warning_f16n!("Failed to apply profile: {}", err);
+        CONFIG.lock().clone()
+    });
+    let prev = APPLIED_CONFIG.lock().replace(cfg.clone());
+    apply_config(&cfg, prev.as_ref());
+}
+
+/// 是否指定了颜色模式（配置文件或命令行），没有指定时启动时使用探测结果
+pub fn color_mode_configured() -> bool {
+    let cfg = effective_config(current_path().as_deref()).unwrap_or_else(|_| CONFIG.lock().clone());
+    !cfg.color_mode.is_empty()
+}
+
+/// 按播放的文件应用配置段，实际配置有变化时返回 `true`
+pub fn apply_for(path: &str) -> bool {
+    let cfg = effective_config(Some(path)).unwrap_or_else(|err| {
+        warning_f16n!("Failed to apply profile: {}", err);
+        CONFIG.lock().clone()
+    });
+    let mut applied = APPLIED_CONFIG.lock();
+    if applied.as_ref() == Some(&cfg) {
+        return false;
+    }
+    let prev = applied.replace(cfg.clone());
+    drop(applied);
+    apply_config(&cfg, prev.as_ref());
+    true
+}
+
+/// 应用配置，`prev` 为上一次应用的配置，只有值变化的配置项才会重新设置，
+/// 这样 `/set`、重新加载配置或切换文件时不会覆盖用户在运行时的选择（如按键切换的布局）
+fn apply_config(cfg: &Config, prev: Option<&Config>) {
+    macro_rules! changed {
+        ($($field:ident),+) => {
+            prev.is_none_or(|p| $(p.$field != cfg.$field)||+)
+        };
+    }
+
+    #[cfg(feature = "i18n")]
+    if changed!(lang) && !cfg.lang.is_empty() {
+        crate::set_lang(&cfg.lang);
+    }
+
+    #[cfg(feature = "audio")]
+    {
+        if changed!(volume) {
+            crate::audio::set_volume(cfg.volume as f32 / 100.0);
+        }
+        if changed!(audio_device) {
+            crate::audio::set_output_device(Some(cfg.audio_device.clone()));
+        }
+        if changed!(audio_delay, audio_delays) {
+            crate::audio::set_audio_delays(cfg.audio_delay, cfg.audio_delays.clone());
+        }
+        if changed!(show_visualizer) {
+            crate::render::set_show_audio_visualizer(cfg.show_visualizer);
+        }
+        if changed!(visualizer) {
+            crate::visualizer::set_mode(
+                crate::visualizer::Visualizer::from_name(&cfg.visualizer)
+                    .unwrap_or(crate::visualizer::Visualizer::Volume),
+            );
+        }
+
+        use crate::afilter::{self, Downmix, Normalize};
+        if changed!(af_eq) {
+            afilter::set_eq(afilter::parse_eq(&cfg.af_eq).unwrap_or_default());
+        }
+        if changed!(af_normalize) {
+            afilter::set_normalize(
+                Normalize::from_name(&cfg.af_normalize).unwrap_or(Normalize::Off),
+            );
+        }
+        if changed!(af_normalize_target) {
+            afilter::set_normalize_target(cfg.af_normalize_target as f32);
+        }
+        if changed!(af_drc) {
+            afilter::set_drc(cfg.af_drc);
+        }
+        if changed!(af_downmix) {
+            afilter::set_downmix(Downmix::from_name(&cfg.af_downmix).unwrap_or(Downmix::Auto));
+        }
+        if changed!(crossfade) {
+            crate::audio::set_crossfade(cfg.crossfade);
+        }
+    }
+
+    #[cfg(feature = "video")]
+    {
+        use crate::vdecoder::{self, Skip, ThreadType};
+        if changed!(vd_threads) {
+            vdecoder::set_threads(cfg.vd_threads);
+        }
+        if changed!(vd_thread_type) {
+            vdecoder::set_thread_type(
+                ThreadType::from_name(&cfg.vd_thread_type).unwrap_or(ThreadType::Auto),
+            );
+        }
+        if changed!(vd_skip_loop_filter) {
+            vdecoder::set_skip_loop_filter(
+                Skip::from_name(&cfg.vd_skip_loop_filter).unwrap_or(Skip::Default),
+            );
+        }
+        if changed!(vd_skip_frame) {
+            vdecoder::set_skip_frame(Skip::from_name(&cfg.vd_skip_frame).unwrap_or(Skip::Default));
+        }
+        if changed!(vd_lowres) {
+            vdecoder::set_lowres(vdecoder::parse_lowres(&cfg.vd_lowres).unwrap_or(None));
+        }
+        if changed!(vd_auto_degrade) {
+            vdecoder::set_auto_degrade(cfg.vd_auto_degrade);
+        }
+
+        use crate::video::{self, ScaleAlgo};
+        if changed!(scaler) {
+            video::set_scale_algo(ScaleAlgo::from_name(&cfg.scaler).unwrap_or(ScaleAlgo::Auto));
+        }
+
+        use crate::vfilter::{self, Crop, Deinterlace, VideoEq};
+        if changed!(vf_crop) {
+            vfilter::set_crop(Crop::parse(&cfg.vf_crop).unwrap_or(Crop::Off));
+        }
+        if changed!(vf_rotate) {
+            vfilter::set_rotate(vfilter::parse_rotate(&cfg.vf_rotate).unwrap_or(None));
+        }
+        if changed!(vf_hflip) {
+            vfilter::set_hflip(cfg.vf_hflip);
+        }
+        if changed!(vf_vflip) {
+            vfilter::set_vflip(cfg.vf_vflip);
+        }
+        if changed!(vf_deinterlace) {
+            vfilter::set_deinterlace(
+                Deinterlace::from_name(&cfg.vf_deinterlace).unwrap_or(Deinterlace::Auto),
+            );
+        }
+        if changed!(vf_eq) {
+            vfilter::set_eq(VideoEq::parse(&cfg.vf_eq).unwrap_or(VideoEq::DEFAULT));
+        }
+
+        use crate::view::{self, Aspect};
+        if changed!(aspect) {
+            view::set_aspect(Aspect::parse(&cfg.aspect).unwrap_or(Aspect::Fit));
+        }
+
+        use crate::tiles::{self, Layout};
+        if changed!(tile_layout) {
+            tiles::set_layout(Layout::from_name(&cfg.tile_layout).unwrap_or(Layout::SideBySide));
+        }
+    }
+
+    if changed!(looping) {
+        PLAYLIST.lock().set_looping(cfg.looping);
+    }
+
+    if changed!(sync_master) {
+        avsync::set_master_clock(
+            MasterClock::from_name(&cfg.sync_master).unwrap_or(MasterClock::Audio),
+        );
+    }
+
+    *crate::SEEK_SMALL_STEP.lock() = cfg.seek_small;
+    *crate::SEEK_LARGE_STEP.lock() = cfg.seek_large;
+
+    {
+        let mut ctx = RENDER_CONTEXT.lock();
+        if changed!(color_mode) {
+            if let Some(color_mode) = ColorMode::from_name(&cfg.color_mode) {
+                ctx.set_color_mode(color_mode);
+            } else if cfg.color_mode.is_empty() && prev.is_some() {
+                // 运行时清空了颜色模式，恢复为探测结果；启动时由 main 在探测后设置
+                ctx.set_color_mode(crate::termcap::color_mode());
+            }
+        }
+        if changed!(chroma_mode)
+            && let Some(chroma_mode) = ChromaMode::from_name(&cfg.chroma_mode)
+        {
+            ctx.chroma_mode = chroma_mode;
+            ctx.force_flush_next();
+        }
+    }
+
+    if changed!(dither) {
+        dither::set_dither(Dither::from_name(&cfg.dither).unwrap_or(Dither::BlueNoise));
+    }
+    if changed!(color_match) {
+        dither::set_color_match(
+            ColorMatch::from_name(&cfg.color_match).unwrap_or(ColorMatch::OkLab),
+        );
+    }
+    if changed!(sixel_palette) {
+        dither::set_sixel_palette(
+            SixelPalette::from_name(&cfg.sixel_palette).unwrap_or(SixelPalette::Adaptive),
+        );
+    }
+    ascii::set_ramp(ascii::parse_ramp(&cfg.ascii_ramp).unwrap_or_default());
+    ascii::set_edges(cfg.ascii_edges);
+    crate::ratectl::set_budget(cfg.output_budget);
+    crate::escape::set_color_threshold(cfg.color_threshold);
+
+    #[cfg(feature = "subtitle")]
+    {
+        if changed!(subtitle_delay) {
+            crate::subtitle::set_delay(cfg.subtitle_delay);
+        }
+        if changed!(subtitle_color) {
+            crate::subtitle::set_color(Color::from_hex(&cfg.subtitle_color));
+        }
+    }
+
+    if changed!(show_overlay) {
+        ui::SHOW_OVERLAY_TEXT.store(cfg.show_overlay, Ordering::SeqCst);
+    }
+    if changed!(show_progressbar) {
+        ui::SHOW_PROGRESSBAR.store(cfg.show_progressbar, Ordering::SeqCst);
+    }
+    ui::set_progressbar_height(cfg.progressbar_height);
+
+    logging::set_message_timeout(Duration::from_secs_f64(cfg.message_timeout));
+}
+
+/// 监视配置文件，被外部修改时重新加载并应用
+pub fn watch_main() {
+    let cfg = CONFIG_DIR;
+    let path = Path::new(cfg.as_str()).join(CONFIG_FILE);
+    let modified = |p: &Path| std::fs::metadata(p).and_then(|m| m.modified()).ok();
+
+    let mut last = modified(&path);
+    let mut pending = false;
+    while TERM_QUIT.load(Ordering::SeqCst) == false {
+        std::thread::sleep(Duration::from_millis(500));
+        // 等待文件修改时间稳定后再读取，避免读到写了一半的文件
+        let now = modified(&path);
+        if now != last {
+            last = now;
+            pending = true;
+            continue;
+        }
+        if !pending {
+            continue;
+        }
+        pending = false;
+
+        let Ok(content) = std::fs::read_to_string(&path) else {
+            continue;
+        };
+        // 自己写入的内容不需要重新加载
+        if TOML_SOURCE.lock().as_deref() == Some(content.as_str()) {
+            continue;
+        }
+        match parse_config(content) {
+            Ok(()) => {
+                apply();
+                info_l10n!("Config reloaded");
+            }
+            Err(err) => warning_f16n!("Failed to reload config: {}", err),
+        }
+    }
+}
+
 fn load_playlist(mut file: File) -> Result<()> {
     let mut s = String::new();
     file.read_to_string(&mut s)?;
@@ -158,13 +1344,23 @@ fn save_playlist(mut file: File) -> Result<()> {
     Ok(())
 }
 
-pub fn save(dir: Option<&str>) -> Result<()> {
+/// 只保存配置文件
+pub fn save_config_file(dir: Option<&str>) -> Result<()> {
     let cfg = CONFIG_DIR;
     let dir = dir.unwrap_or(cfg.as_str());
 
     let path = Path::new(dir).join(CONFIG_FILE);
     save_config(File::create(path)?)?;
 
+    Ok(())
+}
+
+pub fn save(dir: Option<&str>) -> Result<()> {
+    save_config_file(dir)?;
+
+    let cfg = CONFIG_DIR;
+    let dir = dir.unwrap_or(cfg.as_str());
+
     let path = Path::new(dir).join(PLAYLIST_FILE);
     save_playlist(File::create(path)?)?;
 
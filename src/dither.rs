@@ -0,0 +1,822 @@
+use parking_lot::Mutex;
+use std::sync::OnceLock;
+#[cfg(feature = "sixel")]
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use crate::util::{Color, ColorMode, gamma_reverse, palette256_to_color, try_palette256};
+
+// @ ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== @
+
+/// 抖动算法
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dither {
+    /// 不抖动，每个像素独立取最接近的颜色
+    None,
+    /// 8x8 Bayer 有序抖动
+    Bayer,
+    /// Floyd–Steinberg 误差扩散，画面静止的部分沿用上一帧的结果
+    FloydSteinberg,
+    /// 蓝噪声抖动，颗粒比 Bayer 自然，且不会随帧闪烁
+    BlueNoise,
+}
+
+impl Dither {
+    pub const ALL: &[Dither] = &[
+        Dither::None,
+        Dither::Bayer,
+        Dither::FloydSteinberg,
+        Dither::BlueNoise,
+    ];
+
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Dither::None => "none",
+            Dither::Bayer => "bayer",
+            Dither::FloydSteinberg => "floyd-steinberg",
+            Dither::BlueNoise => "blue-noise",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.trim().to_lowercase();
+        Self::ALL.iter().copied().find(|m| m.name() == name)
+    }
+}
+
+/// 匹配调色板颜色时使用的色差公式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorMatch {
+    /// sRGB 欧氏距离
+    Rgb,
+    /// OKLab 欧氏距离
+    OkLab,
+    /// CIELAB 下的 CIEDE2000 色差，最准确也最慢
+    Ciede2000,
+}
+
+impl ColorMatch {
+    pub const ALL: &[ColorMatch] = &[ColorMatch::Rgb, ColorMatch::OkLab, ColorMatch::Ciede2000];
+
+    pub const fn name(&self) -> &'static str {
+        match self {
+            ColorMatch::Rgb => "rgb",
+            ColorMatch::OkLab => "oklab",
+            ColorMatch::Ciede2000 => "ciede2000",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.trim().to_lowercase();
+        Self::ALL.iter().copied().find(|m| m.name() == name)
+    }
+}
+
+/// Sixel 使用的调色板
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SixelPalette {
+    /// 每帧用中位切分生成自适应调色板
+    Adaptive,
+    /// 固定的 xterm 256 色调色板
+    Xterm,
+}
+
+impl SixelPalette {
+    pub const ALL: &[SixelPalette] = &[SixelPalette::Adaptive, SixelPalette::Xterm];
+
+    pub const fn name(&self) -> &'static str {
+        match self {
+            SixelPalette::Adaptive => "adaptive",
+            SixelPalette::Xterm => "xterm",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.trim().to_lowercase();
+        Self::ALL.iter().copied().find(|m| m.name() == name)
+    }
+}
+
+static DITHER: Mutex<Dither> = Mutex::new(Dither::BlueNoise);
+static COLOR_MATCH: AtomicU8 = AtomicU8::new(ColorMatch::OkLab as u8);
+static SIXEL_PALETTE: Mutex<SixelPalette> = Mutex::new(SixelPalette::Adaptive);
+
+pub fn set_dither(dither: Dither) {
+    *DITHER.lock() = dither;
+}
+
+pub fn set_color_match(color_match: ColorMatch) {
+    COLOR_MATCH.store(color_match as u8, Ordering::SeqCst);
+}
+
+fn color_match() -> ColorMatch {
+    ColorMatch::ALL[COLOR_MATCH.load(Ordering::SeqCst) as usize]
+}
+
+pub fn set_sixel_palette(palette: SixelPalette) {
+    *SIXEL_PALETTE.lock() = palette;
+}
+
+/// 用于叠加文本的抖动状态
+pub fn status() -> String {
+    format!("{} / {}", DITHER.lock().name(), color_match().name())
+}
+
+// @ ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== @
+// @ 色彩空间 @
+
+fn linear(c: Color) -> [f32; 3] {
+    [
+        gamma_reverse(c.r as f32 / 255.0),
+        gamma_reverse(c.g as f32 / 255.0),
+        gamma_reverse(c.b as f32 / 255.0),
+    ]
+}
+
+fn oklab(c: Color) -> [f32; 3] {
+    let [r, g, b] = linear(c);
+    let l = (0.41222146 * r + 0.53633255 * g + 0.051445995 * b).cbrt();
+    let m = (0.2119035 * r + 0.6806995 * g + 0.10739696 * b).cbrt();
+    let s = (0.08830246 * r + 0.28171885 * g + 0.6299787 * b).cbrt();
+    [
+        0.21045426 * l + 0.7936178 * m - 0.004072047 * s,
+        1.9779985 * l - 2.4285922 * m + 0.4505937 * s,
+        0.025904037 * l + 0.78277177 * m - 0.80867577 * s,
+    ]
+}
+
+/// D65 白点下的 CIELAB
+fn cielab(c: Color) -> [f32; 3] {
+    let [r, g, b] = linear(c);
+    let x = (0.4124564 * r + 0.3575761 * g + 0.1804375 * b) / 0.95047;
+    let y = 0.2126729 * r + 0.7151522 * g + 0.0721750 * b;
+    let z = (0.0193339 * r + 0.119192 * g + 0.9503041 * b) / 1.08883;
+    let f = |t: f32| {
+        if t > 216.0 / 24389.0 {
+            t.cbrt()
+        } else {
+            (24389.0 / 27.0 * t + 16.0) / 116.0
+        }
+    };
+    let (fx, fy, fz) = (f(x), f(y), f(z));
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
+
+fn ciede2000(a: [f32; 3], b: [f32; 3]) -> f32 {
+    use std::f32::consts::PI;
+    let [l1, a1, b1] = a;
+    let [l2, a2, b2] = b;
+    let c_bar = ((a1 * a1 + b1 * b1).sqrt() + (a2 * a2 + b2 * b2).sqrt()) / 2.0;
+    let c_bar7 = c_bar.powi(7);
+    let g = 0.5 * (1.0 - (c_bar7 / (c_bar7 + 25f32.powi(7))).sqrt());
+    let (a1, a2) = (a1 * (1.0 + g), a2 * (1.0 + g));
+    let (c1, c2) = ((a1 * a1 + b1 * b1).sqrt(), (a2 * a2 + b2 * b2).sqrt());
+    let hue = |b: f32, a: f32| {
+        if a == 0.0 && b == 0.0 {
+            0.0
+        } else {
+            b.atan2(a).rem_euclid(2.0 * PI)
+        }
+    };
+    let (h1, h2) = (hue(b1, a1), hue(b2, a2));
+
+    let dl = l2 - l1;
+    let dc = c2 - c1;
+    let dh = if c1 * c2 == 0.0 {
+        0.0
+    } else if (h2 - h1).abs() <= PI {
+        h2 - h1
+    } else if h2 - h1 > PI {
+        h2 - h1 - 2.0 * PI
+    } else {
+        h2 - h1 + 2.0 * PI
+    };
+    let dh = 2.0 * (c1 * c2).sqrt() * (dh / 2.0).sin();
+
+    let l_bar = (l1 + l2) / 2.0;
+    let c_bar = (c1 + c2) / 2.0;
+    let h_bar = if c1 * c2 == 0.0 {
+        h1 + h2
+    } else if (h1 - h2).abs() <= PI {
+        (h1 + h2) / 2.0
+    } else if h1 + h2 < 2.0 * PI {
+        (h1 + h2 + 2.0 * PI) / 2.0
+    } else {
+        (h1 + h2 - 2.0 * PI) / 2.0
+    };
+    let t = 1.0 - 0.17 * (h_bar - PI / 6.0).cos()
+        + 0.24 * (2.0 * h_bar).cos()
+        + 0.32 * (3.0 * h_bar + PI / 30.0).cos()
+        - 0.20 * (4.0 * h_bar - 63.0 * PI / 180.0).cos();
+    let d_theta = PI / 6.0 * (-((h_bar * 180.0 / PI - 275.0) / 25.0).powi(2)).exp();
+    let c_bar7 = c_bar.powi(7);
+    let rc = 2.0 * (c_bar7 / (c_bar7 + 25f32.powi(7))).sqrt();
+    let l50 = (l_bar - 50.0) * (l_bar - 50.0);
+    let sl = 1.0 + 0.015 * l50 / (20.0 + l50).sqrt();
+    let sc = 1.0 + 0.045 * c_bar;
+    let sh = 1.0 + 0.015 * c_bar * t;
+    let rt = -(2.0 * d_theta).sin() * rc;
+
+    let (tl, tc, th) = (dl / sl, dc / sc, dh / sh);
+    (tl * tl + tc * tc + th * th + rt * tc * th).sqrt()
+}
+
+fn squared(a: [f32; 3], b: [f32; 3]) -> f32 {
+    let (d0, d1, d2) = (a[0] - b[0], a[1] - b[1], a[2] - b[2]);
+    d0 * d0 + d1 * d1 + d2 * d2
+}
+
+// @ ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== @
+// @ 调色板匹配 @
+
+/// 按色差公式预先计算好坐标的调色板
+struct Matcher {
+    metric: ColorMatch,
+    coords: Vec<[f32; 3]>,
+}
+
+impl Matcher {
+    fn new(metric: ColorMatch, palette: &[Color]) -> Self {
+        let coords = palette.iter().map(|&c| Self::coord(metric, c)).collect();
+        Self { metric, coords }
+    }
+
+    fn coord(metric: ColorMatch, c: Color) -> [f32; 3] {
+        match metric {
+            ColorMatch::Rgb => [c.r as f32, c.g as f32, c.b as f32],
+            ColorMatch::OkLab => oklab(c),
+            ColorMatch::Ciede2000 => cielab(c),
+        }
+    }
+
+    fn nearest(&self, c: Color) -> usize {
+        let p = Self::coord(self.metric, c);
+        let mut best = [(f32::MAX, 0usize); 6];
+        for (i, &q) in self.coords.iter().enumerate() {
+            let d = squared(p, q);
+            if d < best[best.len() - 1].0 {
+                // 插入排序，保留最近的几个候选
+                let mut j = best.len() - 1;
+                while j > 0 && best[j - 1].0 > d {
+                    best[j] = best[j - 1];
+                    j -= 1;
+                }
+                best[j] = (d, i);
+            }
+        }
+        if self.metric != ColorMatch::Ciede2000 {
+            return best[0].1;
+        }
+        // CIEDE2000 太慢，只在 ΔE76 最近的几个候选中比较
+        best.iter()
+            .filter(|(d, _)| *d < f32::MAX)
+            .map(|&(_, i)| (ciede2000(p, self.coords[i]), i))
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+            .map_or(0, |(_, i)| i)
+    }
+}
+
+/// 把颜色截断到每通道 5 位，作为查找表的键
+const fn lut_key(c: Color) -> usize {
+    ((c.r as usize >> 3) << 10) | ((c.g as usize >> 3) << 5) | (c.b as usize >> 3)
+}
+
+/// 查找表键对应的代表颜色
+const fn lut_color(key: usize) -> Color {
+    Color::new(
+        ((key >> 10 & 31) << 3 | 4) as u8,
+        ((key >> 5 & 31) << 3 | 4) as u8,
+        ((key & 31) << 3 | 4) as u8,
+    )
+}
+
+/// 每种色差公式对应的 256 色查找表
+/// - 不使用前 16 色，它们的实际颜色取决于终端主题
+fn palette256_lut(metric: ColorMatch) -> &'static [u8] {
+    static LUTS: [OnceLock<Box<[u8]>>; 3] = [OnceLock::new(), OnceLock::new(), OnceLock::new()];
+    LUTS[metric as usize].get_or_init(|| {
+        let palette: Vec<Color> = (16..=255).map(palette256_to_color).collect();
+        let matcher = Matcher::new(metric, &palette);
+        (0..1 << 15)
+            .map(|key| matcher.nearest(lut_color(key)) as u8 + 16)
+            .collect()
+    })
+}
+
+/// 在感知色彩空间中查找最接近的 256 色索引
+pub fn palette256_nearest(c: Color) -> u8 {
+    if let Some(index) = try_palette256(c) {
+        return index;
+    }
+    palette256_lut(color_match())[lut_key(c)]
+}
+
+// @ ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== @
+// @ 阈值图 @
+
+/// 8x8 Bayer 矩阵的阈值，范围 0 到 1
+fn bayer(x: usize, y: usize) -> f32 {
+    let mut v = 0;
+    for bit in 0..3 {
+        let xb = (x >> bit ^ y >> bit) & 1;
+        let yb = (y >> bit) & 1;
+        v |= (xb << 1 | yb) << (2 * (2 - bit));
+    }
+    (v as f32 + 0.5) / 64.0
+}
+
+const BLUE_NOISE_SIZE: usize = 32;
+
+/// 用 void-and-cluster 算法生成的蓝噪声阈值图，范围 0 到 1
+fn blue_noise(x: usize, y: usize) -> f32 {
+    static TEXTURE: OnceLock<Box<[f32]>> = OnceLock::new();
+    let texture = TEXTURE.get_or_init(generate_blue_noise);
+    texture[(y % BLUE_NOISE_SIZE) * BLUE_NOISE_SIZE + x % BLUE_NOISE_SIZE]
+}
+
+fn generate_blue_noise() -> Box<[f32]> {
+    const N: usize = BLUE_NOISE_SIZE;
+    let n = N * N;
+
+    // 环绕距离上的高斯核
+    let mut kernel = vec![0f32; n];
+    for dy in 0..N {
+        for dx in 0..N {
+            let (x, y) = (dx.min(N - dx) as f32, dy.min(N - dy) as f32);
+            kernel[dy * N + dx] = (-(x * x + y * y) / (2.0 * 1.5 * 1.5)).exp();
+        }
+    }
+    let update = |energy: &mut [f32], p: usize, sign: f32| {
+        let (px, py) = (p % N, p / N);
+        for y in 0..N {
+            for x in 0..N {
+                energy[y * N + x] += sign * kernel[(y + N - py) % N * N + (x + N - px) % N];
+            }
+        }
+    };
+    let tightest = |on: &[bool], energy: &[f32]| {
+        (0..n)
+            .filter(|&p| on[p])
+            .max_by(|&a, &b| energy[a].total_cmp(&energy[b]))
+            .unwrap()
+    };
+    let largest_void = |on: &[bool], energy: &[f32]| {
+        (0..n)
+            .filter(|&p| !on[p])
+            .min_by(|&a, &b| energy[a].total_cmp(&energy[b]))
+            .unwrap()
+    };
+
+    // 固定种子的初始随机图案，约 10% 的点
+    let mut on = vec![false; n];
+    let mut energy = vec![0f32; n];
+    let mut seed = 0x2545_f491u32;
+    let mut ones = 0;
+    while ones < n / 10 {
+        seed ^= seed << 13;
+        seed ^= seed >> 17;
+        seed ^= seed << 5;
+        let p = seed as usize % n;
+        if !on[p] {
+            on[p] = true;
+            update(&mut energy, p, 1.0);
+            ones += 1;
+        }
+    }
+
+    // 反复把最密集的点移到最大的空洞，直到图案均匀
+    for _ in 0..n {
+        let t = tightest(&on, &energy);
+        on[t] = false;
+        update(&mut energy, t, -1.0);
+        let v = largest_void(&on, &energy);
+        on[v] = true;
+        update(&mut energy, v, 1.0);
+        if v == t {
+            break;
+        }
+    }
+
+    let mut rank = vec![0usize; n];
+    // 从初始图案中依次移除最密集的点，排名递减
+    let (mut on_removing, mut energy_removing) = (on.clone(), energy.clone());
+    for r in (0..ones).rev() {
+        let t = tightest(&on_removing, &energy_removing);
+        on_removing[t] = false;
+        update(&mut energy_removing, t, -1.0);
+        rank[t] = r;
+    }
+    // 依次填补最大的空洞，排名递增
+    for r in ones..n {
+        let v = largest_void(&on, &energy);
+        on[v] = true;
+        update(&mut energy, v, 1.0);
+        rank[v] = r;
+    }
+
+    rank.iter().map(|&r| (r as f32 + 0.5) / n as f32).collect()
+}
+
+// @ ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== @
+// @ 抖动 @
+
+/// 透明像素的索引
+pub const TRANSPARENT: u16 = u16::MAX;
+
+/// 误差扩散的跨帧状态
+struct DiffusionState {
+    key: (usize, usize, u64),
+    sources: Vec<Color>,
+    indices: Vec<u16>,
+}
+
+static DIFFUSION_STATE: Mutex<DiffusionState> = Mutex::new(DiffusionState {
+    key: (0, 0, 0),
+    sources: Vec::new(),
+    indices: Vec::new(),
+});
+
+/// 源像素变化不超过此值时沿用上一帧的抖动结果
+const STABLE_THRESHOLD: i32 = 6;
+
+#[derive(Clone, Copy)]
+struct Image<'a> {
+    data: &'a [Color],
+    width: usize,
+    height: usize,
+    pitch: usize,
+}
+
+/// 把图像抖动到调色板上，返回每个像素的调色板索引
+/// - `spread`：有序抖动的幅度，约为调色板相邻颜色的间距
+/// - `palette_id`：调色板变化时必须改变，用于丢弃误差扩散的跨帧状态
+fn dither(
+    image: Image,
+    spread: f32,
+    palette_id: u64,
+    mut nearest: impl FnMut(Color) -> u16,
+    palette: &[Color],
+) -> Vec<u16> {
+    let Image {
+        data,
+        width,
+        height,
+        pitch,
+    } = image;
+    let mut out = vec![TRANSPARENT; width * height];
+    let to_color = |c: [f32; 3]| {
+        Color::new(
+            c[0].clamp(0.0, 255.0) as u8,
+            c[1].clamp(0.0, 255.0) as u8,
+            c[2].clamp(0.0, 255.0) as u8,
+        )
+    };
+
+    let dither = *DITHER.lock();
+    match dither {
+        Dither::None | Dither::Bayer | Dither::BlueNoise => {
+            for y in 0..height {
+                for x in 0..width {
+                    let c = data[y * pitch + x];
+                    if c.is_transparent() {
+                        continue;
+                    }
+                    let offset = match dither {
+                        Dither::Bayer => (bayer(x % 8, y % 8) - 0.5) * spread,
+                        Dither::BlueNoise => (blue_noise(x, y) - 0.5) * spread,
+                        _ => 0.0,
+                    };
+                    let c = [
+                        c.r as f32 + offset,
+                        c.g as f32 + offset,
+                        c.b as f32 + offset,
+                    ];
+                    out[y * width + x] = nearest(to_color(c));
+                }
+            }
+        }
+        Dither::FloydSteinberg => {
+            let mut state = DIFFUSION_STATE.lock();
+            let key = (width, height, palette_id);
+            let stable = state.key == key;
+            if !stable {
+                state.key = key;
+                state.sources = vec![Color::transparent(); width * height];
+                state.indices = vec![TRANSPARENT; width * height];
+            }
+
+            // 两行误差缓冲，两侧各留一个像素
+            let mut errors = [vec![[0f32; 3]; width + 2], vec![[0f32; 3]; width + 2]];
+            for y in 0..height {
+                let (cur, next) = errors.split_at_mut(1);
+                let (cur, next) = (&mut cur[0], &mut next[0]);
+                next.fill([0.0; 3]);
+                // 蛇形扫描，避免误差总往一个方向堆积
+                let reverse = y % 2 == 1;
+                for i in 0..width {
+                    let x = if reverse { width - 1 - i } else { i };
+                    let c = data[y * pitch + x];
+                    if c.is_transparent() {
+                        continue;
+                    }
+                    let e = cur[x + 1];
+                    let want = [
+                        c.r as f32 + e[0].clamp(-64.0, 64.0),
+                        c.g as f32 + e[1].clamp(-64.0, 64.0),
+                        c.b as f32 + e[2].clamp(-64.0, 64.0),
+                    ];
+
+                    let p = y * width + x;
+                    let last = state.sources[p];
+                    let unchanged = stable
+                        && state.indices[p] != TRANSPARENT
+                        && (c.r as i32 - last.r as i32).abs() <= STABLE_THRESHOLD
+                        && (c.g as i32 - last.g as i32).abs() <= STABLE_THRESHOLD
+                        && (c.b as i32 - last.b as i32).abs() <= STABLE_THRESHOLD;
+                    let index = if unchanged {
+                        state.indices[p]
+                    } else {
+                        state.sources[p] = c;
+                        nearest(to_color(want))
+                    };
+                    state.indices[p] = index;
+                    out[p] = index;
+
+                    let q = palette[index as usize];
+                    let err = [
+                        want[0] - q.r as f32,
+                        want[1] - q.g as f32,
+                        want[2] - q.b as f32,
+                    ];
+                    let (ahead, behind) = if reverse { (x, x + 2) } else { (x + 2, x) };
+                    for k in 0..3 {
+                        cur[ahead][k] += err[k] * 7.0 / 16.0;
+                        next[behind][k] += err[k] * 3.0 / 16.0;
+                        next[x + 1][k] += err[k] * 5.0 / 16.0;
+                        next[ahead][k] += err[k] * 1.0 / 16.0;
+                    }
+                }
+                errors.swap(0, 1);
+            }
+        }
+    }
+    out
+}
+
+/// 灰度模式实际输出的灰阶，以及从输出的灰度值到灰阶下标的查找表
+/// - 输出时按 `luminance()` 取灰度，有些灰度值取不到，每个能输出的灰度值取一个代表颜色
+fn gray_ramp() -> &'static (Vec<Color>, [u8; 256]) {
+    static RAMP: OnceLock<(Vec<Color>, [u8; 256])> = OnceLock::new();
+    RAMP.get_or_init(|| {
+        let mut palette = Vec::new();
+        let mut levels = Vec::new();
+        for v in 0..=255 {
+            let gray = Color::new(v, v, v);
+            let level = gray.luminance();
+            if levels.last() != Some(&level) {
+                levels.push(level);
+                palette.push(gray);
+            }
+        }
+        let lut = std::array::from_fn(|want| {
+            let distance = |level: &u8| (*level as i32 - want as i32).abs();
+            let nearest = levels.iter().enumerate().min_by_key(|(_, l)| distance(l));
+            nearest.map_or(0, |(i, _)| i as u8)
+        });
+        (palette, lut)
+    })
+}
+
+/// 为调色板受限的颜色模式预先抖动画面
+/// - 返回的像素都是目标调色板中的颜色，之后输出时能精确匹配
+/// - 不需要抖动的模式返回 None
+pub fn process(
+    mode: ColorMode,
+    data: &[Color],
+    width: usize,
+    height: usize,
+    pitch: usize,
+) -> Option<Vec<Color>> {
+    const BLACK_WHITE: [Color; 2] = [Color::new(0, 0, 0), Color::new(255, 255, 255)];
+    static PALETTE256: OnceLock<Vec<Color>> = OnceLock::new();
+
+    if *DITHER.lock() == Dither::None {
+        return None;
+    }
+    let image = Image {
+        data,
+        width,
+        height,
+        pitch,
+    };
+    let (indices, palette) = match mode {
+        ColorMode::Palette256Only => {
+            let palette = PALETTE256.get_or_init(|| (0..=255).map(palette256_to_color).collect());
+            let metric = color_match();
+            let lut = palette256_lut(metric);
+            let nearest = |c: Color| lut[lut_key(c)] as u16;
+            (
+                dither(image, 48.0, metric as u64, nearest, palette),
+                &palette[..],
+            )
+        }
+        ColorMode::BlackWhite => {
+            let nearest = |c: Color| (c.luminance() >= 128) as u16;
+            (
+                dither(image, 255.0, 1 << 8, nearest, &BLACK_WHITE),
+                &BLACK_WHITE[..],
+            )
+        }
+        ColorMode::GrayScale => {
+            // 相邻灰阶相差一到两级
+            let (palette, lut) = gray_ramp();
+            let nearest = |c: Color| lut[c.luminance() as usize] as u16;
+            (dither(image, 2.0, 1 << 9, nearest, palette), &palette[..])
+        }
+        _ => return None,
+    };
+    Some(
+        indices
+            .into_iter()
+            .map(|i| match i {
+                TRANSPARENT => Color::transparent(),
+                i => palette[i as usize],
+            })
+            .collect(),
+    )
+}
+
+// @ ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== @
+// @ Sixel 自适应调色板 @
+
+/// Sixel 调色板的最大颜色数
+#[cfg(feature = "sixel")]
+const SIXEL_COLORS: usize = 256;
+/// 生成调色板时最多采样的像素数
+#[cfg(feature = "sixel")]
+const PALETTE_SAMPLES: usize = 16384;
+/// 评估调色板误差时采样的像素数
+#[cfg(feature = "sixel")]
+const EVALUATE_SAMPLES: usize = 2048;
+/// 新调色板的误差低于旧调色板的此比例时才切换，避免画面闪烁
+#[cfg(feature = "sixel")]
+const PALETTE_SWITCH_RATIO: f32 = 0.8;
+
+#[cfg(feature = "sixel")]
+struct SixelState {
+    palette: Vec<Color>,
+    matcher: Option<Matcher>,
+    /// 懒惰填充的查找表，u16::MAX 表示尚未计算
+    lut: Vec<u16>,
+    id: u64,
+}
+
+#[cfg(feature = "sixel")]
+static SIXEL_STATE: Mutex<SixelState> = Mutex::new(SixelState {
+    palette: Vec::new(),
+    matcher: None,
+    lut: Vec::new(),
+    id: 0,
+});
+
+#[cfg(feature = "sixel")]
+static PALETTE_ID: AtomicU64 = AtomicU64::new(1 << 16);
+
+/// 盒子中颜色范围最大的通道，返回 (优先级, 通道)
+#[cfg(feature = "sixel")]
+fn widest_channel(colors: &[Color]) -> (f32, usize) {
+    // 人眼对绿色最敏感
+    const WEIGHTS: [f32; 3] = [1.0, 1.2, 0.8];
+    if colors.len() < 2 {
+        return (0.0, 0);
+    }
+    let (lo, hi) = colors.iter().fold(([255u8; 3], [0u8; 3]), |(lo, hi), c| {
+        (
+            [lo[0].min(c.r), lo[1].min(c.g), lo[2].min(c.b)],
+            [hi[0].max(c.r), hi[1].max(c.g), hi[2].max(c.b)],
+        )
+    });
+    let weight = (colors.len() as f32).sqrt();
+    (0..3)
+        .map(|k| ((hi[k] - lo[k]) as f32 * WEIGHTS[k] * weight, k))
+        .max_by(|a, b| a.0.total_cmp(&b.0))
+        .unwrap()
+}
+
+/// 中位切分：反复把颜色范围最大的盒子从中位数处切开
+#[cfg(feature = "sixel")]
+fn median_cut(samples: &mut [Color], max_colors: usize) -> Vec<Color> {
+    let mut boxes = vec![(0, samples.len(), widest_channel(samples))];
+    while boxes.len() < max_colors {
+        let Some(i) = (0..boxes.len())
+            .filter(|&i| boxes[i].2.0 > 0.0)
+            .max_by(|&a, &b| boxes[a].2.0.total_cmp(&boxes[b].2.0))
+        else {
+            break;
+        };
+        let (start, end, (_, k)) = boxes[i];
+        samples[start..end].sort_unstable_by_key(|c| match k {
+            0 => c.r,
+            1 => c.g,
+            _ => c.b,
+        });
+        let mid = start + (end - start) / 2;
+        boxes[i] = (start, mid, widest_channel(&samples[start..mid]));
+        boxes.push((mid, end, widest_channel(&samples[mid..end])));
+    }
+    boxes
+        .iter()
+        .filter(|(start, end, _)| end > start)
+        .map(|&(start, end, _)| {
+            let (r, g, b) = samples[start..end]
+                .iter()
+                .fold((0u32, 0u32, 0u32), |(r, g, b), c| {
+                    (r + c.r as u32, g + c.g as u32, b + c.b as u32)
+                });
+            let n = (end - start) as u32;
+            Color::new((r / n) as u8, (g / n) as u8, (b / n) as u8)
+        })
+        .collect()
+}
+
+#[cfg(feature = "sixel")]
+fn sample(data: &[Color], max: usize) -> Vec<Color> {
+    let opaque = data.iter().filter(|c| !c.is_transparent()).count();
+    let step = opaque.div_ceil(max).max(1);
+    data.iter()
+        .filter(|c| !c.is_transparent())
+        .step_by(step)
+        .copied()
+        .collect()
+}
+
+/// 用 RGB 距离快速估计调色板的量化误差
+#[cfg(feature = "sixel")]
+fn palette_error(samples: &[Color], palette: &[Color]) -> f32 {
+    let rgb = |c: &Color| [c.r as f32, c.g as f32, c.b as f32];
+    let coords: Vec<_> = palette.iter().map(rgb).collect();
+    samples
+        .iter()
+        .map(|c| {
+            let p = rgb(c);
+            coords
+                .iter()
+                .map(|&q| squared(p, q))
+                .fold(f32::MAX, f32::min)
+        })
+        .sum()
+}
+
+/// 为 Sixel 量化图像
+/// - `data` 必须是连续存储的
+/// - 返回调色板和每个像素的索引，透明像素的索引为 [`TRANSPARENT`]
+#[cfg(feature = "sixel")]
+pub fn sixel_quantize(data: &[Color], width: usize, height: usize) -> (Vec<Color>, Vec<u16>) {
+    let metric = color_match();
+    let image = Image {
+        data,
+        width,
+        height,
+        pitch: width,
+    };
+    if *SIXEL_PALETTE.lock() == SixelPalette::Xterm {
+        let palette: Vec<Color> = (0..=255).map(palette256_to_color).collect();
+        let lut = palette256_lut(metric);
+        let nearest = |c: Color| lut[lut_key(c)] as u16;
+        let indices = dither(image, 32.0, metric as u64, nearest, &palette);
+        return (palette, indices);
+    }
+
+    let mut state = SIXEL_STATE.lock();
+    let mut samples = sample(data, PALETTE_SAMPLES);
+    let candidate = median_cut(&mut samples, SIXEL_COLORS);
+    let evaluate = sample(data, EVALUATE_SAMPLES);
+    let switch = state.matcher.as_ref().is_none_or(|m| m.metric != metric)
+        || palette_error(&evaluate, &candidate)
+            < palette_error(&evaluate, &state.palette) * PALETTE_SWITCH_RATIO;
+    if switch && !candidate.is_empty() {
+        state.matcher = Some(Matcher::new(metric, &candidate));
+        state.palette = candidate;
+        state.lut = vec![u16::MAX; 1 << 15];
+        state.id = PALETTE_ID.fetch_add(1, Ordering::SeqCst);
+    }
+
+    let SixelState {
+        palette,
+        matcher,
+        lut,
+        id,
+    } = &mut *state;
+    let Some(matcher) = matcher.as_ref() else {
+        return (Vec::new(), vec![TRANSPARENT; width * height]);
+    };
+    let nearest = |c: Color| {
+        let key = lut_key(c);
+        if lut[key] == u16::MAX {
+            lut[key] = matcher.nearest(lut_color(key)) as u16;
+        }
+        lut[key]
+    };
+    let indices = dither(image, 24.0, *id, nearest, palette);
+    (palette.clone(), indices)
+}
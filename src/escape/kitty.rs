@@ -0,0 +1,116 @@
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use parking_lot::Mutex;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::io::Write;
+
+use crate::termcap;
+use crate::util::{Color, ColorMode};
+
+/// Unicode 占位符，终端复用器内用它在文本中标出图像的位置
+pub const KITTY_PLACEHOLDER: char = '\u{10eeee}';
+/// 第一行画面的图像 ID，每行画面是一个单独的图像，ID 依次递增
+/// - 每行单独一个图像时，占位符不需要行列变音符号
+const IMAGE_ID_BASE: u32 = 0x74_7600;
+/// 每个转义序列携带的 base64 数据长度上限
+const CHUNK_SIZE: usize = 4096;
+
+/// 每行画面上次传输的内容哈希，没有变化的行不重新传输
+static SENT_ROWS: Mutex<Vec<Option<u64>>> = Mutex::new(Vec::new());
+
+/// 占位符的前景色，用 24 位颜色编码图像 ID
+pub fn kitty_id_color(row: usize) -> Color {
+    let id = IMAGE_ID_BASE + row as u32;
+    Color::new((id >> 16) as u8, (id >> 8) as u8, id as u8)
+}
+
+fn write_delete(buf: &mut Vec<u8>, sent: &[Option<u64>]) {
+    let mut seq = Vec::new();
+    for (row, _) in sent.iter().enumerate().filter(|(_, hash)| hash.is_some()) {
+        seq.clear();
+        write!(seq, "\x1b_Ga=d,d=I,i={},q=2\x1b\\", IMAGE_ID_BASE + row as u32).unwrap();
+        termcap::write_graphics(buf, &seq, ColorMode::Kitty);
+    }
+}
+
+/// 删除已传输的图像，切换到其他颜色模式时调用
+pub fn kitty_reset(buf: &mut Vec<u8>) {
+    let mut sent = SENT_ROWS.lock();
+    if !sent.is_empty() {
+        write_delete(buf, &sent);
+        sent.clear();
+    }
+}
+
+/// 按行传输画面
+/// - 直接放置时图像位于文本之下（`z=-1`），界面文本不会被遮住
+/// - 在终端复用器内使用虚拟放置，由 `render_video` 输出的占位符显示
+/// - `force` 为真时删除之前的图像并重新传输所有行
+/// - `origin` 是画面左上角所在的 (行, 列)
+pub fn format_kitty(
+    buf: &mut Vec<u8>,
+    data: &[Color],
+    width: usize,
+    height: usize,
+    pitch: usize,
+    origin: (usize, usize),
+    cells_width: usize,
+    cells_height: usize,
+    force: bool,
+) {
+    let placeholders = termcap::kitty_placeholders();
+    let mut sent = SENT_ROWS.lock();
+    if force || sent.len() != cells_height {
+        write_delete(buf, &sent);
+        sent.clear();
+        sent.resize(cells_height, None);
+    }
+
+    let mut strip = Vec::new();
+    let mut seq = Vec::new();
+    for (row, last) in sent.iter_mut().enumerate() {
+        let (y0, y1) = (row * height / cells_height, (row + 1) * height / cells_height);
+        if y1 <= y0 || width == 0 {
+            continue;
+        }
+        strip.clear();
+        for y in y0..y1 {
+            let line = &data[y * pitch..y * pitch + width];
+            strip.extend_from_slice(unsafe {
+                std::slice::from_raw_parts(line.as_ptr() as *const u8, size_of_val(line))
+            });
+        }
+        let mut hasher = DefaultHasher::new();
+        strip.hash(&mut hasher);
+        let hash = hasher.finish();
+        if *last == Some(hash) {
+            continue;
+        }
+        *last = Some(hash);
+
+        let id = IMAGE_ID_BASE + row as u32;
+        let keys = if placeholders {
+            format!("a=T,U=1,f=32,s={width},v={},i={id},c={cells_width},r=1,q=2", y1 - y0)
+        } else {
+            write!(buf, "\x1b[{};{}H", origin.0 + row + 1, origin.1 + 1).unwrap();
+            format!(
+                "a=T,f=32,s={width},v={},i={id},p=1,c={cells_width},r=1,C=1,z=-1,q=2",
+                y1 - y0
+            )
+        };
+        let encoded = BASE64.encode(&strip);
+        let chunks = encoded.as_bytes().chunks(CHUNK_SIZE);
+        let count = chunks.len();
+        for (i, chunk) in chunks.enumerate() {
+            let more = (i + 1 < count) as u8;
+            seq.clear();
+            if i == 0 {
+                write!(seq, "\x1b_G{keys},m={more};").unwrap();
+            } else {
+                write!(seq, "\x1b_Gm={more};").unwrap();
+            }
+            seq.extend_from_slice(chunk);
+            seq.extend_from_slice(b"\x1b\\");
+            termcap::write_graphics(buf, &seq, ColorMode::Kitty);
+        }
+    }
+}
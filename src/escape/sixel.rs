@@ -1,31 +1,25 @@
 use std::io::Write;
 
-use crate::util::{Color, JoinAll, palette256_from_color, palette256_to_color};
+use crate::dither::{self, TRANSPARENT};
+use crate::util::{Color, JoinAll};
 
 // 一行是六像素高
-async fn format_sixel_line(image: [&[Color]; 6]) -> Vec<u8> {
+async fn format_sixel_line(indices: [&[u16]; 6]) -> Vec<u8> {
     let mut out = Vec::new();
     let mut color_used = [false; 256];
-    let width = image[0].len();
-    let mut indices = Vec::with_capacity(6);
-    for line in image {
-        let mut row = Vec::with_capacity(width);
-        for &pixel in line {
-            if pixel.is_transparent() {
-                row.push(255);
-            } else {
-                let index = palette256_from_color(pixel);
+    let width = indices[0].len();
+    for row in indices {
+        for &index in row {
+            if index != TRANSPARENT {
                 color_used[index as usize] = true;
-                row.push(index);
             }
         }
-        indices.push(row);
     }
 
     let mut used_colors = Vec::new();
     for (index, used) in color_used.iter().enumerate() {
         if *used {
-            used_colors.push(index as u8);
+            used_colors.push(index as u16);
         }
     }
 
@@ -88,26 +82,27 @@ pub async fn format_sixel(
         flat.as_slice()
     };
 
+    // 自适应调色板或 xterm 调色板，已经过抖动
+    let (palette, indices) = dither::sixel_quantize(&data[..width * height], width, height);
+
     let mut color_used = [false; 256];
-    for &pixel in data {
-        if pixel.is_transparent() {
-            continue;
+    for &index in &indices {
+        if index != TRANSPARENT {
+            color_used[index as usize] = true;
         }
-        let index = palette256_from_color(pixel);
-        color_used[index as usize] = true;
     }
 
     for (index, _) in color_used.iter().enumerate().filter(|&(_, &used)| used) {
-        let color = palette256_to_color(index as u8);
+        let color = palette[index];
         let r = (color.r as u16 * 100 / 255) as u16;
         let g = (color.g as u16 * 100 / 255) as u16;
         let b = (color.b as u16 * 100 / 255) as u16;
         write!(wr, "#{index};2;{r};{g};{b}").unwrap();
     }
 
-    let pad = vec![Color::transparent(); width];
-    let pad = unsafe { std::mem::transmute::<_, &[Color]>(pad.as_slice()) };
-    let data = unsafe { std::mem::transmute::<_, &[Color]>(data) };
+    let pad = vec![TRANSPARENT; width];
+    let pad = unsafe { std::mem::transmute::<_, &[u16]>(pad.as_slice()) };
+    let indices = unsafe { std::mem::transmute::<_, &[u16]>(indices.as_slice()) };
 
     let mut tasks = Vec::new();
     let mut y = 0;
@@ -116,7 +111,7 @@ pub async fn format_sixel(
             let mut rows = [&[][..]; 6];
             for i in 0..6 {
                 if y + i < height {
-                    rows[i] = &data[(y + i) * width..(y + i + 1) * width];
+                    rows[i] = &indices[(y + i) * width..(y + i + 1) * width];
                 } else {
                     rows[i] = pad;
                 }
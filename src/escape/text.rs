@@ -0,0 +1,357 @@
+use std::io::Write;
+use std::sync::atomic::{AtomicU32, Ordering};
+#[cfg(feature = "unicode")]
+use unicode_width::UnicodeWidthChar;
+
+use crate::util::{Cell, Color, ColorMode, escape_set_color, some_if_ne};
+
+/// 颜色变化不超过此值（每个通道 0 到 255）的单元格不重新输出
+static COLOR_THRESHOLD: AtomicU32 = AtomicU32::new(2);
+/// 跳过的单元格不超过此数时，才考虑直接覆盖输出而不是移动光标
+const MAX_OVERWRITE: usize = 4;
+
+pub fn set_color_threshold(threshold: u32) {
+    COLOR_THRESHOLD.store(threshold, Ordering::SeqCst);
+}
+
+/// 编码后的一行
+/// - 行首的颜色状态取决于上一行，所以第一个颜色转义序列在拼接时写出
+pub struct EncodedLine {
+    pub buf: Vec<u8>,
+    /// 第一个颜色转义序列在 `buf` 中的位置和要设置的 (前景色, 背景色)
+    pub head: Option<(usize, Color, Color)>,
+    /// 行尾的 (前景色, 背景色)
+    pub tail: Option<(Color, Color)>,
+    /// 与逐个单元格输出相比节省的字节数
+    pub saved: usize,
+    /// 变化很小而没有重新输出的单元格数
+    pub kept: usize,
+}
+
+/// 行内编码的选项，每帧相同
+#[derive(Clone, Copy)]
+pub struct LineEncoder {
+    pub color_mode: ColorMode,
+    /// `None` 表示使用单元格的 `glyph`
+    pub default_char: Option<char>,
+    pub force_flush: bool,
+    /// 终端支持 REP（`CSI n b`）
+    pub rep: bool,
+}
+
+/// 从 `from` 切换到 `to` 的颜色转义序列
+/// - 透明色只能用 `CSI m` 恢复，它会同时重置前景色和背景色
+pub fn write_colors(buf: &mut Vec<u8>, from: (Color, Color), to: (Color, Color), mode: ColorMode) {
+    let reset = (to.0 != from.0 && to.0.is_transparent())
+        || (to.1 != from.1 && to.1.is_transparent());
+    let (fg, bg) = match reset {
+        true => (Some(to.0), Some(to.1)),
+        false => (some_if_ne(to.0, from.0), some_if_ne(to.1, from.1)),
+    };
+    escape_set_color(buf, fg, bg, mode);
+}
+
+/// 光标前移 `n` 列，下移 `n` 行时用 `E`
+pub fn write_cursor(buf: &mut Vec<u8>, n: usize, cmd: char) {
+    match n {
+        0 => {}
+        1 => write!(buf, "\x1b[{cmd}").unwrap(),
+        n => write!(buf, "\x1b[{n}{cmd}").unwrap(),
+    }
+}
+
+/// `write_cursor` 输出的字节数
+pub fn cursor_len(n: usize) -> usize {
+    match n {
+        0 => 0,
+        1 => 3,
+        n => 3 + n.ilog10() as usize + 1,
+    }
+}
+
+/// 按人眼对绿色最敏感、对蓝色最不敏感加权，灰度变化 `d` 时距离为 `d`
+fn near(a: Color, b: Color, threshold: u32) -> bool {
+    if a == b {
+        return true;
+    }
+    if threshold == 0 || a.is_transparent() || b.is_transparent() {
+        return false;
+    }
+    let d = |x: u8, y: u8| (x as i32 - y as i32).pow(2) as u32;
+    2 * d(a.r, b.r) + 4 * d(a.g, b.g) + 3 * d(a.b, b.b) <= 9 * threshold * threshold
+}
+
+struct LineState {
+    enc: LineEncoder,
+    line: EncodedLine,
+    /// 当前的颜色状态，行首为 `None`
+    colors: Option<(Color, Color)>,
+    char_buf: [u8; 4],
+}
+
+impl LineState {
+    fn char_of(&self, cell: &Cell) -> char {
+        cell.c.unwrap_or(self.enc.default_char.unwrap_or(cell.glyph))
+    }
+
+    fn set_colors(&mut self, fg: Color, bg: Color) {
+        match self.colors {
+            None => self.line.head = Some((self.line.buf.len(), fg, bg)),
+            Some(from) => write_colors(&mut self.line.buf, from, (fg, bg), self.enc.color_mode),
+        }
+        self.colors = Some((fg, bg));
+    }
+
+    /// 输出 `count` 个相同的单元格，按字节数选择逐个输出、REP 或 ECH
+    /// - ECH 用当前背景色擦除，只用于看起来是纯色的单元格，擦除后光标不动
+    /// - `at_end` 为真时这些单元格位于行尾，ECH 之后不需要移动光标
+    fn put_run(&mut self, cell: &Cell, count: usize, at_end: bool) {
+        let c = self.char_of(cell);
+        self.set_colors(cell.fg, cell.bg);
+        let bytes = c.encode_utf8(&mut self.char_buf).len();
+        let literal = bytes * count;
+        let rep = match self.enc.rep && count > 1 && !c.is_control() {
+            true => bytes + cursor_len(count - 1),
+            false => usize::MAX,
+        };
+        let blank = c == ' ' || (cell.fg == cell.bg && matches!(c, '▄' | '█'));
+        let ech = match blank && count > 1 {
+            true => cursor_len(count) + if at_end { 0 } else { cursor_len(count) },
+            false => usize::MAX,
+        };
+        let best = literal.min(rep).min(ech);
+        let buf = &mut self.line.buf;
+        if best == literal {
+            for _ in 0..count {
+                buf.extend_from_slice(&self.char_buf[..bytes]);
+            }
+        } else if best == rep {
+            buf.extend_from_slice(&self.char_buf[..bytes]);
+            write_cursor(buf, count - 1, 'b');
+        } else {
+            write_cursor(buf, count, 'X');
+            if !at_end {
+                write_cursor(buf, count, 'C');
+            }
+        }
+        self.line.saved += literal - best;
+    }
+
+    /// 跳过未变化的单元格，颜色状态已知且覆盖输出更短时直接覆盖
+    fn skip(&mut self, cells: &[Cell]) {
+        let moving = cursor_len(cells.len());
+        if let Some(colors) = self.colors
+            && cells.len() <= MAX_OVERWRITE
+        {
+            let mut overwrite = Vec::new();
+            let mut state = colors;
+            for cell in cells {
+                write_colors(&mut overwrite, state, (cell.fg, cell.bg), self.enc.color_mode);
+                state = (cell.fg, cell.bg);
+                let c = self.char_of(cell);
+                overwrite.extend_from_slice(c.encode_utf8(&mut self.char_buf).as_bytes());
+            }
+            if overwrite.len() < moving {
+                self.line.buf.extend_from_slice(&overwrite);
+                self.line.saved += moving - overwrite.len();
+                self.colors = Some(state);
+                return;
+            }
+        }
+        write_cursor(&mut self.line.buf, cells.len(), 'C');
+    }
+}
+
+impl LineEncoder {
+    /// 编码一行中变化的单元格
+    /// - 与上一帧相差不超过阈值的单元格不输出，并把 `cells` 改回上一帧的内容，
+    ///   使下一帧与终端上实际显示的内容比较，缓慢的渐变不会被一直忽略
+    pub fn encode(&self, cells: &mut [Cell], lasts: &[Cell]) -> EncodedLine {
+        let threshold = COLOR_THRESHOLD.load(Ordering::Relaxed);
+        let mut st = LineState {
+            enc: *self,
+            line: EncodedLine {
+                buf: Vec::with_capacity(1024),
+                head: None,
+                tail: None,
+                saved: 0,
+                kept: 0,
+            },
+            colors: None,
+            char_buf: [0; 4],
+        };
+
+        for cell in cells.iter_mut() {
+            cell.fg = displayed_color(cell.fg, self.color_mode);
+            cell.bg = displayed_color(cell.bg, self.color_mode);
+        }
+
+        let mut skip_start = None;
+        let mut i = 0;
+        while i < cells.len() {
+            let (cell, last) = (cells[i], lasts[i]);
+            if cell.c == Some('\0') {
+                i += 1;
+                continue;
+            }
+            #[cfg(feature = "unicode")]
+            let cw = cell.c.map_or(1, |c| c.width().unwrap_or(1).max(1));
+            #[cfg(not(feature = "unicode"))]
+            let cw = 1;
+            if !self.force_flush && cw == 1 {
+                if cell == last {
+                    skip_start.get_or_insert(i);
+                    i += 1;
+                    continue;
+                }
+                if st.char_of(&cell) == st.char_of(&last)
+                    && near(cell.fg, last.fg, threshold)
+                    && near(cell.bg, last.bg, threshold)
+                {
+                    cells[i] = last;
+                    st.line.kept += 1;
+                    skip_start.get_or_insert(i);
+                    i += 1;
+                    continue;
+                }
+            }
+            if let Some(start) = skip_start.take() {
+                st.skip(&cells[start..i]);
+            }
+
+            let mut count = 1;
+            if cw == 1 {
+                while i + count < cells.len() && cells[i + count] == cell {
+                    count += 1;
+                }
+            }
+            st.put_run(&cell, count, i + count == cells.len());
+            i += count;
+        }
+
+        st.line.tail = st.colors;
+        st.line
+    }
+}
+
+/// 颜色在终端中实际显示的颜色
+/// - 调色板模式下不同的颜色可能输出相同的转义序列，换算后比较可以少输出没有变化的单元格
+pub fn displayed_color(c: Color, mode: ColorMode) -> Color {
+    if c.is_transparent() {
+        return c;
+    }
+    match mode {
+        ColorMode::Palette256Only => {
+            let index = crate::util::palette256_from_color(c);
+            let shown = crate::util::palette256_to_color(index);
+            match crate::util::palette256_from_color(shown) == index {
+                true => shown,
+                false => c,
+            }
+        }
+        ColorMode::BlackWhite => match c.luminance() < 128 {
+            true => Color::new(0, 0, 0),
+            false => Color::new(255, 255, 255),
+        },
+        _ => c,
+    }
+}
+
+// @ ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== @
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FG: Color = Color::new(255, 255, 255);
+    const BG: Color = Color::new(0, 0, 0);
+
+    fn encoder(rep: bool) -> LineEncoder {
+        LineEncoder {
+            color_mode: ColorMode::TrueColorOnly,
+            default_char: None,
+            force_flush: false,
+            rep,
+        }
+    }
+
+    fn line(text: &str) -> Vec<Cell> {
+        text.chars().map(|c| Cell::new(c, FG, BG)).collect()
+    }
+
+    fn gray(v: u8) -> Cell {
+        Cell::new('#', Color::new(v, v, v), BG)
+    }
+
+    #[test]
+    fn literal_or_rep() {
+        let lasts = line(&"b".repeat(10));
+        let encoded = encoder(false).encode(&mut line(&"a".repeat(10)), &lasts);
+        assert_eq!(encoded.buf, b"aaaaaaaaaa");
+        assert_eq!(encoded.saved, 0);
+        assert_eq!(encoded.head, Some((0, FG, BG)));
+        assert_eq!(encoded.tail, Some((FG, BG)));
+
+        let encoded = encoder(true).encode(&mut line(&"a".repeat(10)), &lasts);
+        assert_eq!(encoded.buf, b"a\x1b[9b");
+        assert_eq!(encoded.saved, 5);
+
+        // 很短的重复按原样输出更短
+        let encoded = encoder(true).encode(&mut line("aab"), &line("ccc"));
+        assert_eq!(encoded.buf, b"aab");
+    }
+
+    #[test]
+    fn erase_blank_runs() {
+        // 行中间擦除后要移动光标，REP 更短时用 REP
+        let text = format!("{}x", " ".repeat(20));
+        let lasts = line(&"y".repeat(21));
+        let encoded = encoder(false).encode(&mut line(&text), &lasts);
+        assert_eq!(encoded.buf, b"\x1b[20X\x1b[20Cx");
+        assert_eq!(encoded.saved, 10);
+        let encoded = encoder(true).encode(&mut line(&text), &lasts);
+        assert_eq!(encoded.buf, b" \x1b[19bx");
+
+        // 行尾擦除后不需要移动光标
+        let lasts = line(&"y".repeat(20));
+        let encoded = encoder(true).encode(&mut line(&" ".repeat(20)), &lasts);
+        assert_eq!(encoded.buf, b"\x1b[20X");
+        assert_eq!(encoded.saved, 15);
+    }
+
+    #[test]
+    fn overwrite_or_move() {
+        // 跳过一个单元格时直接覆盖比移动光标短
+        let encoded = encoder(false).encode(&mut line("azb"), &line("xzy"));
+        assert_eq!(encoded.buf, b"azb");
+        assert_eq!(encoded.saved, 2);
+
+        // 跳过的单元格太多时移动光标
+        let encoded = encoder(false).encode(&mut line("azzzzzb"), &line("xzzzzzy"));
+        assert_eq!(encoded.buf, b"a\x1b[5Cb");
+
+        // 行首的颜色状态未知，不能覆盖
+        let encoded = encoder(false).encode(&mut line("zb"), &line("zy"));
+        assert_eq!(encoded.buf, b"\x1b[Cb");
+        assert_eq!(encoded.head, Some((3, FG, BG)));
+    }
+
+    #[test]
+    fn kept_cells_roll_back() {
+        let lasts = vec![gray(100), gray(100)];
+        let mut cells = vec![gray(102), gray(200)];
+        let encoded = encoder(false).encode(&mut cells, &lasts);
+        assert_eq!(encoded.kept, 1);
+        assert_eq!(encoded.buf, b"\x1b[C#");
+        assert!(cells[0] == lasts[0]);
+        assert!(cells[1] == gray(200));
+
+        // 下一帧与终端上实际显示的颜色比较，缓慢的渐变累积起来仍会输出
+        let lasts = cells;
+        let mut cells = vec![gray(103), gray(200)];
+        let encoded = encoder(false).encode(&mut cells, &lasts);
+        assert_eq!(encoded.kept, 0);
+        assert_eq!(encoded.buf, b"#");
+        assert!(cells[0] == gray(103));
+    }
+}
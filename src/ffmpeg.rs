@@ -8,17 +8,25 @@ use av::util::frame::{Audio as AudioFrame, video::Video as VideoFrame};
 use av::{Packet, Subtitle};
 use parking_lot::{Condvar, Mutex};
 use std::collections::VecDeque;
-use std::sync::atomic::Ordering;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::Duration;
 
 use crate::avsync::{self, end_decode};
+use crate::chapters;
+use crate::playlist::PLAYLIST;
 use crate::term::TERM_QUIT;
 
+#[cfg(feature = "audio")]
+use crate::afilter;
 #[cfg(feature = "audio")]
 use crate::audio::{self, AUDIO_FRAME, AUDIO_FRAME_SIG, audio_main};
+#[cfg(feature = "audio")]
+use crate::music;
 #[cfg(feature = "subtitle")]
 use crate::subtitle;
 #[cfg(feature = "video")]
+use crate::vdecoder;
+#[cfg(feature = "video")]
 use crate::video::{self, VIDEO_FRAME, VIDEO_FRAME_SIG, VIDEO_FRAMETIME, video_main};
 
 #[allow(static_mut_refs)]
@@ -102,17 +110,58 @@ pub fn seek_request_absolute(sec: f64) {
     DECODER_WAKEUP.notify_one();
 }
 
+// @ ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== @
+
+/// 距离结尾还剩多少时开始预读下一项
+const PRELOAD_AHEAD: Duration = Duration::from_secs(15);
+
+/// 预先打开并探测好的下一项
+static PRELOADED: Mutex<Option<(String, Input)>> = Mutex::new(None);
+
+/// 当前文件是否正常解码到了结尾（而不是被中断）
+static REACHED_EOF: AtomicBool = AtomicBool::new(false);
+
+pub fn reached_eof() -> bool {
+    REACHED_EOF.load(Ordering::SeqCst)
+}
+
+/// 在后台打开并探测下一项，切换时可以直接使用
+fn preload(path: String) {
+    std::thread::spawn(move || {
+        if let Ok(ictx) = av::format::input(&path) {
+            PRELOADED.lock().replace((path, ictx));
+        }
+    });
+}
+
+/// 打开输入文件，优先使用预读好的结果
+fn open_input(path: &str) -> Result<Input, av::Error> {
+    match PRELOADED.lock().take() {
+        Some((preloaded, ictx)) if preloaded == path => Ok(ictx),
+        _ => av::format::input(path),
+    }
+}
+
+// @ ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== @
+
 #[allow(unused_variables, unused_mut, unused_assignments)]
 pub fn decode_main(path: &str) -> Result<bool> {
-    let Ok(mut ictx) = av::format::input(path) else {
+    REACHED_EOF.store(false, Ordering::SeqCst);
+    let Ok(mut ictx) = open_input(path) else {
         error_f16n!("Failed to open input file: {}", path);
         return Ok(false);
     };
 
+    // 嵌入的封面也是视频流，不能当作视频播放
     #[cfg(feature = "video")]
     let video_stream_index = ictx
         .streams()
         .best(av::media::Type::Video)
+        .filter(|s| !is_attached_pic(s))
+        .or_else(|| {
+            ictx.streams()
+                .find(|s| s.parameters().medium() == av::media::Type::Video && !is_attached_pic(s))
+        })
         .map_or(-1, |s| s.index() as isize);
     #[cfg(feature = "audio")]
     let audio_stream_index = ictx
@@ -143,10 +192,12 @@ pub fn decode_main(path: &str) -> Result<bool> {
         };
         let codec_ctx =
             AVCCtx::from_parameters(stream.parameters()).context(l10n!("video decoder"))?;
-        let codec = codec_ctx
-            .decoder()
-            .video()
-            .context(l10n!("video decoder"))?;
+        let mut decoder = codec_ctx.decoder();
+        #[cfg(feature = "video")]
+        vdecoder::configure(&mut decoder);
+        #[cfg(feature = "video")]
+        crate::vfilter::load_stream_rotation(&stream);
+        let codec = decoder.video().context(l10n!("video decoder"))?;
         (
             Some(codec),
             Some(stream.time_base()),
@@ -167,6 +218,12 @@ pub fn decode_main(path: &str) -> Result<bool> {
             .decoder()
             .audio()
             .context(l10n!("audio decoder"))?;
+        #[cfg(feature = "audio")]
+        afilter::set_replaygain(afilter::ReplayGain::from_tags(|key| {
+            let tags = stream.metadata();
+            let tags = tags.get(key).map(str::to_string);
+            tags.or_else(|| ictx.metadata().get(key).map(str::to_string))
+        }));
         (
             Some(codec),
             Some(stream.time_base()),
@@ -208,6 +265,10 @@ pub fn decode_main(path: &str) -> Result<bool> {
         AUDIO_TIME_BASE.lock().replace(audio_timebase);
     }
 
+    #[cfg(feature = "audio")]
+    music::load(&ictx, path, audio_stream_index);
+    chapters::load(&ictx);
+
     if video_decoder.is_none() && audio_decoder.is_none() {
         error!("No audio or video stream found");
         error_l10n!("What the fuck is this file?");
@@ -229,6 +290,8 @@ pub fn decode_main(path: &str) -> Result<bool> {
     };
 
     avsync::reset(duration, audio_decoder.is_some(), video_decoder.is_some());
+    #[cfg(feature = "audio")]
+    audio::begin_item();
 
     #[cfg(feature = "video")]
     let video_main = if video_stream_index >= 0 {
@@ -251,7 +314,24 @@ pub fn decode_main(path: &str) -> Result<bool> {
 
     avsync::hint_seeked(Duration::ZERO);
 
+    let mut preloaded = false;
+    #[cfg(feature = "video")]
+    let mut degrader = vdecoder::Degrader::new();
+    #[cfg(feature = "video")]
+    let mut view_generation = crate::view::generation();
+
     while !(TERM_QUIT.load(Ordering::SeqCst) || avsync::decode_ended()) {
+        // 快到结尾时预读下一项，减少切换时的停顿
+        if !preloaded
+            && duration > Duration::ZERO
+            && avsync::played_time_or_zero() + PRELOAD_AHEAD >= duration
+        {
+            preloaded = true;
+            if let Some(next) = PLAYLIST.lock().peek_next().cloned() {
+                preload(next);
+            }
+        }
+
         if let Some((abs, off)) = SEEK_REQUEST.lock().take() {
             if do_seek(&mut ictx, abs, off, &mut video_queue, &mut audio_queue) {
                 video_last_pts = None;
@@ -317,6 +397,16 @@ pub fn decode_main(path: &str) -> Result<bool> {
 
             #[cfg(feature = "video")]
             decode_video(&mut video_decoder, &mut video_queue, &mut video_last_pts);
+            #[cfg(feature = "video")]
+            degrader.update(&mut video_decoder);
+            #[cfg(feature = "video")]
+            if view_generation != crate::view::generation() {
+                view_generation = crate::view::generation();
+                if vdecoder::needs_reopen() {
+                    reopen_video_decoder(&ictx, video_stream_index, &mut video_decoder);
+                    degrader.decoder_reopened();
+                }
+            }
             #[cfg(feature = "audio")]
             decode_audio(&mut audio_decoder, &mut audio_queue, &mut audio_last_pts);
 
@@ -327,6 +417,12 @@ pub fn decode_main(path: &str) -> Result<bool> {
             *lock = false;
 
             if no_packet && audio_queue.is_empty() && video_queue.is_empty() {
+                #[cfg(feature = "audio")]
+                flush_audio(&mut audio_decoder, &mut audio_last_pts);
+                if SEEK_REQUEST.lock().is_some() {
+                    break;
+                }
+                REACHED_EOF.store(!TERM_QUIT.load(Ordering::SeqCst), Ordering::SeqCst);
                 end_decode();
                 break;
             }
@@ -352,15 +448,27 @@ pub fn decode_main(path: &str) -> Result<bool> {
     // 清除还没处理的音频和视频帧
     #[cfg(feature = "video")]
     let _ = VIDEO_FRAME.lock().take();
+    #[cfg(feature = "video")]
+    crate::tiles::hint_frame_time(None);
     #[cfg(feature = "audio")]
     let _ = AUDIO_FRAME.lock().take();
     // 清除字幕
     #[cfg(feature = "subtitle")]
     subtitle::clear();
+    #[cfg(feature = "audio")]
+    music::clear();
+    chapters::clear();
 
     Ok(true)
 }
 
+/// 流是否为附加图片（如 MP3 / FLAC 中嵌入的封面）
+pub fn is_attached_pic(stream: &av::format::stream::Stream) -> bool {
+    stream
+        .disposition()
+        .contains(av::format::stream::Disposition::ATTACHED_PIC)
+}
+
 fn do_seek(
     ictx: &mut Input,
     abs: bool,
@@ -396,6 +504,25 @@ fn do_seek(
     ret >= 0
 }
 
+/// 放大后用新的 lowres 重新打开视频解码器，并从当前位置重新解码
+#[cfg(feature = "video")]
+fn reopen_video_decoder(ictx: &Input, index: isize, decoder: &mut Option<VideoDecoder>) {
+    let Some(stream) = usize::try_from(index).ok().and_then(|i| ictx.stream(i)) else {
+        return;
+    };
+    let Ok(codec_ctx) = AVCCtx::from_parameters(stream.parameters()) else {
+        return;
+    };
+    let mut new_decoder = codec_ctx.decoder();
+    vdecoder::configure(&mut new_decoder);
+    let Ok(new_decoder) = new_decoder.video() else {
+        return;
+    };
+    *decoder = Some(new_decoder);
+    // 新的解码器要从关键帧开始
+    seek_request_absolute(avsync::played_time_or_zero().as_secs_f64());
+}
+
 #[cfg(feature = "video")]
 fn decode_video(
     video_decoder: &mut Option<VideoDecoder>,
@@ -502,6 +629,41 @@ fn decode_audio(
     }
 }
 
+/// 文件结束时取出音频解码器里剩下的帧
+/// - 中途有跳转或退出时放弃剩下的帧，但无论如何都要重置解码器，否则它会一直停在 EOF 状态
+#[cfg(feature = "audio")]
+fn flush_audio(audio_decoder: &mut Option<AudioDecoder>, audio_pts: &mut Option<i64>) {
+    let Some(audio_decoder) = audio_decoder.as_mut() else {
+        return;
+    };
+
+    let mut frame = AudioFrame::empty();
+    if audio_decoder.send_eof().is_ok() {
+        'drain: while audio_decoder.receive_frame(&mut frame).is_ok() {
+            if frame.pts().is_none() {
+                frame.set_pts(*audio_pts);
+            }
+            let mut lock = AUDIO_FRAME.lock();
+            while lock.is_some() {
+                if TERM_QUIT.load(Ordering::SeqCst) || SEEK_REQUEST.lock().is_some() {
+                    break 'drain;
+                }
+                drop(lock);
+                let mut wakeup = DECODER_WAKEUP_MUTEX.lock();
+                if *wakeup == false {
+                    DECODER_WAKEUP.wait_for(&mut wakeup, Duration::from_millis(50));
+                }
+                *wakeup = false;
+                drop(wakeup);
+                lock = AUDIO_FRAME.lock();
+            }
+            lock.replace(std::mem::replace(&mut frame, AudioFrame::empty()));
+            AUDIO_FRAME_SIG.notify_one();
+        }
+    }
+    audio_decoder.flush();
+}
+
 #[cfg(feature = "subtitle")]
 fn decode_subtitle(subtitle_decoder: &mut Option<SubtitleDecoder>, packet: Packet) {
     let Some(subtitle_decoder) = subtitle_decoder.as_mut() else {
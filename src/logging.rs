@@ -93,6 +93,10 @@ static MESSAGES: Mutex<Messages> = Mutex::new(Messages {
     timeout: Duration::from_secs(5),
 });
 
+pub fn set_message_timeout(timeout: Duration) {
+    MESSAGES.lock().timeout = timeout;
+}
+
 pub fn remove_expired_messages() {
     let now = SystemTime::now();
     let mut lock = MESSAGES.lock();
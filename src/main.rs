@@ -49,12 +49,17 @@ mod ui;
 #[deny(unused_must_use)]
 mod avsync;
 
+mod ascii;
+mod chapters;
+mod dither;
 mod playlist;
+mod ratectl;
 mod render;
 mod statistics;
 mod stdin;
 mod stdout;
 mod term;
+mod termcap;
 
 #[cfg(feature = "command")]
 mod command;
@@ -73,11 +78,27 @@ mod config;
 #[cfg(feature = "ffmpeg")]
 mod ffmpeg;
 
+#[cfg(feature = "audio")]
+mod afilter;
 #[cfg(feature = "audio")]
 mod audio;
+#[cfg(feature = "audio")]
+mod music;
+#[cfg(feature = "audio")]
+mod visualizer;
 
+#[cfg(feature = "video")]
+mod compare;
+#[cfg(feature = "video")]
+mod tiles;
+#[cfg(feature = "video")]
+mod vdecoder;
+#[cfg(feature = "video")]
+mod vfilter;
 #[cfg(feature = "video")]
 mod video;
+#[cfg(feature = "video")]
+mod view;
 
 #[cfg(feature = "subtitle")]
 mod subtitle;
@@ -90,6 +111,9 @@ mod escape {
     usemod!(osc8);
     #[cfg(feature = "osc1337")]
     usemod!(osc1337);
+    #[cfg(feature = "kitty")]
+    usemod!(kitty);
+    usemod!(text);
 }
 
 pub static TOKIO_RUNTIME: LazyLock<Runtime> = LazyLock::new(|| {
@@ -179,22 +203,202 @@ struct CliArgs {
     inputs: Vec<String>,
 
     /// Small seek step in seconds
-    #[arg(long = "seek-small", default_value_t = 5.0)]
-    seek_small: f64,
+    #[arg(long = "seek-small")]
+    seek_small: Option<f64>,
 
     /// Large seek step in seconds
-    #[arg(long = "seek-large", default_value_t = 30.0)]
-    seek_large: f64,
+    #[arg(long = "seek-large")]
+    seek_large: Option<f64>,
+
+    /// Volume, 0-200
+    #[arg(long = "volume")]
+    volume: Option<u32>,
+
+    /// Color mode (truecolor, palette256, ascii, braille, octant, ...)
+    #[arg(long = "color-mode")]
+    color_mode: Option<String>,
+
+    /// Chroma mode (none, red, green, blue, ...)
+    #[arg(long = "chroma-mode")]
+    chroma_mode: Option<String>,
 
+    /// UI language (en-us, zh-cn, ja-jp, ...)
+    #[arg(long = "lang")]
+    lang: Option<String>,
+
+    /// Audio output device name
+    #[arg(long = "audio-device")]
+    audio_device: Option<String>,
+
+    /// Config profile to use ([profile.<name>] in tvid.toml)
+    #[arg(long = "profile")]
+    profile: Option<String>,
+
+    /// Loop the playlist
     #[arg(short = 'l', long = "loop")]
     loop_playlist: bool,
 
     #[arg(short = 'p', long = "playlist")]
     playlist: Option<String>,
+
+    /// Play another input in a tile next to the main video (repeatable)
+    #[arg(long = "tile", value_name = "INPUT")]
+    tiles: Vec<String>,
+
+    /// Compare the main video with another encode of it
+    #[arg(long = "compare", value_name = "INPUT")]
+    compare: Option<String>,
+}
+
+/// 所有支持的界面语言
+pub const LANGS: &[&str] = &[
+    "en-us", "zh-cn", "zh-tw", "ja-jp", "fr-fr", "de-de", "es-es", "ko-kr", "pt-br", "ru-ru",
+    "it-it", "tr-tr", "vi-vn",
+];
+
+/// 切换界面语言，返回实际选中的语言代码
+/// - 支持 `zh` / `ja` 这样的简写
+/// - 不支持的语言返回 `None`，此时语言不会改变
+#[cfg(feature = "i18n")]
+pub fn set_lang(lang: &str) -> Option<&'static str> {
+    let lang = lang.trim().to_lowercase().replace('_', "-");
+    let selected = match lang.as_str() {
+        "en-us" | "en" => {
+            static_l10n::lang!("en-us");
+            "en-us"
+        }
+        "zh-cn" | "zh" => {
+            static_l10n::lang!("zh-cn");
+            "zh-cn"
+        }
+        "zh-tw" => {
+            static_l10n::lang!("zh-tw");
+            "zh-tw"
+        }
+        "ja-jp" | "ja" => {
+            static_l10n::lang!("ja-jp");
+            "ja-jp"
+        }
+        "fr-fr" | "fr" => {
+            static_l10n::lang!("fr-fr");
+            "fr-fr"
+        }
+        "de-de" | "de" => {
+            static_l10n::lang!("de-de");
+            "de-de"
+        }
+        "es-es" | "es" => {
+            static_l10n::lang!("es-es");
+            "es-es"
+        }
+        "ko-kr" | "ko" => {
+            static_l10n::lang!("ko-kr");
+            "ko-kr"
+        }
+        "pt-br" | "pt" => {
+            static_l10n::lang!("pt-br");
+            "pt-br"
+        }
+        "ru-ru" | "ru" => {
+            static_l10n::lang!("ru-ru");
+            "ru-ru"
+        }
+        "it-it" | "it" => {
+            static_l10n::lang!("it-it");
+            "it-it"
+        }
+        "tr-tr" | "tr" => {
+            static_l10n::lang!("tr-tr");
+            "tr-tr"
+        }
+        "vi-vn" | "vi" => {
+            static_l10n::lang!("vi-vn");
+            "vi-vn"
+        }
+        _ => return None,
+    };
+    Some(selected)
 }
 
 static SEEK_SMALL_STEP: Mutex<f64> = Mutex::new(5.0);
 static SEEK_LARGE_STEP: Mutex<f64> = Mutex::new(30.0);
+/// `[`/`]` 调整音频延迟的步长（秒）
+#[cfg(feature = "audio")]
+const AUDIO_DELAY_STEP: f64 = 0.01;
+
+/// 命令行参数中覆盖配置的项
+fn cli_overrides(cli: &CliArgs) -> Vec<(&'static str, String)> {
+    let mut overrides = Vec::new();
+    if let Some(step) = cli.seek_small {
+        overrides.push(("seek_small", step.to_string()));
+    }
+    if let Some(step) = cli.seek_large {
+        overrides.push(("seek_large", step.to_string()));
+    }
+    if let Some(volume) = cli.volume {
+        overrides.push(("volume", volume.min(200).to_string()));
+    }
+    if let Some(device) = &cli.audio_device {
+        overrides.push(("audio_device", device.clone()));
+    }
+    if let Some(lang) = &cli.lang {
+        overrides.push(("lang", lang.clone()));
+    }
+    if let Some(name) = &cli.color_mode {
+        overrides.push(("color_mode", name.clone()));
+    }
+    if let Some(name) = &cli.chroma_mode {
+        overrides.push(("chroma_mode", name.clone()));
+    }
+    if cli.loop_playlist {
+        overrides.push(("looping", "true".to_string()));
+    }
+    overrides
+}
+
+/// 命令行参数覆盖配置文件（仅影响本次运行，不写回配置）
+#[cfg(feature = "config")]
+fn apply_cli_overrides(cli: &CliArgs) {
+    for (key, value) in cli_overrides(cli) {
+        if let Err(err) = config::set_override(key, &value) {
+            warning!("{}", err);
+        }
+    }
+    config::apply();
+}
+
+/// 命令行参数覆盖默认设置
+#[cfg(not(feature = "config"))]
+fn apply_cli_overrides(cli: &CliArgs) {
+    for (key, value) in cli_overrides(cli) {
+        match key {
+            "seek_small" => *SEEK_SMALL_STEP.lock() = value.parse().unwrap_or(5.0),
+            "seek_large" => *SEEK_LARGE_STEP.lock() = value.parse().unwrap_or(30.0),
+            #[cfg(feature = "audio")]
+            "volume" => audio::set_volume(value.parse::<f32>().unwrap_or(100.0) / 100.0),
+            #[cfg(feature = "audio")]
+            "audio_device" => audio::set_output_device(Some(value)),
+            #[cfg(feature = "i18n")]
+            "lang" => {
+                if set_lang(&value).is_none() {
+                    warning_f16n!("Unsupported language: {}", value);
+                }
+            }
+            "color_mode" => match util::ColorMode::from_name(&value) {
+                Some(color_mode) => render::RENDER_CONTEXT.lock().set_color_mode(color_mode),
+                None => warning_f16n!("Unknown color mode: {}", value),
+            },
+            "chroma_mode" => match util::ChromaMode::from_name(&value) {
+                Some(chroma_mode) => render::RENDER_CONTEXT.lock().chroma_mode = chroma_mode,
+                None => warning_f16n!("Unknown chroma mode: {}", value),
+            },
+            "looping" => {
+                PLAYLIST.lock().set_looping(true);
+            }
+            _ => {}
+        }
+    }
+}
 
 fn register_input_callbacks() {
     stdin::register_keypress_callback(Key::Escape, |_, _| {
@@ -219,6 +423,10 @@ fn register_input_callbacks() {
         playlist::toggle_show_playlist();
         true
     });
+    stdin::register_keypress_callback(Key::Normal('k'), |_, _| {
+        chapters::toggle_show_chapters();
+        true
+    });
     stdin::register_keypress_callback(Key::Normal('m'), |_, _| true);
     stdin::register_keypress_callback(Key::Normal('f'), |_, _| {
         ui::FILE_SELECT.fetch_xor(true, Ordering::SeqCst);
@@ -226,25 +434,34 @@ fn register_input_callbacks() {
     });
     #[cfg(feature = "audio")]
     stdin::register_keypress_callback(Key::Normal('w'), |_, _| {
-        render::toggle_show_audio_visualizer();
+        render::cycle_audio_visualizer();
+        true
+    });
+
+    #[cfg(feature = "audio")]
+    stdin::register_keypress_callback(Key::Normal(']'), |_, _| {
+        audio::adjust_audio_delay(AUDIO_DELAY_STEP);
+        true
+    });
+    #[cfg(feature = "audio")]
+    stdin::register_keypress_callback(Key::Normal('['), |_, _| {
+        audio::adjust_audio_delay(-AUDIO_DELAY_STEP);
         true
     });
 
     stdin::register_keypress_callback(Key::Lower('c'), |_, _| {
         let mut ctx = render::RENDER_CONTEXT.lock();
         ctx.color_mode.switch_to_next();
-        let (fppc_x, fppc_y) = ctx.color_mode.fppc();
-        ctx.update_fppc(fppc_x, fppc_y);
-        ctx.force_flush_next();
+        let color_mode = ctx.color_mode;
+        ctx.set_color_mode(color_mode);
         true
     });
 
     stdin::register_keypress_callback(Key::Upper('c'), |_, _| {
         let mut ctx = render::RENDER_CONTEXT.lock();
         ctx.color_mode.switch_to_prev();
-        let (fppc_x, fppc_y) = ctx.color_mode.fppc();
-        ctx.update_fppc(fppc_x, fppc_y);
-        ctx.force_flush_next();
+        let color_mode = ctx.color_mode;
+        ctx.set_color_mode(color_mode);
         true
     });
 
@@ -264,37 +481,56 @@ fn register_input_callbacks() {
         seek_request_relative(*SEEK_SMALL_STEP.lock());
         true
     });
+    stdin::register_keypress_callback(Key::PageUp, |_, _| {
+        chapters::prev();
+        true
+    });
+    stdin::register_keypress_callback(Key::PageDown, |_, _| {
+        chapters::next();
+        true
+    });
+
+    #[cfg(feature = "video")]
+    view::register_input_callbacks();
+    #[cfg(feature = "video")]
+    stdin::register_keypress_callback(Key::Normal('g'), |_, _| {
+        tiles::cycle_layout();
+        true
+    });
+    #[cfg(feature = "video")]
+    compare::register_input_callbacks();
 
     playlist::register_keypress_callbacks();
+    chapters::register_keypress_callbacks();
     ui::register_input_callbacks();
     #[cfg(feature = "command")]
     command::register_input_callbacks();
 }
 
+/// 下一个要播放的输入
+/// - 分屏切换音频来源时，从交换时的时间开始播放交换来的输入，播放列表不前进
+fn next_input() -> Option<String> {
+    #[cfg(feature = "video")]
+    if let Some((path, time)) = tiles::take_swap() {
+        ffmpeg::seek_request_absolute(time.as_secs_f64());
+        return Some(path);
+    }
+    PLAYLIST.lock().next().cloned()
+}
+
 static APP_START_TIME: OnceLock<Instant> = OnceLock::new();
 
 fn main() -> Result<()> {
     APP_START_TIME.set(Instant::now()).unwrap();
 
     #[cfg(feature = "i18n")]
-    match sys_locale::get_locale()
-        .map(|l| l.to_lowercase().replace('_', "-"))
-        .unwrap_or("en-us".to_string())
-        .as_str()
     {
-        "zh-cn" => static_l10n::lang!("zh-cn"),
-        "zh-tw" => static_l10n::lang!("zh-tw"),
-        "ja-jp" => static_l10n::lang!("ja-jp"),
-        "fr-fr" => static_l10n::lang!("fr-fr"),
-        "de-de" => static_l10n::lang!("de-de"),
-        "es-es" => static_l10n::lang!("es-es"),
-        "ko-kr" => static_l10n::lang!("ko-kr"),
-        "pt-br" => static_l10n::lang!("pt-br"),
-        "ru-ru" => static_l10n::lang!("ru-ru"),
-        "it-it" => static_l10n::lang!("it-it"),
-        "tr-tr" => static_l10n::lang!("tr-tr"),
-        "vi-vn" => static_l10n::lang!("vi-vn"),
-        _ => static_l10n::lang!("en-us"),
+        let locale = sys_locale::get_locale()
+            .map(|l| l.to_lowercase().replace('_', "-"))
+            .unwrap_or("en-us".to_string());
+        if set_lang(&locale).is_none() {
+            set_lang("en-us");
+        }
     }
 
     let program_name = env::args().nth(0).unwrap_or_else(|| {
@@ -303,15 +539,18 @@ fn main() -> Result<()> {
     });
 
     let cli = CliArgs::parse();
-    *SEEK_SMALL_STEP.lock() = cli.seek_small;
-    *SEEK_LARGE_STEP.lock() = cli.seek_large;
 
     #[cfg(feature = "config")]
     {
         config::create_if_not_exists(None)?;
         config::load(None)?;
+        if let Err(err) = config::set_profile(cli.profile.as_deref()) {
+            warning!("{}", err);
+        }
     }
 
+    apply_cli_overrides(&cli);
+
     if cli.show_help {
         print_help(&program_name);
         std::process::exit(0);
@@ -334,16 +573,46 @@ fn main() -> Result<()> {
 
     term::init();
 
+    // 探测完成后，没有指定颜色模式时使用探测结果（只在启动时做一次，之后保留用户的选择）
+    termcap::probe();
+    {
+        #[cfg(feature = "config")]
+        let configured = config::color_mode_configured();
+        #[cfg(not(feature = "config"))]
+        let configured = cli.color_mode.is_some();
+        // 指定的模式也需要重新检查，终端复用器内可能无法显示图像
+        let mut ctx = render::RENDER_CONTEXT.lock();
+        let color_mode = if configured {
+            ctx.color_mode
+        } else {
+            termcap::color_mode()
+        };
+        ctx.set_color_mode(color_mode);
+    }
+
     #[cfg(feature = "ssh")]
     ssh::run()?;
 
     ffmpeg::init();
 
+    #[cfg(feature = "video")]
+    for path in &cli.tiles {
+        tiles::add(path);
+    }
+    #[cfg(feature = "video")]
+    if let Some(path) = &cli.compare {
+        compare::open(path);
+    }
+
     register_input_callbacks();
 
     render::add_render_callback(render::render_video);
+    #[cfg(feature = "video")]
+    render::add_render_callback(compare::render_compare);
     #[cfg(feature = "subtitle")]
     render::add_render_callback(subtitle::render_subtitle);
+    #[cfg(feature = "audio")]
+    render::add_render_callback(music::render_music);
     render::add_render_callback(ui::render_ui);
 
     #[cfg(feature = "command")]
@@ -352,9 +621,15 @@ fn main() -> Result<()> {
     let input_main = TOKIO_RUNTIME.spawn(stdin::input_main());
     let output_main = TOKIO_RUNTIME.spawn(stdout::output_main());
     let render_main = std::thread::spawn(render::render_main);
+    #[cfg(feature = "config")]
+    let config_watch = std::thread::spawn(config::watch_main);
 
     let mut continuous_failure_count = 0;
-    while let Some(path) = { PLAYLIST.lock().next().cloned() } {
+    while let Some(path) = next_input() {
+        #[cfg(feature = "config")]
+        config::apply_for(&path);
+        #[cfg(feature = "video")]
+        tiles::set_main_input(&path);
         let success = ffmpeg::decode_main(&path).unwrap_or_else(|err| {
             error_f16n!("ffmpeg decode error: {}", err);
             false
@@ -378,6 +653,10 @@ fn main() -> Result<()> {
     render_main.join().unwrap_or_else(|err| {
         error_f16n!("render thread join error: {:?}", err);
     });
+    #[cfg(feature = "config")]
+    config_watch.join().unwrap_or_else(|err| {
+        error_f16n!("config watch thread join error: {:?}", err);
+    });
     TOKIO_RUNTIME.block_on(async {
         output_main.await.unwrap_or_else(|err| {
             error_f16n!("output task join error: {:?}", err);
@@ -0,0 +1,465 @@
+use av::Packet;
+use av::codec::context::Context as AVCCtx;
+use av::format::context::Input;
+use av::format::stream::Stream;
+use av::software::scaling::{context::Context as Scaler, flag::Flags};
+use av::util::frame::video::Video as VideoFrame;
+use parking_lot::Mutex;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::ffmpeg::is_attached_pic;
+use crate::render::{ContextWrapper, RENDER_CONTEXT};
+use crate::util::Color;
+
+// @ ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== @
+
+/// 解码后封面的最大边长（像素）
+const COVER_MAX_SIZE: u32 = 512;
+
+/// 专辑封面（RGBA）
+struct Cover {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>,
+}
+
+/// 当前播放文件的音乐信息
+struct MusicInfo {
+    title: Option<String>,
+    artist: Option<String>,
+    album: Option<String>,
+    cover: Option<Cover>,
+    /// 按时间排序的同步歌词
+    lyrics: Vec<(Duration, String)>,
+    /// 缩放到当前布局的封面缓存：(宽, 高, 像素)
+    scaled: Option<(usize, usize, Vec<Color>)>,
+}
+
+impl MusicInfo {
+    const fn new() -> Self {
+        Self {
+            title: None,
+            artist: None,
+            album: None,
+            cover: None,
+            lyrics: Vec::new(),
+            scaled: None,
+        }
+    }
+}
+
+static MUSIC: Mutex<MusicInfo> = Mutex::new(MusicInfo::new());
+
+/// 从容器中读取封面、标签和歌词
+/// - `audio_stream_index`: 音频流索引，部分格式（如 Ogg）把标签放在流上
+pub fn load(ictx: &Input, path: &str, audio_stream_index: isize) {
+    let audio_stream = usize::try_from(audio_stream_index)
+        .ok()
+        .and_then(|i| ictx.stream(i));
+    let tag = |key: &str| {
+        let tags = ictx.metadata();
+        let value = tags.get(key).map(str::to_string);
+        let value = value.or_else(|| {
+            let tags = audio_stream.as_ref()?.metadata();
+            tags.get(key).map(str::to_string)
+        });
+        value.filter(|v| !v.trim().is_empty())
+    };
+
+    let mut info = MusicInfo::new();
+    info.title = tag("title");
+    info.artist = tag("artist").or_else(|| tag("album_artist"));
+    info.album = tag("album");
+    info.cover = ictx
+        .streams()
+        .find(is_attached_pic)
+        .and_then(|stream| decode_cover(&stream));
+
+    // 优先使用同名的 .lrc 文件，其次是内嵌的歌词标签（ID3 中为 lyrics-<语言>）
+    let sidecar = std::fs::read(Path::new(path).with_extension("lrc"))
+        .ok()
+        .map(|bytes| String::from_utf8_lossy(&bytes).into_owned());
+    let embedded = || {
+        let tags = ictx.metadata();
+        let found = tags
+            .iter()
+            .find(|(k, _)| k.to_lowercase().starts_with("lyrics"))
+            .map(|(_, v)| v.to_string());
+        found.or_else(|| {
+            let tags = audio_stream.as_ref()?.metadata();
+            tags.iter()
+                .find(|(k, _)| k.to_lowercase().starts_with("lyrics"))
+                .map(|(_, v)| v.to_string())
+        })
+    };
+    info.lyrics = sidecar
+        .map(|text| parse_lrc(&text))
+        .filter(|lyrics| !lyrics.is_empty())
+        .or_else(|| embedded().map(|text| parse_lrc(&text)))
+        .unwrap_or_default();
+
+    *MUSIC.lock() = info;
+}
+
+/// 清除当前文件的音乐信息
+pub fn clear() {
+    *MUSIC.lock() = MusicInfo::new();
+}
+
+/// 是否有可以显示的音乐信息
+pub fn available() -> bool {
+    let info = MUSIC.lock();
+    info.cover.is_some() || info.title.is_some() || !info.lyrics.is_empty()
+}
+
+/// 解码附加图片流中的封面，并转换成 RGBA
+fn decode_cover(stream: &Stream) -> Option<Cover> {
+    let codec_ctx = AVCCtx::from_parameters(stream.parameters()).ok()?;
+    let mut decoder = codec_ctx.decoder().video().ok()?;
+    let packet = unsafe {
+        let pic = &(*stream.as_ptr()).attached_pic;
+        if pic.data.is_null() || pic.size <= 0 {
+            return None;
+        }
+        Packet::copy(std::slice::from_raw_parts(pic.data, pic.size as usize))
+    };
+    decoder.send_packet(&packet).ok()?;
+    decoder.send_eof().ok()?;
+    let mut frame = VideoFrame::empty();
+    decoder.receive_frame(&mut frame).ok()?;
+    if frame.width() == 0 || frame.height() == 0 {
+        return None;
+    }
+
+    let k = (COVER_MAX_SIZE as f32 / frame.width().max(frame.height()) as f32).min(1.0);
+    let width = ((frame.width() as f32 * k) as u32).max(1);
+    let height = ((frame.height() as f32 * k) as u32).max(1);
+    let mut scaler = Scaler::get(
+        frame.format(),
+        frame.width(),
+        frame.height(),
+        av::format::Pixel::RGBA,
+        width,
+        height,
+        Flags::AREA,
+    )
+    .ok()?;
+    let mut rgba = VideoFrame::empty();
+    scaler.run(&frame, &mut rgba).ok()?;
+
+    let pitch = rgba.stride(0);
+    let data = rgba.data(0);
+    let mut pixels = Vec::with_capacity(width as usize * height as usize);
+    for y in 0..height as usize {
+        for px in data[y * pitch..y * pitch + width as usize * 4].chunks_exact(4) {
+            pixels.push(Color::new(px[0], px[1], px[2]));
+        }
+    }
+    Some(Cover {
+        width: width as usize,
+        height: height as usize,
+        pixels,
+    })
+}
+
+// @ ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== @
+
+/// 解析 LRC 时间标签 `mm:ss.xx`，不是时间标签时返回 `None`
+fn parse_lrc_time(tag: &str) -> Option<Duration> {
+    let (min, sec) = tag.split_once(':')?;
+    let min = min.trim().parse::<u64>().ok()?;
+    // 有些文件用 `mm:ss:xx`
+    let sec = sec.trim().replacen(':', ".", 1).parse::<f64>().ok()?;
+    if !(0.0..60.0).contains(&sec) {
+        return None;
+    }
+    Some(Duration::from_secs(min * 60) + Duration::from_secs_f64(sec))
+}
+
+/// 去掉增强 LRC 的逐字时间标签 `<mm:ss.xx>`
+fn strip_word_times(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('<') {
+        let Some(end) = rest[start..].find('>') else {
+            break;
+        };
+        out.push_str(&rest[..start]);
+        let tag = &rest[start + 1..start + end];
+        if parse_lrc_time(tag).is_none() {
+            out.push_str(&rest[start..=start + end]);
+        }
+        rest = &rest[start + end + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// 解析 LRC 歌词，返回按时间排序的歌词行
+/// - 一行可以有多个时间标签
+/// - 支持 `[offset:±毫秒]`，正数表示歌词提前
+fn parse_lrc(text: &str) -> Vec<(Duration, String)> {
+    let mut offset = 0i64;
+    let mut lines = Vec::new();
+    for line in text.lines() {
+        let mut rest = line.trim();
+        let mut times = Vec::new();
+        while let Some(tag) = rest.strip_prefix('[') {
+            let Some(end) = tag.find(']') else {
+                break;
+            };
+            let inner = &tag[..end];
+            if let Some(time) = parse_lrc_time(inner) {
+                times.push(time);
+            } else if let Some(value) = inner.strip_prefix("offset:") {
+                offset = value.trim().parse().unwrap_or(0);
+            }
+            rest = &tag[end + 1..];
+        }
+        let lyric = strip_word_times(rest).trim().to_string();
+        for time in times {
+            lines.push((time, lyric.clone()));
+        }
+    }
+    for (time, _) in lines.iter_mut() {
+        let ms = time.as_millis() as i64 - offset;
+        *time = Duration::from_millis(ms.max(0) as u64);
+    }
+    lines.sort_by_key(|(time, _)| *time);
+    lines
+}
+
+// @ ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== @
+
+/// 音乐界面的布局（字符）
+struct Layout {
+    /// 封面区域：(x, y, 宽, 高)，没有封面时宽高为零
+    cover: (usize, usize, usize, usize),
+    /// 文字区域：(x, y, 宽, 高)
+    text: (usize, usize, usize, usize),
+}
+
+fn layout(cells_w: usize, cells_h: usize, font_w: f32, font_h: f32, has_cover: bool) -> Layout {
+    let margin_x = (cells_w / 16).max(2);
+    let margin_y = (cells_h / 8).max(1);
+    let avail_h = cells_h.saturating_sub(margin_y * 2);
+    let (cw, ch) = if has_cover {
+        // 让封面在屏幕上是正方形，且不超过屏幕宽度的 40%
+        let cw = ((avail_h as f32 * font_h / font_w) as usize).min(cells_w * 2 / 5);
+        let ch = ((cw as f32 * font_w / font_h) as usize).min(avail_h);
+        (cw, ch)
+    } else {
+        (0, 0)
+    };
+    let cy = (cells_h - ch) / 2;
+    let tx = if cw > 0 { margin_x + cw + 4 } else { margin_x };
+    let th = if ch > 0 { ch } else { avail_h };
+    Layout {
+        cover: (margin_x, cy, cw, ch),
+        text: (tx, (cells_h - th) / 2, cells_w.saturating_sub(tx + 2), th),
+    }
+}
+
+/// 把封面按比例缩放到 `w * h` 像素（区域平均）
+fn scale_cover(cover: &Cover, w: usize, h: usize) -> Vec<Color> {
+    let mut out = Vec::with_capacity(w * h);
+    for y in 0..h {
+        let y0 = y * cover.height / h;
+        let y1 = ((y + 1) * cover.height / h).max(y0 + 1);
+        for x in 0..w {
+            let x0 = x * cover.width / w;
+            let x1 = ((x + 1) * cover.width / w).max(x0 + 1);
+            let (mut r, mut g, mut b) = (0u32, 0u32, 0u32);
+            for sy in y0..y1 {
+                for c in &cover.pixels[sy * cover.width + x0..sy * cover.width + x1] {
+                    r += c.r as u32;
+                    g += c.g as u32;
+                    b += c.b as u32;
+                }
+            }
+            let n = ((y1 - y0) * (x1 - x0)) as u32;
+            out.push(Color::new((r / n) as u8, (g / n) as u8, (b / n) as u8));
+        }
+    }
+    out
+}
+
+/// 把封面画到帧上，之后由当前的颜色模式渲染
+pub fn render_cover(frame: &mut [Color], w: usize, h: usize) {
+    let (cells_w, cells_h, font_w, font_h) = {
+        let ctx = RENDER_CONTEXT.lock();
+        (
+            ctx.cells_width,
+            ctx.cells_height,
+            ctx.font_width,
+            ctx.font_height,
+        )
+    };
+    if cells_w == 0 || cells_h == 0 {
+        return;
+    }
+    let mut info = MUSIC.lock();
+    let info = &mut *info;
+    let Some(cover) = info.cover.as_ref() else {
+        return;
+    };
+    let layout = layout(cells_w, cells_h, font_w, font_h, true);
+    let (cx, cy, cw, ch) = layout.cover;
+    if cw == 0 || ch == 0 {
+        return;
+    }
+
+    // 字符区域换算成帧像素，再按封面比例居中放入
+    let (px, py) = (w as f32 / cells_w as f32, h as f32 / cells_h as f32);
+    let (box_w, box_h) = (cw as f32 * px, ch as f32 * py);
+    let (phys_w, phys_h) = (cw as f32 * font_w, ch as f32 * font_h);
+    let k = (phys_w / cover.width as f32).min(phys_h / cover.height as f32);
+    let sw = ((cover.width as f32 * k / phys_w * box_w) as usize).max(1);
+    let sh = ((cover.height as f32 * k / phys_h * box_h) as usize).max(1);
+    let ox = (cx as f32 * px + (box_w - sw as f32) / 2.0) as usize;
+    let oy = (cy as f32 * py + (box_h - sh as f32) / 2.0) as usize;
+
+    if info
+        .scaled
+        .as_ref()
+        .is_none_or(|(w, h, _)| (*w, *h) != (sw, sh))
+    {
+        info.scaled = Some((sw, sh, scale_cover(cover, sw, sh)));
+    }
+    let Some((_, _, scaled)) = info.scaled.as_ref() else {
+        return;
+    };
+    for y in 0..sh.min(h.saturating_sub(oy)) {
+        let n = sw.min(w.saturating_sub(ox));
+        frame[(oy + y) * w + ox..(oy + y) * w + ox + n]
+            .copy_from_slice(&scaled[y * sw..y * sw + n]);
+    }
+}
+
+const TITLE_COLOR: Color = Color::new(255, 255, 255);
+const TAG_COLOR: Color = Color::new(160, 160, 160);
+const LYRIC_COLOR: Color = Color::new(110, 110, 110);
+const LYRIC_CURRENT_COLOR: Color = Color::new(255, 220, 120);
+
+/// 渲染回调：在封面旁边显示标签和滚动的歌词
+pub fn render_music(wrap: &mut ContextWrapper) {
+    if !crate::render::show_music_view() {
+        return;
+    }
+    let info = MUSIC.lock();
+    let layout = layout(
+        wrap.cells_width,
+        wrap.cells_height,
+        wrap.font_width,
+        wrap.font_height,
+        info.cover.is_some(),
+    );
+    let (tx, ty, tw, th) = layout.text;
+    if tw == 0 || th == 0 {
+        return;
+    }
+    let (tx, ty) = (tx as isize, ty as isize);
+    let mut put = |text: &str, y: isize, fg: Color| {
+        crate::ui::helper::putat(wrap, text, tx, y, tw, 1, tx, y, Some(fg), None, false);
+    };
+
+    let mut y = ty;
+    let tags = [
+        (info.title.as_deref(), TITLE_COLOR),
+        (info.artist.as_deref(), TAG_COLOR),
+        (info.album.as_deref(), TAG_COLOR),
+    ];
+    for (text, color) in tags {
+        if let Some(text) = text {
+            put(text, y, color);
+            y += 1;
+        }
+    }
+    if info.lyrics.is_empty() {
+        return;
+    }
+
+    // 当前歌词行固定在歌词区域中间，前后各显示几行
+    let top = if y > ty { y + 1 } else { y };
+    let rows = (ty + th as isize - top).max(0) as usize;
+    if rows == 0 {
+        return;
+    }
+    let played = wrap.played_time.unwrap_or_default();
+    let current = info.lyrics.partition_point(|(time, _)| *time <= played);
+    let current = current as isize - 1;
+    let mid = top + rows as isize / 2;
+    for row in 0..rows as isize {
+        let i = current + (top + row - mid);
+        let Some((_, text)) = usize::try_from(i).ok().and_then(|i| info.lyrics.get(i)) else {
+            continue;
+        };
+        let color = if i == current {
+            LYRIC_CURRENT_COLOR
+        } else {
+            LYRIC_COLOR
+        };
+        put(text, top + row, color);
+    }
+}
+
+// @ ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== @
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn lrc_time_formats() {
+        assert_eq!(parse_lrc_time("01:02.50"), Some(ms(62_500)));
+        assert_eq!(parse_lrc_time("01:02"), Some(ms(62_000)));
+        // `mm:ss:xx` 与 `mm:ss.xx` 相同
+        assert_eq!(parse_lrc_time("01:02:50"), Some(ms(62_500)));
+        assert_eq!(parse_lrc_time(" 3 : 04.25 "), Some(ms(184_250)));
+        assert_eq!(parse_lrc_time("00:60.00"), None);
+        assert_eq!(parse_lrc_time("ar:someone"), None);
+        assert_eq!(parse_lrc_time("12"), None);
+    }
+
+    #[test]
+    fn strip_word_time_tags() {
+        assert_eq!(strip_word_times("<00:01.00>a <00:01.50>b"), "a b");
+        // 不是时间的尖括号保留
+        assert_eq!(strip_word_times("a <b> c"), "a <b> c");
+        assert_eq!(strip_word_times("a <00:01.00"), "a <00:01.00");
+    }
+
+    #[test]
+    fn lrc_multiple_tags_sorted() {
+        let lrc = "[ti:Title]\n[00:10.00][00:30.00]chorus\n[00:20.00]verse\n[00:40.00]\n";
+        assert_eq!(
+            parse_lrc(lrc),
+            vec![
+                (ms(10_000), "chorus".to_string()),
+                (ms(20_000), "verse".to_string()),
+                (ms(30_000), "chorus".to_string()),
+                (ms(40_000), String::new()),
+            ]
+        );
+    }
+
+    #[test]
+    fn lrc_offset() {
+        // 正数表示歌词提前，结果不小于零
+        let lrc = "[offset:+500]\n[00:00.20]first\n[00:02.00]<00:02.00>second <00:02.50>line\n";
+        assert_eq!(
+            parse_lrc(lrc),
+            vec![
+                (ms(0), "first".to_string()),
+                (ms(1_500), "second line".to_string()),
+            ]
+        );
+        let lrc = "[00:01.00]a\n[offset:-250]\n";
+        assert_eq!(parse_lrc(lrc), vec![(ms(1_250), "a".to_string())]);
+    }
+}
@@ -99,6 +99,23 @@ impl Playlist {
         Some(&self.items[self.pos])
     }
 
+    /// 查看 [`Playlist::next`] 将返回的项目，不移动位置
+    pub fn peek_next(&self) -> Option<&String> {
+        if self.items.len() == 0 {
+            return None;
+        }
+        if let Some(next) = self.setnext {
+            return self.items.get(next);
+        }
+        if self.pos + 1 < self.items.len() {
+            Some(&self.items[self.pos + 1])
+        } else if self.looping {
+            Some(&self.items[0])
+        } else {
+            None
+        }
+    }
+
     pub fn prev(&mut self) -> Option<&String> {
         if self.items.len() == 0 {
             return None;
@@ -0,0 +1,229 @@
+use parking_lot::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::render::RENDER_CONTEXT;
+use crate::statistics;
+use crate::stdout::pending_frames;
+use crate::util::{Color, ColorMode};
+
+// @ ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== @
+
+/// 输出帧率低于此值时降级
+const MIN_FPS: f32 = 10.0;
+/// 估计恢复后的帧率高于此值时恢复
+const RECOVER_FPS: f32 = 20.0;
+/// 每降一级，估计每帧的输出量减半
+const LEVEL_FACTOR: f32 = 2.0;
+/// 连续满足条件这么久才切换级别，避免来回抖动
+const DEGRADE_AFTER: Duration = Duration::from_secs(2);
+const RECOVER_AFTER: Duration = Duration::from_secs(5);
+/// 标准输出队列中最多积压的帧数，超过时暂停渲染，限制延迟
+const MAX_QUEUED_FRAMES: usize = 1;
+/// 等待输出时的最长暂停，保证暂停、跳转等操作仍然及时响应
+const MAX_PACE_WAIT: Duration = Duration::from_millis(500);
+/// 实测链路速度时留出的余量
+const LINK_MARGIN: f32 = 0.9;
+
+/// 降级级别
+/// - 0：不降级
+/// - 1：改用 256 色并关闭抖动
+/// - 2：再把分辨率减半
+const MAX_LEVEL: u8 = 2;
+
+/// 每秒输出字节数的上限，0 表示只按实测的链路速度限制
+static BUDGET: AtomicU64 = AtomicU64::new(0);
+
+pub fn set_budget(bytes_per_second: u64) {
+    BUDGET.store(bytes_per_second, Ordering::SeqCst);
+}
+
+struct Controller {
+    level: u8,
+    /// 每帧输出字节数的指数滑动平均
+    frame_bytes: f32,
+    /// 可用的输出速度（字节/秒），`None` 表示不受限
+    capacity: Option<f32>,
+    /// 开始满足降级或恢复条件的时间
+    since: Option<Instant>,
+    /// 降级前的颜色模式，和降级后实际使用的模式
+    degraded_mode: Option<(ColorMode, ColorMode)>,
+}
+
+static CONTROLLER: Mutex<Controller> = Mutex::new(Controller {
+    level: 0,
+    frame_bytes: 0.0,
+    capacity: None,
+    since: None,
+    degraded_mode: None,
+});
+
+impl Controller {
+    /// 按输出能力计算的最小帧间隔
+    fn interval(&self) -> Duration {
+        match self.capacity {
+            Some(capacity) if capacity > 0.0 => {
+                Duration::from_secs_f32((self.frame_bytes / capacity).min(60.0))
+            }
+            _ => Duration::ZERO,
+        }
+    }
+
+    fn fps(&self) -> f32 {
+        match self.interval().as_secs_f32() {
+            0.0 => f32::INFINITY,
+            s => 1.0 / s,
+        }
+    }
+}
+
+/// 实测的标准输出速度，按最近 60 次写入的字节数和耗时计算
+/// - 写入耗时过短时无法准确测量，认为不受限
+fn link_rate() -> Option<f32> {
+    let stats = statistics::get(0);
+    let stats = stats.lock();
+    let bytes: usize = stats.output_bytes.iter().sum();
+    let time: Duration = stats.output_time.iter().sum();
+    (time >= Duration::from_millis(20)).then(|| bytes as f32 / time.as_secs_f32())
+}
+
+/// 每帧放入输出队列后调用
+pub fn frame_queued(bytes: usize) {
+    let link = link_rate().map(|rate| rate * LINK_MARGIN);
+    let mut ctl = CONTROLLER.lock();
+    ctl.frame_bytes = match ctl.frame_bytes {
+        0.0 => bytes as f32,
+        avg => avg * 0.8 + bytes as f32 * 0.2,
+    };
+    let budget = BUDGET.load(Ordering::SeqCst);
+    let budget = (budget > 0).then_some(budget as f32);
+    ctl.capacity = match (link, budget) {
+        (Some(link), Some(budget)) => Some(link.min(budget)),
+        (link, budget) => link.or(budget),
+    };
+
+    let fps = ctl.fps();
+    let (wanted, after) = if fps < MIN_FPS && ctl.level < MAX_LEVEL {
+        (Some(ctl.level + 1), DEGRADE_AFTER)
+    } else if ctl.level > 0 && fps / LEVEL_FACTOR >= RECOVER_FPS {
+        (Some(ctl.level - 1), RECOVER_AFTER)
+    } else {
+        (None, Duration::ZERO)
+    };
+    match wanted {
+        None => ctl.since = None,
+        Some(level) => {
+            let since = *ctl.since.get_or_insert_with(Instant::now);
+            if since.elapsed() >= after {
+                if level > ctl.level {
+                    info_f16n!("Output is too slow, lowering quality (level {})", level);
+                } else {
+                    info_f16n!("Output caught up, restoring quality (level {})", level);
+                }
+                ctl.level = level;
+                ctl.since = None;
+            }
+        }
+    }
+}
+
+/// 降级时使用的较便宜的颜色模式
+/// - 真彩色的转义序列最长，图像协议每帧都要传输整幅图像
+fn cheaper(mode: ColorMode) -> Option<ColorMode> {
+    match mode {
+        ColorMode::TrueColorOnly | ColorMode::Palette256Prefer | ColorMode::GrayScale => {
+            Some(ColorMode::Palette256Only)
+        }
+        mode if mode.fppc() == (0, 0) => Some(ColorMode::Palette256Only),
+        _ => None,
+    }
+}
+
+/// 按当前级别切换或恢复颜色模式
+/// - 用户在降级期间手动切换了模式时，不再恢复
+fn apply_color_mode() {
+    // 加锁顺序与输出时一致：先 RENDER_CONTEXT 再 CONTROLLER（`frame_queued` 在持有 RENDER_CONTEXT 时调用）
+    let mut ctx = RENDER_CONTEXT.lock();
+    let mut ctl = CONTROLLER.lock();
+    match ctl.degraded_mode {
+        Some((original, applied)) if ctl.level == 0 => {
+            if ctx.color_mode == applied {
+                ctx.set_color_mode(original);
+            }
+            ctl.degraded_mode = None;
+        }
+        Some((_, applied)) if ctx.color_mode != applied => ctl.degraded_mode = None,
+        None if ctl.level > 0 => {
+            let original = ctx.color_mode;
+            if let Some(cheaper) = cheaper(original) {
+                ctx.set_color_mode(cheaper);
+                ctl.degraded_mode = Some((original, ctx.color_mode));
+            }
+        }
+        _ => {}
+    }
+}
+
+/// 渲染一帧后调用，等待到允许渲染下一帧
+/// - 帧间隔不小于按输出能力计算的间隔
+/// - 输出队列积压时继续等待，使延迟保持在一两帧以内
+pub fn pace(render_start: Instant) {
+    apply_color_mode();
+    let interval = CONTROLLER.lock().interval().min(MAX_PACE_WAIT);
+    loop {
+        let elapsed = render_start.elapsed();
+        if elapsed >= MAX_PACE_WAIT
+            || (elapsed >= interval && pending_frames() <= MAX_QUEUED_FRAMES)
+        {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(5));
+    }
+}
+
+/// 是否处于降级状态，降级时不抖动，抖动的噪声会让相邻单元格无法合并
+pub fn degraded() -> bool {
+    CONTROLLER.lock().level > 0
+}
+
+/// 分辨率缩小的倍数
+pub fn resolution_divisor() -> usize {
+    match CONTROLLER.lock().level {
+        2.. => 2,
+        _ => 1,
+    }
+}
+
+/// 把画面按 `divisor` 取块，块内像素相同，相邻单元格的颜色转义序列可以省略
+pub fn reduce_resolution(
+    frame: &[Color],
+    width: usize,
+    height: usize,
+    pitch: usize,
+    divisor: usize,
+) -> Vec<Color> {
+    let mut out = Vec::with_capacity(width * height);
+    for y in 0..height {
+        let row = &frame[(y - y % divisor) * pitch..];
+        out.extend((0..width).map(|x| row[x - x % divisor]));
+    }
+    out
+}
+
+pub struct Status {
+    /// 可用的输出速度（字节/秒），`None` 表示不受限
+    pub capacity: Option<f32>,
+    /// 按输出能力估计的最高帧率
+    pub max_fps: f32,
+    pub level: u8,
+}
+
+/// 用于叠加文本的输出速度状态
+pub fn status() -> Status {
+    let ctl = CONTROLLER.lock();
+    Status {
+        capacity: ctl.capacity,
+        max_fps: ctl.fps(),
+        level: ctl.level,
+    }
+}
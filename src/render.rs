@@ -1,16 +1,20 @@
 use av::util::frame::video::Video as VideoFrame;
 use core::panic;
 use parking_lot::{Condvar, Mutex};
+#[cfg(any(feature = "sixel", feature = "osc1337"))]
 use std::io::Write as _;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::{Duration, Instant};
-#[cfg(feature = "unicode")]
-use unicode_width::UnicodeWidthChar;
 
+use crate::ascii;
+use crate::dither;
+use crate::escape::{EncodedLine, LineEncoder, cursor_len, write_colors, write_cursor};
 use crate::playlist::PLAYLIST;
+use crate::ratectl;
 use crate::stdout::{pend_print, pending_frames, remove_pending_frames};
 use crate::term::{self, TERM_QUIT, Winsize};
+use crate::termcap;
 use crate::{TOKIO_RUNTIME, statistics};
 use crate::{avsync, util::*};
 
@@ -215,7 +219,9 @@ impl RenderContext {
             (xchars, ychars)
         };
         let (xpixels, ypixels) = if xpixels == 0 || ypixels == 0 {
-            (xchars * 8, ychars * 16)
+            // 启动时通过 `CSI 16t` 查询到的单元格大小，查询失败时按 8×16 猜测
+            let (cw, ch) = termcap::cell_size().unwrap_or((8, 16));
+            (xchars * cw, ychars * ch)
         } else {
             (xpixels, ypixels)
         };
@@ -313,6 +319,26 @@ impl RenderContext {
         self.update_size(None, None);
     }
 
+    /// 切换颜色模式，并按新模式的 fppc 重新计算大小
+    /// - 终端复用器不允许 passthrough 时，图像协议无法显示，改用文本模式
+    pub fn set_color_mode(&mut self, color_mode: ColorMode) {
+        let color_mode = if termcap::graphics_usable(color_mode) {
+            color_mode
+        } else {
+            let fallback = termcap::text_color_mode();
+            warning_f16n!(
+                "{} needs terminal multiplexer passthrough, using {} instead",
+                color_mode,
+                fallback
+            );
+            fallback
+        };
+        self.color_mode = color_mode;
+        let (fppc_x, fppc_y) = color_mode.fppc();
+        self.update_fppc(fppc_x, fppc_y);
+        self.force_flush_next();
+    }
+
     fn take_cells(&mut self) -> Option<(Vec<Cell>, Vec<Cell>)> {
         match (self.cells.take(), self.lasts.take()) {
             (Some(cells), Some(lasts)) => Some((cells, lasts)),
@@ -413,7 +439,44 @@ pub fn add_render_callback(callback: fn(&mut ContextWrapper<'_, '_>)) {
 /// 主渲染目标（当前本地终端）
 pub static RENDER_CONTEXT: Mutex<RenderContext> = Mutex::new(RenderContext::new());
 
-fn render(frame: &[Color], width: usize, height: usize, pitch: usize) -> bool {
+/// `composite` 为 `false` 时不合成对比/分屏画面（可视化、音乐封面等合成的画面）
+fn render(frame: &[Color], width: usize, height: usize, pitch: usize, composite: bool) -> bool {
+    // 对比模式或分屏播放时先把其他画面合成进来
+    #[cfg(feature = "video")]
+    let composed = composite
+        .then(|| {
+            crate::compare::process(frame, width, height, pitch)
+                .or_else(|| crate::tiles::compose(frame, width, height, pitch))
+        })
+        .flatten();
+    #[cfg(not(feature = "video"))]
+    let _ = composite;
+    #[cfg(feature = "video")]
+    let (frame, pitch) = match composed {
+        Some(ref composed) => (composed.as_slice(), width),
+        None => (frame, pitch),
+    };
+
+    // 输出跟不上时降低分辨率
+    let divisor = ratectl::resolution_divisor();
+    let reduced =
+        (divisor > 1).then(|| ratectl::reduce_resolution(frame, width, height, pitch, divisor));
+    let (frame, pitch) = match reduced {
+        Some(ref reduced) => (reduced.as_slice(), width),
+        None => (frame, pitch),
+    };
+
+    // 调色板受限的模式先抖动，避免色带；降级时不抖动，减少输出量
+    let color_mode = RENDER_CONTEXT.lock().color_mode;
+    let dithered = match ratectl::degraded() {
+        true => None,
+        false => dither::process(color_mode, frame, width, height, pitch),
+    };
+    let (frame, pitch) = match dithered {
+        Some(ref dithered) => (dithered.as_slice(), width),
+        None => (frame, pitch),
+    };
+
     let mut ctx = RENDER_CONTEXT.lock();
 
     let Some((mut cells, lasts)) = ctx.take_cells() else {
@@ -465,57 +528,49 @@ async fn print_diff_line(
     lasts: &[Cell],
     force_flush: bool,
     color_mode: ColorMode,
-) -> Vec<u8> {
+    rep: bool,
+) -> EncodedLine {
     let default_char = match color_mode {
         #[cfg(feature = "sixel")]
         ColorMode::Sixel => ' ',
         #[cfg(feature = "osc1337")]
         ColorMode::OSC1337 => ' ',
+        #[cfg(feature = "kitty")]
+        ColorMode::Kitty => ' ',
         ColorMode::TrueColorOnly => '▄',
         ColorMode::Palette256Prefer => '▄',
         ColorMode::Palette256Only => '▄',
         ColorMode::GrayScale => '▄',
         ColorMode::BlackWhite => '▄',
         ColorMode::AsciiArt => '*',
+        ColorMode::AsciiMono => '*',
         ColorMode::Braille => '⣿',
+        ColorMode::Quadrant => '█',
+        ColorMode::Sextant => '█',
+        ColorMode::Octant => '█',
     };
-    let mut last_bg = Color::transparent();
-    let mut last_fg = Color::transparent();
-    let mut buf = Vec::with_capacity(1024);
-    let mut skip_count = 0u32;
-    for (cell, last) in cells.iter().zip(lasts.iter()) {
-        if cell.c == Some('\0') {
-            continue;
-        }
-
-        #[cfg(feature = "unicode")]
-        let cw = cell.c.map_or(1, |c| c.width().unwrap_or(1).max(1));
-        #[cfg(not(feature = "unicode"))]
-        let cw = 1;
-        if !force_flush && cell == last && cw == 1 {
-            skip_count += 1;
-            continue;
-        } else if skip_count == 1 {
-            write!(buf, "\x1b[C").unwrap();
-            skip_count = 0;
-        } else if skip_count > 1 {
-            write!(buf, "\x1b[{skip_count}C").unwrap();
-            skip_count = 0;
-        }
-
-        let (fg, bg) = (some_if_ne(cell.fg, last_fg), some_if_ne(cell.bg, last_bg));
-
-        escape_set_color(&mut buf, fg, bg, color_mode);
-        if default_char == '⣿' {
-            buf.extend_from_slice(cell.c.unwrap_or(cell.braille).to_string().as_bytes());
-        } else {
-            buf.extend_from_slice(cell.c.unwrap_or(default_char).to_string().as_bytes());
-        }
-
-        last_fg = cell.fg;
-        last_bg = cell.bg;
+    // 这些模式每个单元格的字符不同，保存在 glyph 中
+    #[allow(unused_mut)]
+    let mut use_glyph = matches!(
+        color_mode,
+        ColorMode::AsciiArt
+            | ColorMode::AsciiMono
+            | ColorMode::Braille
+            | ColorMode::Quadrant
+            | ColorMode::Sextant
+            | ColorMode::Octant
+    );
+    #[cfg(feature = "kitty")]
+    if color_mode == ColorMode::Kitty {
+        use_glyph = true;
     }
-    buf
+    let encoder = LineEncoder {
+        color_mode,
+        default_char: (!use_glyph).then_some(default_char),
+        force_flush,
+        rep,
+    };
+    encoder.encode(cells, lasts)
 }
 
 async fn print_diff_inner(
@@ -540,6 +595,7 @@ async fn print_diff_inner(
         text_force_flush = true; // 由于图像会覆盖文本
     }
 
+    let rep = termcap::rep();
     // ASSUME cells 和 lasts 只在此处使用并且所有 task 均被正确回收
     let result = (cells.into_iter().zip(lasts.into_iter()))
         .map(|(cell, last)| {
@@ -548,6 +604,7 @@ async fn print_diff_inner(
                 last,
                 text_force_flush,
                 wrap.color_mode,
+                rep,
             ))
         })
         .collect::<Vec<_>>()
@@ -570,8 +627,9 @@ async fn print_diff_inner(
             wrap.padding_left + 1,
         )
         .unwrap();
+        let mut sixel = Vec::with_capacity(65536);
         crate::escape::format_sixel(
-            &mut buf,
+            &mut sixel,
             wrap.frame,
             wrap.frame_width,
             wrap.frame_height,
@@ -580,6 +638,7 @@ async fn print_diff_inner(
             wrap.video_cells_height,
         )
         .await;
+        termcap::write_graphics(&mut buf, &sixel, ColorMode::Sixel);
     }
 
     #[cfg(feature = "osc1337")]
@@ -591,26 +650,70 @@ async fn print_diff_inner(
             wrap.padding_left + 1,
         )
         .unwrap();
+        let mut image = Vec::with_capacity(65536);
         crate::escape::format_image(
+            &mut image,
+            wrap.frame,
+            wrap.frame_width,
+            wrap.frame_height,
+            wrap.frame_pitch,
+            wrap.video_cells_width,
+            wrap.video_cells_height,
+        );
+        termcap::write_graphics(&mut buf, &image, ColorMode::OSC1337);
+    }
+
+    #[cfg(feature = "kitty")]
+    if wrap.color_mode == ColorMode::Kitty {
+        crate::escape::format_kitty(
             &mut buf,
             wrap.frame,
             wrap.frame_width,
             wrap.frame_height,
             wrap.frame_pitch,
+            (wrap.padding_top, wrap.padding_left),
             wrap.video_cells_width,
             wrap.video_cells_height,
+            wrap.clear_before_render,
         );
+    } else {
+        crate::escape::kitty_reset(&mut buf);
     }
 
+    // 颜色状态跨行沿用，没有变化的行合并为一次换行
     buf.extend_from_slice(b"\x1b[m\x1b[H");
+    let reset = (Color::transparent(), Color::transparent());
+    let mut colors = reset;
+    let mut newlines = 0;
+    let (mut saved, mut kept) = (0, 0);
+    let mut scratch = Vec::new();
     for (i, line) in result.into_iter().enumerate() {
         if i != 0 {
-            buf.extend_from_slice(b"\x1b[m\x1b[E");
+            newlines += 1;
         }
-        buf.extend_from_slice(&line);
+        saved += line.saved;
+        kept += line.kept;
+        let Some((head, fg, bg)) = line.head else {
+            continue;
+        };
+        // 逐行输出时每行以 `CSI m CSI E` 开头，并从默认颜色开始设置
+        saved += 6 * newlines - cursor_len(newlines);
+        write_cursor(&mut buf, newlines, 'E');
+        newlines = 0;
+        buf.extend_from_slice(&line.buf[..head]);
+        let before = buf.len();
+        write_colors(&mut buf, colors, (fg, bg), wrap.color_mode);
+        scratch.clear();
+        write_colors(&mut scratch, reset, (fg, bg), wrap.color_mode);
+        saved += scratch.len().saturating_sub(buf.len() - before);
+        buf.extend_from_slice(&line.buf[head..]);
+        colors = line.tail.unwrap_or((fg, bg));
     }
+    saved += 6 * newlines;
 
     statistics::set_escape_string_encode_time(0, instant.elapsed());
+    statistics::set_encoder_savings(0, saved, kept);
+    ratectl::frame_queued(buf.len());
     if wrap.force_flush_next {
         remove_pending_frames();
         assert!(buf.len() > 0, "force flush but buffer is empty");
@@ -652,10 +755,38 @@ static VIDEO_FRAME: Mutex<Option<Arc<VideoFrame>>> = Mutex::new(None);
 static VIDEO_FRAME_COND: Condvar = Condvar::new();
 static VIDEO_FRAME_REQUEST: Condvar = Condvar::new();
 
+/// 已经显示过的缩放帧，留给视频线程复用，避免每帧重新分配
+static FRAME_POOL: Mutex<Vec<VideoFrame>> = Mutex::new(Vec::new());
+/// 帧池最多保留的帧数
+const FRAME_POOL_SIZE: usize = 3;
+
 pub fn api_send_frame(frame: VideoFrame) {
     let mut lock = VIDEO_FRAME.lock();
-    lock.replace(Arc::new(frame));
+    let old = lock.replace(Arc::new(frame));
     VIDEO_FRAME_COND.notify_one();
+    drop(lock);
+
+    // 渲染线程不再持有旧帧时回收
+    if let Some(old) = old.and_then(|old| Arc::try_unwrap(old).ok()) {
+        let mut pool = FRAME_POOL.lock();
+        if pool.len() < FRAME_POOL_SIZE {
+            pool.push(old);
+        }
+    }
+}
+
+/// 取出一个指定大小的 RGBA 帧，优先复用帧池中的帧
+pub fn api_take_frame(width: u32, height: u32) -> VideoFrame {
+    let mut pool = FRAME_POOL.lock();
+    pool.retain(|f| f.width() == width && f.height() == height);
+    pool.pop()
+        .unwrap_or_else(|| VideoFrame::new(av::format::Pixel::RGBA, width, height))
+}
+
+/// 请求视频线程重新输出当前帧，例如视图发生变化时
+pub fn api_request_frame() {
+    let _lock = VIDEO_FRAME.lock();
+    VIDEO_FRAME_REQUEST.notify_one();
 }
 
 pub fn api_wait_frame_request_for(duration: Duration) -> bool {
@@ -688,36 +819,28 @@ fn update_termsize_and_take_frame(
 }
 
 #[cfg(feature = "audio")]
-fn render_audio_visualizer(empty_frame: &mut [Color], w: usize, h: usize) {
-    use crate::audio::{AUDIO_VOLUME_STATISTICS, AUDIO_VOLUME_STATISTICS_LEN};
-    let mut stat = AUDIO_VOLUME_STATISTICS.lock();
-    while stat.len() < AUDIO_VOLUME_STATISTICS_LEN {
-        stat.push_front(0.0);
-    }
-    for x in 0..w {
-        let max = (h as f32 * 0.8).clamp(0.0, h as f32).round() as usize;
-        empty_frame[(h - max) / 2 * w + x] = Color::new(64, 192, 128);
-        empty_frame[(h + max) / 2 * w + x] = Color::new(64, 192, 128);
-        let i0 = x as f32 / w as f32 * stat.len() as f32;
-        let i1 = (i0.floor() as usize).clamp(0, stat.len() - 1);
-        let i2 = (i0.ceil() as usize).clamp(0, stat.len() - 1);
-        let k = i0 - i0.floor();
-        let vol = stat[i1] * (1.0 - k) + stat[i2] * k;
-        let filled = (vol * h as f32 * 0.8).round().clamp(0.0, h as f32) as usize;
-        for y in (h - filled) / 2..(h + filled) / 2 {
-            let idx = y * w + x;
-            empty_frame[idx] = Color::new(255, 255, 255);
+static SHOW_AUDIO_VISUALIZER: AtomicBool = AtomicBool::new(false);
+
+/// 循环切换可视化样式；最后一个样式之后回到视频画面或音乐界面
+#[cfg(feature = "audio")]
+pub fn cycle_audio_visualizer() {
+    use crate::visualizer;
+    if !visualizer_visible() {
+        set_show_audio_visualizer(true);
+        return;
+    }
+    match visualizer::mode().next() {
+        Some(next) => visualizer::set_mode(next),
+        None => {
+            visualizer::set_mode(visualizer::Visualizer::ALL[0]);
+            set_show_audio_visualizer(false);
         }
     }
 }
 
 #[cfg(feature = "audio")]
-static SHOW_AUDIO_VISUALIZER: AtomicBool = AtomicBool::new(false);
-
-#[cfg(feature = "audio")]
-pub fn toggle_show_audio_visualizer() -> bool {
-    let old = SHOW_AUDIO_VISUALIZER.fetch_xor(true, Ordering::SeqCst);
-    !old
+pub fn set_show_audio_visualizer(show: bool) {
+    SHOW_AUDIO_VISUALIZER.store(show, Ordering::SeqCst);
 }
 
 #[cfg(feature = "audio")]
@@ -730,6 +853,41 @@ pub fn show_audio_visualizer() -> bool {
     false
 }
 
+/// 没有视频、也没有打开可视化时，有封面或标签的文件显示音乐界面
+#[cfg(feature = "audio")]
+pub fn show_music_view() -> bool {
+    !avsync::has_video() && !show_audio_visualizer() && crate::music::available()
+}
+
+#[cfg(not(feature = "audio"))]
+pub fn show_music_view() -> bool {
+    false
+}
+
+/// 当前是否显示音频可视化（没有视频也没有音乐界面时总是显示）
+pub fn visualizer_visible() -> bool {
+    show_audio_visualizer() || (!avsync::has_video() && !show_music_view())
+}
+
+/// 延迟校准模式：每秒开头闪白画面，与提示音对照
+#[cfg(feature = "audio")]
+fn render_calibration_flash(empty_frame: &mut [Color]) {
+    let time = avsync::played_time_or_zero().as_secs_f64();
+    if time.fract() < crate::audio::CALIBRATION_PULSE {
+        empty_frame.fill(Color::new(255, 255, 255));
+    }
+}
+
+#[cfg(feature = "audio")]
+fn is_calibrating() -> bool {
+    crate::audio::is_calibrating()
+}
+
+#[cfg(not(feature = "audio"))]
+fn is_calibrating() -> bool {
+    false
+}
+
 pub fn render_main() {
     let mut empty_frame = Vec::new();
     while TERM_QUIT.load(Ordering::SeqCst) == false {
@@ -737,12 +895,19 @@ pub fn render_main() {
 
         let render_start = Instant::now();
 
-        let show_visualizer = !avsync::has_video() || show_audio_visualizer();
+        let calibrating = is_calibrating();
+        let show_music = !calibrating && show_music_view();
+        let show_visualizer = !calibrating && !show_music && visualizer_visible();
         #[cfg(feature = "audio")]
-        if show_visualizer {
-            render_audio_visualizer(&mut empty_frame, width, height);
+        if calibrating {
+            render_calibration_flash(&mut empty_frame);
+        } else if show_music {
+            crate::music::render_cover(&mut empty_frame, width, height);
+        } else if show_visualizer {
+            crate::visualizer::render(&mut empty_frame, width, height);
         }
-        let success = if !show_visualizer && let Some(ref frame) = frame {
+        let synthetic = show_visualizer || show_music || calibrating;
+        let success = if !synthetic && let Some(ref frame) = frame {
             let bytes = frame.data(0);
             let colors: &[Color] = unsafe {
                 std::slice::from_raw_parts(
@@ -753,12 +918,12 @@ pub fn render_main() {
             let width = frame.width() as usize;
             let height = frame.height() as usize;
             let pitch = frame.stride(0) / std::mem::size_of::<Color>();
-            render(colors, width, height, pitch)
+            render(colors, width, height, pitch, true)
         } else {
-            render(&empty_frame, width, height, width)
+            render(&empty_frame, width, height, width, !synthetic)
         };
         #[cfg(feature = "audio")]
-        if show_visualizer {
+        if synthetic {
             empty_frame.fill(Color::new(0, 0, 0));
         }
 
@@ -766,6 +931,9 @@ pub fn render_main() {
             continue;
         }
 
+        // 按输出能力限制帧率，输出队列积压时等待
+        ratectl::pace(render_start);
+
         let remaining = Duration::from_millis(33).saturating_sub(render_start.elapsed());
         let mut lock = VIDEO_FRAME.lock();
         let next = lock.clone();
@@ -784,27 +952,32 @@ fn render_video_1x1(wrap: &mut ContextWrapper) {
             l10n!("render_video_1x1 only supports fppc_x = 1 and fppc_y = 1")
         );
     }
-    if let Some(chroma_key) = wrap.chroma_mode.color() {
-        for cy in wrap.padding_top..(wrap.cells_height - wrap.padding_bottom) {
-            for cx in wrap.padding_left..(wrap.cells_width - wrap.padding_right) {
-                let fy = cy - wrap.padding_top;
-                let fx = cx - wrap.padding_left;
-                let fg = wrap.frame[fy * wrap.frame_pitch + fx];
-                let fs = fg.similar_to(&chroma_key, 0.1);
-                wrap.cells[cy * wrap.cells_pitch + cx] = match fs {
-                    true => Cell::new(' ', Color::transparent(), Color::transparent()),
-                    false => Cell::none(fg, Color::transparent()),
-                };
-            }
+    // 字符按整个画面的亮度图选择，边缘检测需要相邻像素
+    let w = wrap.cells_width - wrap.padding_left - wrap.padding_right;
+    let h = wrap.cells_height - wrap.padding_top - wrap.padding_bottom;
+    let mut lum = Vec::with_capacity(w * h);
+    for fy in 0..h {
+        for fx in 0..w {
+            lum.push(wrap.frame[fy * wrap.frame_pitch + fx].luminance());
         }
-    } else {
-        for cy in wrap.padding_top..(wrap.cells_height - wrap.padding_bottom) {
-            for cx in wrap.padding_left..(wrap.cells_width - wrap.padding_right) {
-                let fy = cy - wrap.padding_top;
-                let fx = cx - wrap.padding_left;
-                let fg = wrap.frame[fy * wrap.frame_pitch + fx];
-                wrap.cells[cy * wrap.cells_pitch + cx] = Cell::none(fg, Color::transparent());
+    }
+    let glyphs = ascii::Glyphs::current();
+    let mono = wrap.color_mode == ColorMode::AsciiMono;
+    let chroma_key = wrap.chroma_mode.color();
+    for cy in wrap.padding_top..(wrap.cells_height - wrap.padding_bottom) {
+        for cx in wrap.padding_left..(wrap.cells_width - wrap.padding_right) {
+            let fy = cy - wrap.padding_top;
+            let fx = cx - wrap.padding_left;
+            let fg = wrap.frame[fy * wrap.frame_pitch + fx];
+            if chroma_key.is_some_and(|key| fg.similar_to(&key, 0.1)) {
+                wrap.cells[cy * wrap.cells_pitch + cx] =
+                    Cell::new(' ', Color::transparent(), Color::transparent());
+                continue;
             }
+            let fg = if mono { Color::transparent() } else { fg };
+            let cell = &mut wrap.cells[cy * wrap.cells_pitch + cx];
+            *cell = Cell::none(fg, Color::transparent());
+            cell.glyph = glyphs.pick(&lum, w, h, fx, fy);
         }
     }
 }
@@ -901,7 +1074,7 @@ fn render_video_2x4(wrap: &mut ContextWrapper) {
                 let color = (c1 + c2 + c3 + c4 + c5 + c6 + c7 + c8) / (8 - num) as f32;
                 let color = Color::from(color);
                 wrap.cells[cy * wrap.cells_pitch + cx] = Cell::none(color, Color::transparent());
-                wrap.cells[cy * wrap.cells_pitch + cx].braille =
+                wrap.cells[cy * wrap.cells_pitch + cx].glyph =
                     char::from_u32(0x2800 + bin).unwrap();
             }
         }
@@ -929,10 +1102,187 @@ fn render_video_2x4(wrap: &mut ContextWrapper) {
                 let color = (c1 + c2 + c3 + c4 + c5 + c6 + c7 + c8) / 8.0;
                 let color = Color::from(color);
                 wrap.cells[cy * wrap.cells_pitch + cx] = Cell::none(color, Color::transparent());
-                wrap.cells[cy * wrap.cells_pitch + cx].braille = char::from_u32(0x28ff).unwrap();
+                wrap.cells[cy * wrap.cells_pitch + cx].glyph = char::from_u32(0x28ff).unwrap();
+            }
+        }
+    }
+}
+
+/// kitty 图像在终端复用器内的 Unicode 占位符
+/// - 每行画面是一个图像，占位符的前景色编码该行的图像 ID
+#[cfg(feature = "kitty")]
+fn render_video_kitty(wrap: &mut ContextWrapper) {
+    if !termcap::kitty_placeholders() {
+        return;
+    }
+    for cy in wrap.padding_top..(wrap.cells_height - wrap.padding_bottom) {
+        let color = crate::escape::kitty_id_color(cy - wrap.padding_top);
+        for cx in wrap.padding_left..(wrap.cells_width - wrap.padding_right) {
+            let cell = &mut wrap.cells[cy * wrap.cells_pitch + cx];
+            *cell = Cell::none(color, Color::transparent());
+            cell.glyph = crate::escape::KITTY_PLACEHOLDER;
+        }
+    }
+}
+
+/// 2×n 块字符模式
+/// - 像素按行优先编号，第 i 个像素对应掩码的第 i 位
+/// - 每个单元格选择误差最小的前景/背景两色划分，而不是取平均色
+fn render_video_blocks(wrap: &mut ContextWrapper, rows: usize, glyph: fn(u8) -> char) {
+    if wrap.fppc_x != 2 || wrap.fppc_y != rows {
+        panic!("render_video_blocks only supports fppc_x = 2 and fppc_y = {rows}");
+    }
+    let chroma_key = wrap.chroma_mode.color();
+    let mut pixels = [Color::transparent(); 8];
+    for cy in wrap.padding_top..(wrap.cells_height - wrap.padding_bottom) {
+        for cx in wrap.padding_left..(wrap.cells_width - wrap.padding_right) {
+            let fy = cy - wrap.padding_top;
+            let fx = cx - wrap.padding_left;
+            for r in 0..rows {
+                for c in 0..2 {
+                    pixels[r * 2 + c] = wrap.frame[(fy * rows + r) * wrap.frame_pitch + fx * 2 + c];
+                }
             }
+            let pixels = &pixels[..rows * 2];
+
+            let (mask, fg, bg) = if let Some(chroma_key) = chroma_key {
+                // 被抠掉的像素作为透明背景，其余像素取平均色
+                let mask = (0..pixels.len())
+                    .filter(|&i| !pixels[i].similar_to(&chroma_key, 0.1))
+                    .fold(0u8, |m, i| m | 1 << i);
+                if mask == 0 {
+                    wrap.cells[cy * wrap.cells_pitch + cx] =
+                        Cell::new(' ', Color::transparent(), Color::transparent());
+                    continue;
+                }
+                (mask, mean_color(pixels, mask), Color::transparent())
+            } else {
+                best_partition(pixels)
+            };
+            let cell = &mut wrap.cells[cy * wrap.cells_pitch + cx];
+            *cell = Cell::none(fg, bg);
+            cell.glyph = glyph(mask);
+        }
+    }
+}
+
+/// 掩码选中的像素的平均色
+fn mean_color(pixels: &[Color], mask: u8) -> Color {
+    let (mut r, mut g, mut b, mut n) = (0u32, 0u32, 0u32, 0u32);
+    for (i, p) in pixels.iter().enumerate() {
+        if mask & (1 << i) != 0 {
+            r += p.r as u32;
+            g += p.g as u32;
+            b += p.b as u32;
+            n += 1;
         }
     }
+    let n = n.max(1);
+    Color::new((r / n) as u8, (g / n) as u8, (b / n) as u8)
+}
+
+/// 把最多 8 个像素分成两组，使各组到组内平均色的平方误差之和最小
+/// - 返回 (前景掩码, 前景色, 背景色)
+/// - 误差等于常数减去 Σ|组内和|²/组内像素数，所以只需比较后者
+fn best_partition(pixels: &[Color]) -> (u8, Color, Color) {
+    let n = pixels.len();
+    let full = (1usize << n) - 1;
+    // 每个子集的颜色和，由去掉最低位的子集递推
+    let mut sums = [[0f32; 3]; 256];
+    for mask in 1..=full {
+        let p = pixels[mask.trailing_zeros() as usize];
+        let prev = sums[mask & (mask - 1)];
+        sums[mask] = [
+            prev[0] + p.r as f32,
+            prev[1] + p.g as f32,
+            prev[2] + p.b as f32,
+        ];
+    }
+    let total = sums[full];
+    let norm = |s: [f32; 3]| s[0] * s[0] + s[1] * s[1] + s[2] * s[2];
+
+    // 划分和它的补集等价，只考虑不含最后一个像素的掩码
+    let mut best = (norm(total) / n as f32, 0usize);
+    for (mask, &s) in sums.iter().enumerate().take(1 << (n - 1)).skip(1) {
+        let k = mask.count_ones() as f32;
+        let rest = [total[0] - s[0], total[1] - s[1], total[2] - s[2]];
+        let score = norm(s) / k + norm(rest) / (n as f32 - k);
+        if score > best.0 {
+            best = (score, mask);
+        }
+    }
+
+    if best.1 == 0 {
+        // 颜色一致时输出满块
+        let color = mean_color(pixels, full as u8);
+        return (full as u8, color, color);
+    }
+    let mask = best.1 as u8;
+    let rest = !mask & full as u8;
+    (mask, mean_color(pixels, mask), mean_color(pixels, rest))
+}
+
+/// 2×2 象限块字符
+fn quadrant_glyph(mask: u8) -> char {
+    const GLYPHS: [char; 16] = [
+        ' ', '▘', '▝', '▀', '▖', '▌', '▞', '▛', '▗', '▚', '▐', '▜', '▄', '▙', '▟', '█',
+    ];
+    GLYPHS[mask as usize & 15]
+}
+
+/// 2×3 六分块字符，从 U+1FB00 开始
+/// - 左右半块已有字符，不在六分块中
+fn sextant_glyph(mask: u8) -> char {
+    match mask & 63 {
+        0 => ' ',
+        21 => '▌',
+        42 => '▐',
+        63 => '█',
+        m => {
+            let index = m as u32 - 1 - (m > 21) as u32 - (m > 42) as u32;
+            char::from_u32(0x1fb00 + index).unwrap_or('█')
+        }
+    }
+}
+
+/// 已有字符的八分块组合，按掩码排序
+/// - 其余组合按掩码顺序从 U+1CD00 开始排列
+const OCTANT_SPECIAL: [(u8, char); 26] = [
+    (0x00, ' '),
+    (0x01, '\u{1cea8}'),
+    (0x02, '\u{1ceab}'),
+    (0x03, '\u{1fb82}'),
+    (0x05, '▘'),
+    (0x0a, '▝'),
+    (0x0f, '▀'),
+    (0x14, '\u{1fbe6}'),
+    (0x28, '\u{1fbe7}'),
+    (0x3f, '\u{1fb85}'),
+    (0x40, '\u{1cea3}'),
+    (0x50, '▖'),
+    (0x55, '▌'),
+    (0x5a, '▞'),
+    (0x5f, '▛'),
+    (0x80, '\u{1cea0}'),
+    (0xa0, '▗'),
+    (0xa5, '▚'),
+    (0xaa, '▐'),
+    (0xaf, '▜'),
+    (0xc0, '▂'),
+    (0xf0, '▄'),
+    (0xf5, '▙'),
+    (0xfa, '▟'),
+    (0xfc, '▆'),
+    (0xff, '█'),
+];
+
+/// 2×4 八分块字符
+fn octant_glyph(mask: u8) -> char {
+    match OCTANT_SPECIAL.binary_search_by_key(&mask, |&(m, _)| m) {
+        Ok(i) => OCTANT_SPECIAL[i].1,
+        // 插入位置就是掩码更小的特殊组合的个数
+        Err(skipped) => char::from_u32(0x1cd00 + mask as u32 - skipped as u32).unwrap_or('█'),
+    }
 }
 
 pub fn render_video(wrap: &mut ContextWrapper) {
@@ -941,13 +1291,19 @@ pub fn render_video(wrap: &mut ContextWrapper) {
         ColorMode::Sixel => (),
         #[cfg(feature = "osc1337")]
         ColorMode::OSC1337 => (),
+        #[cfg(feature = "kitty")]
+        ColorMode::Kitty => render_video_kitty(wrap),
         ColorMode::TrueColorOnly => render_video_1x2(wrap),
         ColorMode::Palette256Prefer => render_video_1x2(wrap),
         ColorMode::Palette256Only => render_video_1x2(wrap),
         ColorMode::GrayScale => render_video_1x2(wrap),
         ColorMode::BlackWhite => render_video_1x2(wrap),
         ColorMode::AsciiArt => render_video_1x1(wrap),
+        ColorMode::AsciiMono => render_video_1x1(wrap),
         ColorMode::Braille => render_video_2x4(wrap),
+        ColorMode::Quadrant => render_video_blocks(wrap, 2, quadrant_glyph),
+        ColorMode::Sextant => render_video_blocks(wrap, 3, sextant_glyph),
+        ColorMode::Octant => render_video_blocks(wrap, 4, octant_glyph),
     }
 }
 
@@ -86,6 +86,10 @@ pub struct Statistics {
     pub escape_string_encode_time: MaxSizedQueue<Duration, 60>,
     pub output_time: MaxSizedQueue<Duration, 60>,
     pub output_bytes: MaxSizedQueue<usize, 60>,
+    /// 编码器与逐个单元格输出相比节省的字节数
+    pub encoder_saved_bytes: MaxSizedQueue<usize, 60>,
+    /// 变化很小而没有重新输出的单元格数
+    pub kept_cells: MaxSizedQueue<usize, 60>,
     pub video_skipped_frames: usize,
     pub total_output_bytes: usize,
 }
@@ -97,6 +101,8 @@ impl Statistics {
             escape_string_encode_time: MaxSizedQueue::new(),
             output_time: MaxSizedQueue::new(),
             output_bytes: MaxSizedQueue::new(),
+            encoder_saved_bytes: MaxSizedQueue::new(),
+            kept_cells: MaxSizedQueue::new(),
             video_skipped_frames: 0,
             total_output_bytes: 0,
         }
@@ -129,6 +135,13 @@ pub fn set_output_bytes(id: i32, num: usize) {
     get(id).lock().output_bytes.push_back(num);
 }
 
+pub fn set_encoder_savings(id: i32, saved_bytes: usize, kept_cells: usize) {
+    let statistics = get(id);
+    let mut statistics = statistics.lock();
+    statistics.encoder_saved_bytes.push_back(saved_bytes);
+    statistics.kept_cells.push_back(kept_cells);
+}
+
 pub fn increment_video_skipped_frames(id: i32, num: usize) {
     get(id).lock().video_skipped_frames += num;
 }
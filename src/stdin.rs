@@ -174,6 +174,10 @@ pub enum Key {
     UnitSeparator,
 
     ShiftTab,
+    ShiftUp,
+    ShiftDown,
+    ShiftLeft,
+    ShiftRight,
 }
 
 impl Key {
@@ -250,6 +254,10 @@ impl Key {
                     Key::UnitSeparator => 17,
 
                     Key::ShiftTab => 18,
+                    Key::ShiftUp => 19,
+                    Key::ShiftDown => 20,
+                    Key::ShiftLeft => 21,
+                    Key::ShiftRight => 22,
                 }
             }
         }
@@ -518,19 +526,27 @@ pub fn call_mouse_callbacks(id: i32, m: Mouse) {
 // @ ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== @
 // @ 输入处理 @
 
-async fn input_parsenum(getc: &mut Getc, mut c: u8, end: u8) -> Result<i64> {
-    let mut num = 0i64;
-    while c != end {
-        if c < b'0' || c > b'9' {
-            return Err(anyhow::anyhow!(
-                "{}",
-                f16n!("Invalid number: {}", c as char)
-            ));
+/// 读取 CSI 序列的参数，返回以 `;` 分隔的数字和结束字符
+/// - 例如 `1;2A` 返回 `([1, 2], b'A')`
+async fn input_parseparams(getc: &mut Getc, mut c: u8) -> Result<(Vec<i64>, u8)> {
+    let mut params = vec![0i64];
+    loop {
+        match c {
+            b'0'..=b'9' => {
+                let last = params.last_mut().unwrap();
+                *last = *last * 10 + (c - b'0') as i64;
+            }
+            b';' => params.push(0),
+            0x40..=0x7e => return Ok((params, c)),
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "{}",
+                    f16n!("Invalid number: {}", c as char)
+                ));
+            }
         }
-        num = num * 10 + (c - b'0') as i64;
         c = getc.wait().await?;
     }
-    Ok(num)
 }
 
 async fn input_escape_square_number(getc: &mut Getc, num: i64) -> Result<()> {
@@ -725,13 +741,35 @@ async fn input_escape_square(getc: &mut Getc) -> Result<()> {
         b'H' => call_keypress_callbacks(getc.id, Key::Home),
         b'F' => call_keypress_callbacks(getc.id, Key::End),
         b'Z' => call_keypress_callbacks(getc.id, Key::ShiftTab),
-        c if b'0' <= c && c <= b'9' => {
-            if let Ok(num) = input_parsenum(getc, c, b'~').await {
-                input_escape_square_number(getc, num).await?;
-            } else {
+        c if b'0' <= c && c <= b'9' => match input_parseparams(getc, c).await {
+            Ok((params, b'~')) => input_escape_square_number(getc, params[0]).await?,
+            // 带修饰键的方向键：ESC [ 1 ; <modifier> A，modifier 为 2 时表示 Shift
+            Ok((params, end @ (b'A' | b'B' | b'C' | b'D' | b'H' | b'F'))) => {
+                let shift = params.get(1) == Some(&2);
+                let key = match (end, shift) {
+                    (b'A', true) => Key::ShiftUp,
+                    (b'B', true) => Key::ShiftDown,
+                    (b'C', true) => Key::ShiftRight,
+                    (b'D', true) => Key::ShiftLeft,
+                    (b'A', false) => Key::Up,
+                    (b'B', false) => Key::Down,
+                    (b'C', false) => Key::Right,
+                    (b'D', false) => Key::Left,
+                    (b'H', _) => Key::Home,
+                    _ => Key::End,
+                };
+                call_keypress_callbacks(getc.id, key);
+            }
+            Ok((params, end)) => {
+                error!(
+                    "Unknown escape sequence: ESC [ {:?} {}",
+                    params, end as char
+                );
+            }
+            Err(_) => {
                 error!("Invalid escape sequence: ESC [ <number> ~ (number parsing failed)");
             }
-        }
+        },
         b'<' => input_escape_square_angle(getc).await?,
         b'M' => input_escape_square_M(getc).await?,
         c => {
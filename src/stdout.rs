@@ -97,7 +97,7 @@ pub async fn output_main() {
         let buf = {
             let mut option_buf = None;
             while TERM_QUIT.load(Ordering::SeqCst) == false {
-                if let Some(buf) = STDOUT_BUF.lock().pop_front() {
+                if let Some(buf) = take_pending() {
                     option_buf.replace(buf);
                     break;
                 }
@@ -138,6 +138,38 @@ pub async fn output_main() {
     }
 }
 
+/// 开始和结束同步更新，终端在结束前不刷新画面
+const SYNC_BEGIN: &[u8] = b"\x1b[?2026h";
+const SYNC_END: &[u8] = b"\x1b[?2026l";
+
+/// 取出所有积压的帧，合并为一次输出
+/// - 终端支持同步更新时整批包裹在一次同步更新中，不会显示只写了一部分的帧
+fn take_pending() -> Option<Vec<u8>> {
+    let mut lock = STDOUT_BUF.lock();
+    let first = lock.pop_front()?;
+    let sync = crate::termcap::sync_output();
+    if lock.is_empty() && (!sync || first.is_empty()) {
+        return Some(first);
+    }
+    let len = lock.iter().map(Vec::len).sum::<usize>() + first.len();
+    let mut buf = Vec::with_capacity(len + SYNC_BEGIN.len() + SYNC_END.len());
+    if len == 0 {
+        lock.clear();
+        return Some(buf);
+    }
+    if sync {
+        buf.extend_from_slice(SYNC_BEGIN);
+    }
+    buf.extend_from_slice(&first);
+    for frame in lock.drain(..) {
+        buf.extend_from_slice(&frame);
+    }
+    if sync {
+        buf.extend_from_slice(SYNC_END);
+    }
+    Some(buf)
+}
+
 pub fn notify_quit() {
     STDOUT_SIG.notify_one();
 }
@@ -94,6 +94,23 @@ pub fn parse_ass_line(start: Duration, end: Duration, line: &str) -> Option<AssD
 
 static SUBTITLES: Mutex<VecDeque<Option<AssDialogue>>> = Mutex::new(VecDeque::new());
 
+/// 字幕延迟（秒），正数表示字幕推后显示
+static SUBTITLE_DELAY: Mutex<f64> = Mutex::new(0.0);
+/// 字幕颜色，`None` 表示自动选择与背景对比度最高的颜色
+static SUBTITLE_COLOR: Mutex<Option<Color>> = Mutex::new(None);
+
+pub fn get_delay() -> f64 {
+    *SUBTITLE_DELAY.lock()
+}
+
+pub fn set_delay(delay: f64) {
+    *SUBTITLE_DELAY.lock() = delay;
+}
+
+pub fn set_color(color: Option<Color>) {
+    *SUBTITLE_COLOR.lock() = color;
+}
+
 const SUBTITLE_EXTRA_DISPLAY_TIME: Duration = Duration::from_millis(500);
 
 pub fn clear() {
@@ -292,7 +309,10 @@ fn hex_to_color(s: &str) -> Result<Color, ParseIntError> {
 }
 
 pub fn render_subtitle(wrap: &mut ContextWrapper) {
+    let color = *SUBTITLE_COLOR.lock();
+    let delay = get_delay();
     if let Some(played_time) = wrap.played_time {
+        let played_time = Duration::from_secs_f64((played_time.as_secs_f64() - delay).max(0.0));
         let subtitles = get_subtitles(played_time);
         let mut y = wrap.cells_height - 1 - wrap.padding_bottom;
         for sub in subtitles {
@@ -331,7 +351,7 @@ pub fn render_subtitle(wrap: &mut ContextWrapper) {
                 }
                 let p = (y - (k_out * 5.0) as usize) * wrap.cells_pitch + x;
                 let bg = Color::halfhalf(wrap.cells[p].fg, wrap.cells[p].bg);
-                let base_fg = best_contrast_color(bg);
+                let base_fg = color.unwrap_or_else(|| best_contrast_color(bg));
                 // 如果 span 提供了颜色，优先使用该颜色再与背景按 k 混合
                 let fg = if let Some(col) = span_color {
                     Color::mix(col, bg, k)
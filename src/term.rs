@@ -111,7 +111,9 @@ pub static TERM_QUIT: AtomicBool = AtomicBool::new(false);
 /// - 2004: 启用[括号粘贴](https://en.wikipedia.org/wiki/Bracketed-paste)模式
 pub const TERM_INIT_SEQ: &[u8] = b"\x1b[?1049h\x1b[?25l\x1b[?1006h\x1b[?1003h\x1b[?2004h";
 /// 关闭初始化时开启的特性，见 [`TERM_INIT_SEQ`]
-pub const TERM_EXIT_SEQ: &[u8] = b"\x1b[?2004l\x1b[?1003l\x1b[?1006l\x1b[?25h\x1b[?1049l";
+/// - 先结束可能没有写完的同步更新（2026）
+pub const TERM_EXIT_SEQ: &[u8] =
+    b"\x1b[?2026l\x1b[?2004l\x1b[?1003l\x1b[?1006l\x1b[?25h\x1b[?1049l";
 
 pub extern "C" fn request_quit() {
     TERM_QUIT.store(true, Ordering::SeqCst);
@@ -0,0 +1,582 @@
+use parking_lot::Mutex;
+use std::env;
+#[cfg(unix)]
+use std::time::{Duration, Instant};
+
+use crate::util::ColorMode;
+
+// @ ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== @
+
+/// 终端复用器
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Multiplexer {
+    Tmux,
+    Screen,
+}
+
+/// 启动时探测到的终端能力
+#[derive(Debug, Clone)]
+pub struct Caps {
+    /// XTVERSION 回复的终端名称和版本
+    pub version: String,
+    /// DA2 回复的终端类型编号
+    pub da2_id: Option<u16>,
+    /// 是否支持 24 位真彩色，`None` 表示无法判断
+    pub truecolor: Option<bool>,
+    /// 调色板颜色数，`Some(0)` 表示不支持颜色
+    pub colors: Option<u32>,
+    /// `colors` 来自 XTGETTCAP 的回复，而不是按 `TERM` 猜测
+    pub colors_queried: bool,
+    /// DA1 回复中包含 Sixel
+    pub sixel: bool,
+    /// 回复了 kitty 图像协议的查询
+    pub kitty_graphics: bool,
+    /// 支持 iTerm2 的 OSC 1337 图像
+    pub iterm_images: bool,
+    /// 单元格的像素大小 (宽, 高)
+    pub cell_size: Option<(u16, u16)>,
+    pub multiplexer: Option<Multiplexer>,
+    /// 支持 REP（`CSI n b`），重复输出上一个字符
+    pub rep: bool,
+    /// 支持同步更新（DEC 模式 2026）
+    pub sync_output: bool,
+    /// 终端复用器允许 DCS passthrough，图像协议可以发送到外层终端
+    pub passthrough: bool,
+    /// 通过 SSH 连接
+    pub ssh: bool,
+}
+
+impl Caps {
+    const fn new() -> Self {
+        Self {
+            version: String::new(),
+            da2_id: None,
+            truecolor: None,
+            colors: None,
+            colors_queried: false,
+            sixel: false,
+            kitty_graphics: false,
+            iterm_images: false,
+            cell_size: None,
+            multiplexer: None,
+            rep: false,
+            sync_output: false,
+            passthrough: false,
+            ssh: false,
+        }
+    }
+
+    /// 按探测结果选择最好的颜色模式，没有任何线索时沿用真彩色
+    pub fn color_mode(&self) -> ColorMode {
+        #[cfg(feature = "kitty")]
+        if self.kitty_graphics && self.graphics_usable(ColorMode::Kitty) {
+            return ColorMode::Kitty;
+        }
+        #[cfg(feature = "osc1337")]
+        if self.iterm_images && self.graphics_usable(ColorMode::OSC1337) {
+            return ColorMode::OSC1337;
+        }
+        #[cfg(feature = "sixel")]
+        if self.sixel && self.graphics_usable(ColorMode::Sixel) {
+            return ColorMode::Sixel;
+        }
+        self.text_color_mode()
+    }
+
+    /// 不使用图像协议时最好的颜色模式
+    /// - 只有终端明确回复了颜色数时才降到黑白，按 `TERM` 猜测的颜色数不足 256 时仍用 256 色，
+    ///   多数终端（包括 Linux 控制台）会把 256 色近似到自己的调色板
+    pub fn text_color_mode(&self) -> ColorMode {
+        match (self.truecolor, self.colors) {
+            (Some(true), _) => ColorMode::TrueColorOnly,
+            (_, Some(0)) => ColorMode::AsciiMono,
+            (_, Some(n)) if n >= 256 => ColorMode::Palette256Only,
+            (_, Some(_)) if self.colors_queried => ColorMode::BlackWhite,
+            (_, Some(_)) => ColorMode::Palette256Only,
+            (Some(false), None) => ColorMode::Palette256Only,
+            (None, None) => ColorMode::TrueColorOnly,
+        }
+    }
+
+    /// 图像协议的 fppc 为 (0, 0)，在终端复用器内需要 passthrough 才能显示
+    fn graphics_usable(&self, mode: ColorMode) -> bool {
+        if mode.fppc() != (0, 0) || self.multiplexer.is_none() || self.passthrough {
+            return true;
+        }
+        self.native_graphics(mode)
+    }
+
+    /// tmux 3.4 起可以自己处理 Sixel，此时 DA1 由 tmux 回复且包含 Sixel
+    #[allow(unused_variables)]
+    fn native_graphics(&self, mode: ColorMode) -> bool {
+        #[cfg(feature = "sixel")]
+        if mode == ColorMode::Sixel && self.sixel {
+            return true;
+        }
+        false
+    }
+
+    /// 用于日志的简短描述
+    pub fn summary(&self) -> String {
+        let mut parts = Vec::new();
+        if !self.version.is_empty() {
+            parts.push(self.version.clone());
+        } else if let Some(id) = self.da2_id {
+            parts.push(format!("DA2={id}"));
+        }
+        match (self.truecolor, self.colors) {
+            (Some(true), _) => parts.push("truecolor".to_string()),
+            (_, Some(n)) => parts.push(format!("{n} colors")),
+            _ => {}
+        }
+        if self.sixel {
+            parts.push("sixel".to_string());
+        }
+        if self.kitty_graphics {
+            parts.push("kitty graphics".to_string());
+        }
+        if self.iterm_images {
+            parts.push("iTerm2 images".to_string());
+        }
+        if self.rep {
+            parts.push("REP".to_string());
+        }
+        if self.sync_output {
+            parts.push("sync".to_string());
+        }
+        if let Some((w, h)) = self.cell_size {
+            parts.push(format!("cell {w}x{h}"));
+        }
+        match self.multiplexer {
+            Some(Multiplexer::Tmux) => parts.push("tmux".to_string()),
+            Some(Multiplexer::Screen) => parts.push("screen".to_string()),
+            None => {}
+        }
+        if self.multiplexer.is_some() && self.passthrough {
+            parts.push("passthrough".to_string());
+        }
+        if self.ssh {
+            parts.push("ssh".to_string());
+        }
+        parts.join(", ")
+    }
+}
+
+static CAPS: Mutex<Caps> = Mutex::new(Caps::new());
+
+/// 配置中没有指定颜色模式时使用的模式
+pub fn color_mode() -> ColorMode {
+    CAPS.lock().color_mode()
+}
+
+/// 不使用图像协议时的颜色模式
+pub fn text_color_mode() -> ColorMode {
+    CAPS.lock().text_color_mode()
+}
+
+/// 颜色模式能否在当前终端中显示
+pub fn graphics_usable(mode: ColorMode) -> bool {
+    CAPS.lock().graphics_usable(mode)
+}
+
+/// kitty 图像在终端复用器内使用 Unicode 占位符显示
+#[cfg(feature = "kitty")]
+pub fn kitty_placeholders() -> bool {
+    CAPS.lock().multiplexer.is_some()
+}
+
+/// screen 转发的 DCS 长度有限，需要分段
+#[cfg(any(feature = "sixel", feature = "osc1337", feature = "kitty"))]
+const SCREEN_CHUNK_SIZE: usize = 512;
+
+/// 写入图像协议的转义序列，在终端复用器内时包装为 DCS passthrough
+/// - tmux：`ESC P tmux; … ESC \`，内容中的 ESC 需要重复一次
+/// - screen：分段包装为 `ESC P … ESC \`，内容中的 ST 拆到两段里
+#[cfg(any(feature = "sixel", feature = "osc1337", feature = "kitty"))]
+pub fn write_graphics(buf: &mut Vec<u8>, seq: &[u8], mode: ColorMode) {
+    let multiplexer = {
+        let caps = CAPS.lock();
+        caps.multiplexer.filter(|_| !caps.native_graphics(mode))
+    };
+    match multiplexer {
+        None => buf.extend_from_slice(seq),
+        Some(Multiplexer::Tmux) => {
+            buf.extend_from_slice(b"\x1bPtmux;");
+            for &b in seq {
+                if b == 0x1b {
+                    buf.push(0x1b);
+                }
+                buf.push(b);
+            }
+            buf.extend_from_slice(b"\x1b\\");
+        }
+        Some(Multiplexer::Screen) => {
+            let mut start = 0;
+            while start < seq.len() {
+                let mut end = (start + SCREEN_CHUNK_SIZE).min(seq.len());
+                if let Some(p) = seq[start..end].windows(2).position(|w| w == b"\x1b\\") {
+                    end = start + p + 1;
+                }
+                buf.extend_from_slice(b"\x1bP");
+                buf.extend_from_slice(&seq[start..end]);
+                buf.extend_from_slice(b"\x1b\\");
+                start = end;
+            }
+        }
+    }
+}
+
+/// 能否用 REP 重复输出字符
+pub fn rep() -> bool {
+    CAPS.lock().rep
+}
+
+/// 能否用同步更新包裹每一帧
+pub fn sync_output() -> bool {
+    CAPS.lock().sync_output
+}
+
+/// 探测到的单元格像素大小，`TIOCGWINSZ` 没有给出像素大小时使用
+pub fn cell_size() -> Option<(usize, usize)> {
+    CAPS.lock().cell_size.map(|(w, h)| (w as usize, h as usize))
+}
+
+// @ ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== @
+
+/// 在 `term::init` 之后、启动 stdin 线程之前调用
+/// - 先按环境变量猜测，再向终端发送查询并读取回复
+/// - 所有终端都会回复 DA1，所以把它放在最后，收到后即可结束等待
+pub fn probe() {
+    let mut caps = Caps::new();
+    detect_env(&mut caps);
+    #[cfg(unix)]
+    query(&mut caps);
+    if caps.multiplexer == Some(Multiplexer::Tmux) {
+        detect_tmux(&mut caps);
+    }
+    if caps.truecolor.is_none() {
+        caps.truecolor = known_truecolor(&caps).then_some(true);
+    }
+    caps.rep |= known_rep(&caps);
+    debug_f16n!("Terminal capabilities: {}", caps.summary());
+    *CAPS.lock() = caps;
+}
+
+fn detect_env(caps: &mut Caps) {
+    let var = |name: &str| env::var(name).unwrap_or_default();
+    let term = var("TERM").to_lowercase();
+    let program = var("TERM_PROGRAM");
+
+    if env::var_os("TMUX").is_some() || term.starts_with("tmux") {
+        caps.multiplexer = Some(Multiplexer::Tmux);
+    } else if env::var_os("STY").is_some() || term.starts_with("screen") {
+        caps.multiplexer = Some(Multiplexer::Screen);
+    }
+    caps.ssh = env::var_os("SSH_CONNECTION").is_some() || env::var_os("SSH_TTY").is_some();
+
+    match var("COLORTERM").to_lowercase().as_str() {
+        "truecolor" | "24bit" => caps.truecolor = Some(true),
+        _ if term.ends_with("-direct") => caps.truecolor = Some(true),
+        _ if program == "Apple_Terminal" => caps.truecolor = Some(false),
+        _ => {}
+    }
+    if term.contains("256color") {
+        caps.colors = Some(256);
+    } else if term == "dumb" {
+        caps.colors = Some(0);
+    } else if ["linux", "vt100", "vt220", "ansi"].contains(&term.as_str()) {
+        caps.colors = Some(16);
+    }
+
+    if matches!(program.as_str(), "iTerm.app" | "WezTerm") || var("LC_TERMINAL") == "iTerm2" {
+        caps.iterm_images = true;
+    }
+    if env::var_os("KITTY_WINDOW_ID").is_some() || term == "xterm-kitty" {
+        caps.kitty_graphics = true;
+    }
+    // screen 总是转发 passthrough 的内容
+    caps.passthrough = caps.multiplexer == Some(Multiplexer::Screen);
+}
+
+fn tmux(args: &[&str]) -> Option<String> {
+    let output = std::process::Command::new("tmux")
+        .args(args)
+        .stdin(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// 在 tmux 内时，DA、XTVERSION 和 kitty 图像查询由 tmux 自己处理，
+/// 外层终端的信息从 `client_termfeatures`（tmux 3.2 起）、`client_termname`
+/// 和 `client_termtype`（外层终端的 XTVERSION，tmux 3.3 起）读取
+fn detect_tmux(caps: &mut Caps) {
+    // tmux 3.3 起默认不转发 passthrough，之前的版本没有这个选项
+    caps.passthrough = ["-pqv", "-wqv", "-gwqv"]
+        .into_iter()
+        .find_map(|flags| {
+            tmux(&["show-options", flags, "allow-passthrough"]).filter(|v| !v.is_empty())
+        })
+        .is_none_or(|v| v != "off");
+
+    let Some(output) = tmux(&[
+        "display-message",
+        "-p",
+        "#{client_termfeatures}\t#{client_termname}\t#{client_termtype}",
+    ]) else {
+        return;
+    };
+    let mut fields = output.split('\t');
+    let features: Vec<&str> = fields.next().unwrap_or_default().split(',').collect();
+    let name = fields.next().unwrap_or_default().to_lowercase();
+    let kind = fields.next().unwrap_or_default().to_lowercase();
+    if name.contains("kitty") || kind.starts_with("kitty") || kind.starts_with("ghostty") {
+        caps.kitty_graphics = true;
+    }
+    if kind.starts_with("iterm2") || kind.starts_with("wezterm") {
+        caps.iterm_images = true;
+    }
+    if features.contains(&"sync") {
+        caps.sync_output = true;
+    }
+    if features.contains(&"RGB") {
+        caps.truecolor = Some(true);
+    } else if features.contains(&"256") {
+        caps.truecolor.get_or_insert(false);
+        caps.colors = Some(256);
+    }
+}
+
+/// 已知支持真彩色的终端
+fn known_truecolor(caps: &Caps) -> bool {
+    const NAMES: &[&str] = &[
+        "kitty",
+        "wezterm",
+        "foot",
+        "xterm(",
+        "iterm2",
+        "ghostty",
+        "alacritty",
+        "contour",
+        "mintty",
+        "konsole",
+        "rio",
+        "windowsterminal",
+    ];
+    let version = caps.version.to_lowercase();
+    if NAMES.iter().any(|name| version.starts_with(name)) {
+        return true;
+    }
+    // 65 是 VTE（GNOME Terminal 等）
+    if caps.da2_id == Some(65) {
+        return true;
+    }
+    env::var_os("WT_SESSION").is_some()
+        || matches!(
+            env::var("TERM_PROGRAM").unwrap_or_default().as_str(),
+            "iTerm.app" | "WezTerm" | "vscode" | "ghostty"
+        )
+}
+
+/// 已知支持 REP 的终端，在终端复用器内由复用器自己处理 REP
+/// - 不支持的终端会忽略 REP，画面缺字，所以只在能确认时使用
+fn known_rep(caps: &Caps) -> bool {
+    const NAMES: &[&str] = &[
+        "xterm(", "kitty", "wezterm", "foot", "ghostty", "contour", "mintty", "tmux",
+    ];
+    match caps.multiplexer {
+        Some(Multiplexer::Screen) => return false,
+        Some(Multiplexer::Tmux) => return caps.version.to_lowercase().starts_with("tmux"),
+        None => {}
+    }
+    let version = caps.version.to_lowercase();
+    // 65 是 VTE（GNOME Terminal 等）
+    NAMES.iter().any(|name| version.starts_with(name)) || caps.da2_id == Some(65)
+}
+
+// @ ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== @
+
+/// 依次为：XTVERSION、DA2、XTGETTCAP（RGB、Tc、colors、rep）、kitty 图像查询、
+/// 文本区域像素大小、单元格像素大小、同步更新模式（DECRQM 2026）、DA1
+#[cfg(unix)]
+const QUERIES: &[u8] = b"\x1b[>0q\x1b[>c\x1bP+q524742;5463;636f6c6f7273;726570\x1b\\\
+\x1b_Gi=31,s=1,v=1,a=q,t=d,f=24;AAAA\x1b\\\x1b[14t\x1b[16t\x1b[?2026$p\x1b[c";
+
+/// 通过 SSH 时回复可能较慢，但收到 DA1 就会提前结束
+#[cfg(unix)]
+const QUERY_TIMEOUT: Duration = Duration::from_millis(1000);
+
+#[cfg(unix)]
+fn query(caps: &mut Caps) {
+    use libc::{STDIN_FILENO, STDOUT_FILENO};
+
+    if unsafe { libc::isatty(STDIN_FILENO) == 0 || libc::isatty(STDOUT_FILENO) == 0 } {
+        return;
+    }
+    if !crate::stdout::print_all_sync(QUERIES) {
+        return;
+    }
+
+    // `term::init` 设置了 VMIN = 0、VTIME = 1，没有输入时 read 最多等待 100ms
+    let deadline = Instant::now() + QUERY_TIMEOUT;
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 256];
+    let mut replies = Replies::default();
+    while Instant::now() < deadline && !replies.da1 {
+        let n = unsafe { libc::read(STDIN_FILENO, chunk.as_mut_ptr() as *mut _, chunk.len()) };
+        if n < 0 {
+            break;
+        }
+        buf.extend_from_slice(&chunk[..n as usize]);
+        replies = parse_replies(&buf);
+    }
+    if !replies.da1 {
+        debug_l10n!("Terminal did not answer the capability queries");
+    }
+
+    if let Some(version) = replies.version {
+        caps.version = version;
+    }
+    caps.da2_id = replies.da2_id;
+    caps.sixel = replies.sixel;
+    caps.kitty_graphics |= replies.kitty_graphics;
+    if caps.version.starts_with("iTerm2") || caps.version.starts_with("WezTerm") {
+        caps.iterm_images = true;
+    }
+    if replies.rgb {
+        caps.truecolor = Some(true);
+    }
+    // 在终端复用器内，XTGETTCAP 由复用器回复
+    caps.rep = replies.rep;
+    caps.sync_output |= replies.sync_output;
+    if let Some(colors) = replies.colors {
+        caps.colors = Some(colors);
+        caps.colors_queried = true;
+    }
+    caps.cell_size = replies.cell_size.or_else(|| {
+        // 只回复了文本区域大小时，按字符数换算
+        let (w, h) = replies.text_area?;
+        let winsize = crate::term::get_winsize()?;
+        (winsize.col > 0 && winsize.row > 0).then(|| (w / winsize.col, h / winsize.row))
+    });
+    if caps.cell_size.is_some_and(|(w, h)| w == 0 || h == 0) {
+        caps.cell_size = None;
+    }
+}
+
+/// 从终端回复中解析出的内容
+#[cfg(unix)]
+#[derive(Debug, Default)]
+struct Replies {
+    da1: bool,
+    sixel: bool,
+    da2_id: Option<u16>,
+    version: Option<String>,
+    rgb: bool,
+    rep: bool,
+    sync_output: bool,
+    colors: Option<u32>,
+    kitty_graphics: bool,
+    text_area: Option<(u16, u16)>,
+    cell_size: Option<(u16, u16)>,
+}
+
+#[cfg(unix)]
+fn parse_params(params: &[u8]) -> Vec<u16> {
+    String::from_utf8_lossy(params)
+        .split(';')
+        .map(|p| p.parse().unwrap_or(0))
+        .collect()
+}
+
+/// 解码 XTGETTCAP 回复中的十六进制字符串
+#[cfg(unix)]
+fn unhex(hex: &str) -> Option<String> {
+    let bytes = (0..hex.len() / 2)
+        .map(|i| u8::from_str_radix(hex.get(i * 2..i * 2 + 2)?, 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    String::from_utf8(bytes).ok()
+}
+
+/// 回复中夹杂的其他输入（比如按键）会被忽略
+#[cfg(unix)]
+fn parse_replies(buf: &[u8]) -> Replies {
+    let mut replies = Replies::default();
+    let mut i = 0;
+    while i + 1 < buf.len() {
+        if buf[i] != 0x1b {
+            i += 1;
+            continue;
+        }
+        match buf[i + 1] {
+            b'[' => {
+                let start = i + 2;
+                let Some(len) = buf[start..].iter().position(|b| (0x40..=0x7e).contains(b)) else {
+                    break;
+                };
+                // DECRQM 的回复带有中间字节 `$`
+                let body = &buf[start..start + len];
+                let body = body.strip_suffix(b"$").unwrap_or(body);
+                let (prefix, params) = match body.first() {
+                    Some(&p @ (b'?' | b'>')) => (Some(p), parse_params(&body[1..])),
+                    _ => (None, parse_params(body)),
+                };
+                match (prefix, buf[start + len]) {
+                    (Some(b'?'), b'c') => {
+                        replies.da1 = true;
+                        replies.sixel = params.iter().skip(1).any(|&p| p == 4);
+                    }
+                    (Some(b'>'), b'c') => replies.da2_id = params.first().copied(),
+                    // 1 和 2 表示支持该模式（当前为开或关）
+                    (Some(b'?'), b'y') if params.len() >= 2 && params[0] == 2026 => {
+                        replies.sync_output = matches!(params[1], 1 | 2);
+                    }
+                    (None, b't') if params.len() >= 3 => match params[0] {
+                        4 => replies.text_area = Some((params[2], params[1])),
+                        6 => replies.cell_size = Some((params[2], params[1])),
+                        _ => {}
+                    },
+                    _ => {}
+                }
+                i = start + len + 1;
+            }
+            kind @ (b'P' | b'_') => {
+                // DCS 和 APC 以 ST（ESC \）或 BEL 结束
+                let start = i + 2;
+                let Some(len) = buf[start..]
+                    .windows(2)
+                    .position(|w| w == b"\x1b\\" || w[0] == 0x07)
+                else {
+                    break;
+                };
+                let body = String::from_utf8_lossy(&buf[start..start + len]);
+                if kind == b'_' {
+                    if body.starts_with("Gi=31") && body.ends_with(";OK") {
+                        replies.kitty_graphics = true;
+                    }
+                } else if let Some(version) = body.strip_prefix(">|") {
+                    replies.version = Some(version.to_string());
+                } else if let Some(caps) = body.strip_prefix("1+r") {
+                    for cap in caps.split(';') {
+                        let (name, value) = cap.split_once('=').unwrap_or((cap, ""));
+                        match unhex(name).as_deref() {
+                            Some("RGB" | "Tc") => replies.rgb = true,
+                            Some("rep") => replies.rep = true,
+                            Some("colors") => {
+                                replies.colors = unhex(value).and_then(|v| v.parse().ok());
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                i = start + len + 1;
+            }
+            _ => i += 1,
+        }
+    }
+    replies
+}
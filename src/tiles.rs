@@ -0,0 +1,567 @@
+use av::codec::context::Context as AVCCtx;
+use av::decoder::Video as VideoDecoder;
+use av::ffi::{AV_TIME_BASE, AVSEEK_FLAG_BACKWARD, av_read_frame, av_seek_frame};
+use av::format::context::Input;
+use av::packet::Mut as _;
+use av::software::scaling::context::Context as Scaler;
+use av::util::frame::video::Video as VideoFrame;
+use av::{Packet, Rational};
+use parking_lot::{Mutex, MutexGuard};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::avsync;
+use crate::ffmpeg;
+use crate::render;
+use crate::term::TERM_QUIT;
+use crate::util::Color;
+use crate::video;
+use crate::view;
+
+// @ ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== @
+
+/// 分屏布局
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layout {
+    /// 其他画面缩小后叠在主画面右下角
+    Pip,
+    /// 所有画面横向并排
+    SideBySide,
+    /// 所有画面排成接近正方形的网格
+    Grid,
+}
+
+impl Layout {
+    pub const ALL: &[Layout] = &[Layout::Pip, Layout::SideBySide, Layout::Grid];
+
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Layout::Pip => "pip",
+            Layout::SideBySide => "side",
+            Layout::Grid => "grid",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.trim().to_lowercase();
+        Self::ALL.iter().copied().find(|m| m.name() == name)
+    }
+
+    /// 共 `count` 个画面时的 (列数, 行数)，画中画不分格
+    fn grid(&self, count: usize) -> (usize, usize) {
+        match self {
+            Layout::Pip => (1, 1),
+            Layout::SideBySide => (count, 1),
+            Layout::Grid => {
+                let cols = (count as f32).sqrt().ceil() as usize;
+                (cols, count.div_ceil(cols))
+            }
+        }
+    }
+}
+
+/// 画中画窗口占主画面的比例
+const PIP_SCALE: usize = 3;
+/// 分屏画面的时间与目标时间相差超过此值时重新定位，而不是逐帧追赶
+const SEEK_SLACK: Duration = Duration::from_secs(2);
+/// 等待下一帧时的最长休眠，保证跳转、暂停和关闭及时响应
+const MAX_WAIT: Duration = Duration::from_millis(20);
+/// 解码跟不上时，追赶这么久仍没有追上也先显示一帧
+const MAX_CATCH_UP: Duration = Duration::from_millis(100);
+
+static LAYOUT: Mutex<Layout> = Mutex::new(Layout::SideBySide);
+static TILES: Mutex<Vec<Arc<Tile>>> = Mutex::new(Vec::new());
+/// 主画面正在播放的输入
+static MAIN_INPUT: Mutex<Option<String>> = Mutex::new(None);
+/// 切换音频来源时，下一个要打开的输入和开始时间
+static SWAP: Mutex<Option<(String, Duration)>> = Mutex::new(None);
+/// 主画面最后输出的视频帧的时间，分屏画面按它对齐，使两边显示同一时间的帧
+/// - 主画面没有视频时为 `None`，按播放时间对齐
+static FRAME_TIME: Mutex<Option<Duration>> = Mutex::new(None);
+
+pub fn hint_frame_time(time: Option<Duration>) {
+    *FRAME_TIME.lock() = time;
+}
+
+/// 缩放好的分屏画面
+pub struct Image {
+    pub data: Vec<Color>,
+    pub width: usize,
+    pub height: usize,
+    /// 帧的时间
+    pub time: Duration,
+}
+
+/// 分屏中的一个画面，有自己的解码线程和时钟
+/// - 时钟跟随主画面的视频帧时间，加上 `offset`
+/// - 只解码视频，音频来自主画面
+pub struct Tile {
+    path: String,
+    /// 画面区域的大小（像素），由合成时设置
+    area: Mutex<(usize, usize)>,
+    /// 为真时与主画面使用相同的视图（缩放、平移）并拉伸到整个区域，用于逐像素对比
+    /// - 否则保持宽高比，显示整个画面
+    mirror_view: bool,
+    image: Mutex<Option<Image>>,
+    /// 相对主画面的时间偏移（秒）
+    offset: Mutex<f64>,
+    quit: AtomicBool,
+}
+
+impl Tile {
+    pub fn spawn(path: &str, offset: f64, mirror_view: bool) -> Arc<Self> {
+        let tile = Arc::new(Self {
+            path: path.to_string(),
+            area: Mutex::new((0, 0)),
+            mirror_view,
+            image: Mutex::new(None),
+            offset: Mutex::new(offset),
+            quit: AtomicBool::new(false),
+        });
+        let cloned = tile.clone();
+        std::thread::spawn(move || decode_tile(cloned));
+        tile
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// 应该显示的帧的时间
+    pub fn target_time(&self) -> Duration {
+        let main = FRAME_TIME
+            .lock()
+            .unwrap_or_else(avsync::played_time_or_zero);
+        let time = main.as_secs_f64() + *self.offset.lock();
+        Duration::from_secs_f64(time.max(0.0))
+    }
+
+    pub fn offset(&self) -> f64 {
+        *self.offset.lock()
+    }
+
+    pub fn set_offset(&self, offset: f64) {
+        *self.offset.lock() = offset;
+    }
+
+    pub fn set_area(&self, area: (usize, usize)) {
+        *self.area.lock() = area;
+    }
+
+    /// 最后缩放好的画面，大小可能还是之前的区域大小
+    pub fn image(&self) -> MutexGuard<'_, Option<Image>> {
+        self.image.lock()
+    }
+
+    pub fn stop(&self) {
+        self.quit.store(true, Ordering::SeqCst);
+    }
+}
+
+fn changed() {
+    render::RENDER_CONTEXT.lock().force_flush_next();
+    render::api_request_frame();
+}
+
+pub fn set_layout(layout: Layout) {
+    *LAYOUT.lock() = layout;
+    changed();
+}
+
+pub fn cycle_layout() {
+    let layout = {
+        let mut lock = LAYOUT.lock();
+        let index = Layout::ALL.iter().position(|&l| l == *lock).unwrap_or(0);
+        *lock = Layout::ALL[(index + 1) % Layout::ALL.len()];
+        *lock
+    };
+    changed();
+    info_f16n!("Tile layout: {}", layout.name());
+}
+
+pub fn add(path: &str) {
+    TILES.lock().push(Tile::spawn(path, 0.0, false));
+    changed();
+}
+
+/// 关闭第 `index` 个分屏画面（从 1 开始），返回是否存在
+pub fn remove(index: usize) -> bool {
+    let mut tiles = TILES.lock();
+    if index == 0 || index > tiles.len() {
+        return false;
+    }
+    tiles.remove(index - 1).stop();
+    drop(tiles);
+    changed();
+    true
+}
+
+pub fn clear() {
+    for tile in TILES.lock().drain(..) {
+        tile.stop();
+    }
+    changed();
+}
+
+/// 设置第 `index` 个分屏画面的时间偏移，返回是否存在
+pub fn set_offset(index: usize, offset: f64) -> bool {
+    let tiles = TILES.lock();
+    let Some(tile) = index.checked_sub(1).and_then(|i| tiles.get(i)) else {
+        return false;
+    };
+    tile.set_offset(offset);
+    true
+}
+
+pub fn set_main_input(path: &str) {
+    MAIN_INPUT.lock().replace(path.to_string());
+}
+
+/// 改为播放第 `index` 个分屏画面的音频，返回是否存在
+/// - 与主画面交换：它改为主画面从当前时间继续播放，原来的主画面变成分屏画面
+pub fn swap_audio(index: usize) -> bool {
+    let Some(main) = MAIN_INPUT.lock().clone() else {
+        return false;
+    };
+    let mut tiles = TILES.lock();
+    let Some(tile) = index.checked_sub(1).and_then(|i| tiles.get_mut(i)) else {
+        return false;
+    };
+    let offset = tile.offset();
+    let time = tile.target_time();
+    let old = std::mem::replace(tile, Tile::spawn(&main, -offset, false));
+    old.stop();
+    drop(tiles);
+    SWAP.lock().replace((old.path.clone(), time));
+    ffmpeg::notify_quit();
+    true
+}
+
+/// 取出交换来的主画面输入和开始时间
+pub fn take_swap() -> Option<(String, Duration)> {
+    SWAP.lock().take()
+}
+
+/// 用于提示信息的分屏状态
+pub fn status() -> String {
+    let tiles = TILES.lock();
+    let mut parts = vec![LAYOUT.lock().name().to_string()];
+    for (i, tile) in tiles.iter().enumerate() {
+        match tile.offset() {
+            0.0 => parts.push(format!("{}={}", i + 1, tile.path)),
+            offset => parts.push(format!("{}={} ({:+.3}s)", i + 1, tile.path, offset)),
+        }
+    }
+    parts.join(" ")
+}
+
+pub fn count() -> usize {
+    TILES.lock().len()
+}
+
+// @ ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== @
+
+/// 主画面的显示大小，分屏时按网格放大，使合成后的画面铺满终端
+/// - 对比模式下不分屏
+pub fn display_size(display: (usize, usize)) -> (usize, usize) {
+    let count = TILES.lock().len();
+    if count == 0 || crate::compare::active() {
+        return display;
+    }
+    let (cols, rows) = LAYOUT.lock().grid(count + 1);
+    (display.0 * cols, display.1 * rows)
+}
+
+/// 把分屏画面合成到主画面上，没有分屏或处于对比模式时返回 `None`
+pub fn compose(frame: &[Color], width: usize, height: usize, pitch: usize) -> Option<Vec<Color>> {
+    let tiles = TILES.lock().clone();
+    if tiles.is_empty() || width == 0 || height == 0 || crate::compare::active() {
+        return None;
+    }
+    let layout = *LAYOUT.lock();
+    let mut out = vec![Color::new(0, 0, 0); width * height];
+
+    if layout == Layout::Pip {
+        for y in 0..height {
+            out[y * width..(y + 1) * width].copy_from_slice(&frame[y * pitch..y * pitch + width]);
+        }
+        let (w, h) = (width / PIP_SCALE, height / PIP_SCALE);
+        let margin = width.min(height) / 32;
+        for (i, tile) in tiles.iter().enumerate() {
+            let bottom = height.saturating_sub(margin + i * (h + margin));
+            if bottom < h {
+                break;
+            }
+            let origin = (width - w - margin, bottom - h);
+            blit(&mut out, width, origin, (w, h), tile);
+        }
+        return Some(out);
+    }
+
+    let (cols, rows) = layout.grid(tiles.len() + 1);
+    let (w, h) = (width / cols, height / rows);
+    shrink(&mut out, width, (w, h), frame, width, height, pitch);
+    for (i, tile) in tiles.iter().enumerate() {
+        let (col, row) = ((i + 1) % cols, (i + 1) / cols);
+        blit(&mut out, width, (col * w, row * h), (w, h), tile);
+    }
+    Some(out)
+}
+
+/// 按区域平均把主画面缩小到左上角的 `size` 大小
+fn shrink(
+    out: &mut [Color],
+    out_pitch: usize,
+    size: (usize, usize),
+    frame: &[Color],
+    width: usize,
+    height: usize,
+    pitch: usize,
+) {
+    // 第 `i` 个输出像素对应的源像素范围，至少一个像素
+    let span = |i: usize, src: usize, dst: usize| {
+        let start = i * src / dst;
+        (start, ((i + 1) * src / dst).max(start + 1))
+    };
+    for y in 0..size.1 {
+        let (y0, y1) = span(y, height, size.1);
+        for x in 0..size.0 {
+            let (x0, x1) = span(x, width, size.0);
+            let mut sum = [0u32; 3];
+            for row in y0..y1 {
+                for c in &frame[row * pitch + x0..row * pitch + x1] {
+                    sum[0] += c.r as u32;
+                    sum[1] += c.g as u32;
+                    sum[2] += c.b as u32;
+                }
+            }
+            let n = ((y1 - y0) * (x1 - x0)) as u32;
+            let [r, g, b] = sum.map(|v| (v / n) as u8);
+            out[y * out_pitch + x] = Color::new(r, g, b);
+        }
+    }
+}
+
+/// 把分屏画面居中画到 `origin` 处 `size` 大小的区域，并告知解码线程区域大小
+fn blit(
+    out: &mut [Color],
+    out_pitch: usize,
+    origin: (usize, usize),
+    size: (usize, usize),
+    tile: &Tile,
+) {
+    tile.set_area(size);
+    let image = tile.image();
+    let Some(image) = image.as_ref() else {
+        return;
+    };
+    let (w, h) = (image.width.min(size.0), image.height.min(size.1));
+    let x = origin.0 + (size.0 - w) / 2;
+    let y = origin.1 + (size.1 - h) / 2;
+    for row in 0..h {
+        let dst = (y + row) * out_pitch + x;
+        out[dst..dst + w].copy_from_slice(&image.data[row * image.width..row * image.width + w]);
+    }
+}
+
+// @ ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== @
+
+fn frame_time(frame: &VideoFrame, time_base: Rational) -> Option<Duration> {
+    let pts = frame.timestamp().or(frame.pts())?;
+    let secs = pts as f64 * time_base.numerator() as f64 / time_base.denominator() as f64;
+    Some(Duration::from_secs_f64(secs.max(0.0)))
+}
+
+/// 解码下一帧，文件结束时返回 `None`
+fn next_frame(ictx: &mut Input, decoder: &mut VideoDecoder, index: usize) -> Option<VideoFrame> {
+    let mut frame = VideoFrame::empty();
+    loop {
+        if decoder.receive_frame(&mut frame).is_ok() {
+            return Some(frame);
+        }
+        let mut packet = Packet::empty();
+        if unsafe { av_read_frame(ictx.as_mut_ptr(), packet.as_mut_ptr()) } < 0 {
+            // 取出解码器里剩下的帧，之后再调用时 `send_eof` 失败，返回 `None`
+            let _ = decoder.send_eof();
+            return decoder.receive_frame(&mut frame).is_ok().then_some(frame);
+        }
+        if packet.stream() == index {
+            if let Err(e) = decoder.send_packet(&packet) {
+                debug_f16n!("tile send_packet err: {:?}", e);
+            }
+        }
+    }
+}
+
+/// 按画面的显示宽高比缩放到区域内，或拉伸到整个区域
+struct TileScaler {
+    scaler: Option<Scaler>,
+    key: Option<(av::format::Pixel, u32, u32, u32, u32)>,
+}
+
+impl TileScaler {
+    fn scale(
+        &mut self,
+        frame: &VideoFrame,
+        time: Duration,
+        area: (usize, usize),
+        fill: bool,
+    ) -> Option<Image> {
+        let (w, h) = (frame.width(), frame.height());
+        if w == 0 || h == 0 || area.0 == 0 || area.1 == 0 {
+            return None;
+        }
+        let sar = frame.aspect_ratio();
+        let sar = if sar.numerator() > 0 && sar.denominator() > 0 {
+            sar.numerator() as f32 / sar.denominator() as f32
+        } else {
+            1.0
+        };
+        let dar = w as f32 * sar / h as f32;
+        let (dw, dh) = if fill {
+            area
+        } else if dar > area.0 as f32 / area.1 as f32 {
+            (area.0, (area.0 as f32 / dar).round() as usize)
+        } else {
+            ((area.1 as f32 * dar).round() as usize, area.1)
+        };
+        let (dw, dh) = (dw.clamp(1, area.0) as u32, dh.clamp(1, area.1) as u32);
+
+        let key = (frame.format(), w, h, dw, dh);
+        if self.key != Some(key) {
+            let flags = video::scale_flags((w, h), (dw, dh));
+            let sws = Scaler::get(frame.format(), w, h, av::format::Pixel::RGBA, dw, dh, flags);
+            let Ok(sws) = sws else {
+                error_l10n!("Could not create scaler for video frame");
+                return None;
+            };
+            self.scaler = Some(sws);
+            self.key = Some(key);
+        }
+
+        let mut scaled = VideoFrame::new(av::format::Pixel::RGBA, dw, dh);
+        if let Err(e) = self.scaler.as_mut()?.run(frame, &mut scaled) {
+            error_f16n!("Could not scale video frame: {}", e);
+            return None;
+        }
+        let (dw, dh) = (dw as usize, dh as usize);
+        let bytes = scaled.data(0);
+        let colors: &[Color] = unsafe {
+            std::slice::from_raw_parts(
+                bytes.as_ptr() as *const Color,
+                bytes.len() / std::mem::size_of::<Color>(),
+            )
+        };
+        let pitch = scaled.stride(0) / std::mem::size_of::<Color>();
+        let mut data = Vec::with_capacity(dw * dh);
+        for y in 0..dh {
+            data.extend_from_slice(&colors[y * pitch..y * pitch + dw]);
+        }
+        Some(Image {
+            data,
+            width: dw,
+            height: dh,
+            time,
+        })
+    }
+}
+
+/// 分屏画面的解码线程
+/// - 按 `target_time` 显示到期的最后一帧，落后时丢弃中间的帧
+/// - 目标时间跳变（主画面跳转、修改偏移）超过 `SEEK_SLACK` 时重新定位
+fn decode_tile(tile: Arc<Tile>) {
+    let Ok(mut ictx) = av::format::input(&tile.path) else {
+        error_f16n!("Failed to open input file: {}", tile.path);
+        return;
+    };
+    let stream = ictx
+        .streams()
+        .best(av::media::Type::Video)
+        .filter(|s| !ffmpeg::is_attached_pic(s));
+    let Some(stream) = stream else {
+        error_f16n!("No video stream in tile: {}", tile.path);
+        return;
+    };
+    let (index, time_base) = (stream.index(), stream.time_base());
+    let decoder = AVCCtx::from_parameters(stream.parameters()).and_then(|c| c.decoder().video());
+    let Ok(mut decoder) = decoder else {
+        error_f16n!("Failed to open video decoder for tile: {}", tile.path);
+        return;
+    };
+
+    let mut scaler = TileScaler {
+        scaler: None,
+        key: None,
+    };
+    // 还没到显示时间的下一帧，和最后显示的帧及其缩放到的区域大小
+    let mut pending: Option<(VideoFrame, Duration)> = None;
+    let mut shown: Option<(VideoFrame, Duration, Option<((usize, usize), u64)>)> = None;
+    // 最后解码的帧的时间，刚定位后为定位的目标时间
+    // - 从零开始，加入时主画面已经播放了一段时间也会先定位
+    let mut position = Duration::ZERO;
+    let mut eof = false;
+
+    while !tile.quit.load(Ordering::SeqCst) && !TERM_QUIT.load(Ordering::SeqCst) {
+        let target = tile.target_time();
+        if target + SEEK_SLACK < position || (!eof && target > position + SEEK_SLACK) {
+            let ts = (target.as_secs_f64() * AV_TIME_BASE as f64) as i64;
+            unsafe { av_seek_frame(ictx.as_mut_ptr(), -1, ts, AVSEEK_FLAG_BACKWARD as i32) };
+            decoder.flush();
+            pending = None;
+            position = target;
+            eof = false;
+        }
+
+        // 取出所有到期的帧，只显示最后一帧
+        let mut due = None;
+        let catch_up = Instant::now();
+        while !eof && catch_up.elapsed() < MAX_CATCH_UP {
+            if pending.is_none() {
+                match next_frame(&mut ictx, &mut decoder, index) {
+                    Some(frame) => {
+                        let time = frame_time(&frame, time_base).unwrap_or(position);
+                        position = time;
+                        pending = Some((frame, time));
+                    }
+                    None => eof = true,
+                }
+            }
+            match pending.take() {
+                Some((frame, time)) if time <= target || (shown.is_none() && due.is_none()) => {
+                    due = Some((frame, time));
+                    if time > target {
+                        break;
+                    }
+                }
+                other => {
+                    pending = other;
+                    break;
+                }
+            }
+        }
+
+        // 新的一帧，或暂停时区域大小、视图变化，都要重新缩放
+        if let Some((frame, time)) = due {
+            shown = Some((frame, time, None));
+        }
+        let key = (*tile.area.lock(), view::generation());
+        if let Some((frame, time, scaled_to)) = &mut shown
+            && *scaled_to != Some(key)
+        {
+            let image = match tile.mirror_view {
+                true => view::apply(frame)
+                    .and_then(|(cropped, _)| scaler.scale(&cropped, *time, key.0, true)),
+                false => scaler.scale(frame, *time, key.0, false),
+            };
+            *tile.image.lock() = image;
+            *scaled_to = Some(key);
+        }
+
+        let wait = match &pending {
+            Some((_, time)) => time.saturating_sub(target).min(MAX_WAIT),
+            None => MAX_WAIT,
+        };
+        std::thread::sleep(wait.max(Duration::from_millis(1)));
+    }
+}
@@ -8,6 +8,7 @@ use std::time::Duration;
 
 #[cfg(feature = "audio")]
 use crate::audio;
+use crate::chapters::{self, CHAPTERS_SELECTED_INDEX, SHOW_CHAPTERS};
 #[cfg(feature = "command")]
 use crate::command::render_command;
 use crate::logging::get_messages;
@@ -36,6 +37,7 @@ pub fn render_ui(wrap: &mut ContextWrapper) {
     render_progressbar(wrap);
     render_overlay_text(wrap);
     render_playlist(wrap);
+    render_chapters(wrap);
     render_file_select(wrap);
     render_messages(wrap);
     render_help(wrap);
@@ -49,6 +51,10 @@ pub static SHOW_PROGRESSBAR: AtomicBool = AtomicBool::new(true);
 /// 按像素计的进度条高度
 static mut PROGRESSBAR_HEIGHT: f32 = 16.0;
 
+pub fn set_progressbar_height(height: f32) {
+    unsafe { PROGRESSBAR_HEIGHT = height.max(0.0) };
+}
+
 fn calc_bar_size(cells_width: usize, cells_height: usize, font_height: f32) -> (usize, usize) {
     let bar_w = cells_width as f64 * avsync::playback_progress() + 0.5;
     let bar_h = unsafe { PROGRESSBAR_HEIGHT } / font_height * 2.0;
@@ -74,6 +80,27 @@ fn render_progressbar(wrap: &mut ContextWrapper) {
             }
         }
     }
+
+    // 章节分隔线
+    let total = avsync::total_duration().as_secs_f64();
+    if total <= 0.0 {
+        return;
+    }
+    for chapter in chapters::list() {
+        let x = chapter.start.as_secs_f64() / total * wrap.cells_width as f64 + 0.5;
+        let x = x as usize;
+        if x == 0 || x >= wrap.cells_width {
+            continue;
+        }
+        for y in wrap.cells_height * 2 - bar_h..wrap.cells_height * 2 {
+            let i = y / 2 * wrap.cells_pitch + x;
+            if y % 2 == 0 {
+                wrap.cells[i].bg = Color::halfhalf(wrap.cells[i].bg, Color::new(255, 255, 255));
+            } else {
+                wrap.cells[i].fg = Color::halfhalf(wrap.cells[i].fg, Color::new(255, 255, 255));
+            }
+        }
+    }
 }
 
 fn register_input_callbacks_progressbar() {
@@ -190,6 +217,35 @@ fn format_bytes_count(count: usize) -> String {
     }
 }
 
+/// 输出速度限制的状态
+fn format_output_rate() -> String {
+    let status = crate::ratectl::status();
+    let mut text = match status.capacity {
+        Some(capacity) => format!("{}/s", format_bytes_count(capacity as usize)),
+        None => l10n!("unlimited").to_string(),
+    };
+    if status.max_fps.is_finite() {
+        text += &f16n!(", max {:.1} fps", status.max_fps);
+    }
+    if status.level > 0 {
+        text += &f16n!(", quality level -{}", status.level);
+    }
+    text
+}
+
+/// 用方块字符把直方图画成一行
+fn format_histogram(histogram: &[u32]) -> String {
+    const BARS: [char; 9] = [' ', '▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    let max = histogram.iter().copied().max().unwrap_or(0).max(1);
+    histogram
+        .iter()
+        .map(|&n| {
+            let level = (n as u64 * (BARS.len() - 1) as u64).div_ceil(max as u64);
+            BARS[level as usize]
+        })
+        .collect()
+}
+
 fn render_overlay_text(wrap: &mut ContextWrapper) {
     if wrap.cells_width < 8 || wrap.cells_height < 8 {
         return; // 防炸
@@ -201,10 +257,10 @@ fn render_overlay_text(wrap: &mut ContextWrapper) {
 
     let playing_time_str = format_time(wrap.played_time);
 
-    let audio_offset_str = if avsync::has_audio() {
+    let audio_offset_str = if avsync::has_audio() && avsync::has_audio_output() {
         format!(
             "{:+07.3}ms",
-            (avsync::audio_played_time_or_zero().as_secs_f64()
+            (avsync::audio_played_time_or_zero().as_secs_f64() + avsync::audio_delay()
                 - avsync::played_time_or_zero().as_secs_f64())
                 * 1000.0
         )
@@ -212,6 +268,8 @@ fn render_overlay_text(wrap: &mut ContextWrapper) {
         "   N/A   ".to_string()
     };
 
+    let audio_delay_str = format!("{:+}ms", (avsync::audio_delay() * 1000.0).round() as i64);
+
     let video_offset_str = if avsync::has_video() {
         format!(
             "{:+07.3}ms",
@@ -223,6 +281,9 @@ fn render_overlay_text(wrap: &mut ContextWrapper) {
         "   N/A   ".to_string()
     };
 
+    let sync_stats = avsync::stats();
+    let drift_histogram = format_histogram(&sync_stats.histogram);
+
     let app_time_str = format_time(Some(wrap.app_time));
 
     // 这边关闭 autowrap，防止 unifont 渲染出问题
@@ -232,12 +293,15 @@ fn render_overlay_text(wrap: &mut ContextWrapper) {
     let statistics = statistics.lock();
 
     #[cfg(feature = "audio")]
-    let visualizer_status = if render::show_audio_visualizer() {
-        "ON"
+    let visualizer_status = if render::visualizer_visible() {
+        crate::visualizer::mode().name()
     } else {
         "OFF"
     };
 
+    #[cfg(feature = "audio")]
+    let audio_device = audio::current_device_name().unwrap_or_else(|| l10n!("None").to_string());
+
     let status = if avsync::is_paused() {
         l10n!("Paused")
     } else {
@@ -248,20 +312,39 @@ fn render_overlay_text(wrap: &mut ContextWrapper) {
         "tvid v{}", env!("CARGO_PKG_VERSION");
         "{}", l10n!("Press 'q' to quit, 'n' to skip to next, 'l' for playlist");
         "{}: {}", status, wrap.playing;
-        "{}", f16n!("Video Time: {} (a: {}, v: {})", playing_time_str, audio_offset_str, video_offset_str);
+        "{}", f16n!("Video Time: {} (a: {}, v: {}, delay: {})", playing_time_str, audio_offset_str, video_offset_str, audio_delay_str);
+        "{}", f16n!("Chapter(k): {}", chapters::status());
+        "{}", f16n!("Master Clock: {} (using {})", avsync::master_clock().name(), avsync::effective_master_clock().name());
+        "{}", f16n!("Sync Corrections: {} (resync {}, dropped {}, repeated {}, stretched {})", sync_stats.corrections, sync_stats.resyncs, sync_stats.dropped, sync_stats.repeated, sync_stats.stretched);
+        "{}", f16n!("A/V Drift: -100ms [{}] +100ms", drift_histogram);
         "{}", f16n!("App Time: {}", app_time_str);
         "{}", f16n!("Escape String Encode Time: {:.2?} (avg over last 60)", statistics.escape_string_encode_time.avg());
         "{}", f16n!("Render Time: {:.2?} (avg over last 60)", statistics.render_time.avg());
         "{}", f16n!("Output Time: {:.2?} (avg over last 60)", statistics.output_time.avg());
         "{}", f16n!("Output Bytes: {}", format_bytes_count(statistics.output_bytes.avg::<usize>()));
+        "{}", f16n!("Encoder Savings: {} ({} near-identical cells kept)", format_bytes_count(statistics.encoder_saved_bytes.avg::<usize>()), statistics.kept_cells.avg::<usize>());
         "{}", f16n!("Video Skipped Frames: {}", statistics.video_skipped_frames);
+        #[cfg(feature = "video")]
+        "{}", f16n!("Video Decoder: {}", crate::vdecoder::status());
+        #[cfg(feature = "video")]
+        "{}", f16n!("Video Filter: {}", crate::vfilter::status());
+        #[cfg(feature = "video")]
+        "{}", f16n!("View: {}", crate::view::status());
+        #[cfg(feature = "video")]
+        "{}", f16n!("Compare(b/v): {}", crate::compare::status());
         "{}", f16n!("Total Output Bytes: {}", format_bytes_count(statistics.total_output_bytes));
+        "{}", f16n!("Output Rate: {}", format_output_rate());
         "{}", f16n!("Color Mode: {}", wrap.color_mode);
         "{}", f16n!("Chroma Mode: {}", wrap.chroma_mode);
+        "{}", f16n!("Dither: {}", crate::dither::status());
         #[cfg(feature = "audio")]
         "{}", f16n!("Volume: {}%", (audio::get_volume() * 100.0).round() as usize);
         #[cfg(feature = "audio")]
         "{}", f16n!("Audio Visualizer(w): {}", visualizer_status);
+        #[cfg(feature = "audio")]
+        "{}", f16n!("Audio Device: {}", audio_device);
+        #[cfg(feature = "audio")]
+        "{}", f16n!("Audio Filter: {}", crate::afilter::status());
     );
 }
 
@@ -337,6 +420,84 @@ fn render_playlist(wrap: &mut ContextWrapper) {
     }
 }
 
+fn render_chapters(wrap: &mut ContextWrapper) {
+    if wrap.cells_width < 8 || wrap.cells_height < 8 {
+        return; // 防炸
+    }
+
+    let panel_width = if helper::font_large_enough(wrap) {
+        62.min(wrap.cells_width)
+    } else {
+        482.min(wrap.cells_width)
+    };
+
+    static mut CHAPTERS_POS: f32 = 0.0;
+    let mut panel_pos = unsafe { CHAPTERS_POS };
+    if SHOW_CHAPTERS.load(Ordering::SeqCst) {
+        panel_pos += wrap.delta_time.as_secs_f32() * 3000.0 / wrap.font_width;
+    } else {
+        panel_pos -= wrap.delta_time.as_secs_f32() * 3000.0 / wrap.font_width;
+    }
+    let panel_pos = panel_pos.clamp(0.0, panel_width as f32);
+    unsafe { CHAPTERS_POS = panel_pos };
+
+    let panel_pos = panel_pos as usize;
+    if panel_pos == 0 {
+        return;
+    }
+
+    helper::mask(
+        wrap,
+        wrap.cells_width.saturating_sub(panel_pos) as isize,
+        0,
+        panel_width,
+        wrap.cells_height,
+        Some(TERM_DEFAULT_BG),
+        TERM_DEFAULT_FG,
+        0.5,
+    );
+
+    helper::textbox(
+        wrap.cells_width.saturating_sub(panel_pos) as isize + 1,
+        1,
+        panel_width - 2,
+        wrap.cells_height - 2,
+        false,
+    );
+
+    helper::textbox_default_color(Some(TERM_DEFAULT_BG), None);
+
+    let list = chapters::list();
+    if list.is_empty() {
+        putln_or_ufln!(wrap, "{}", l10n!("No chapters"));
+        return;
+    }
+    putln_or_ufln!(wrap, "{}", f16n!("Chapters ({} items):", list.len()));
+
+    let selected_index = *CHAPTERS_SELECTED_INDEX.lock();
+    let playing_index = chapters::current();
+    for (i, chapter) in list.iter().enumerate() {
+        let icon = if Some(i) == playing_index {
+            "▶ "
+        } else {
+            "  "
+        };
+        let t = chapter.start.as_secs();
+        let text = format!(
+            "{icon}{:02}:{:02}:{:02} {}",
+            t / 3600,
+            t % 3600 / 60,
+            t % 60,
+            chapter.title
+        );
+        if i as isize == selected_index {
+            helper::putln_or_ufln(wrap, &text, Some(TERM_DEFAULT_FG), Some(TERM_DEFAULT_BG));
+        } else {
+            putln_or_ufln!(wrap, "{}", text);
+        }
+    }
+}
+
 fn render_messages(wrap: &mut ContextWrapper) {
     if wrap.cells_width < 8 || wrap.cells_height < 8 {
         return; // 防炸
@@ -273,6 +273,16 @@ impl Color {
         Color::from(ColorF32::mix(fg, bg, t))
     }
 
+    /// 解析 `#RRGGBB` 或 `RRGGBB` 格式的颜色
+    pub fn from_hex(s: &str) -> Option<Self> {
+        let hex = s.trim().trim_start_matches('#');
+        if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+        let val = u32::from_str_radix(hex, 16).ok()?;
+        Some(Color::new((val >> 16) as u8, (val >> 8) as u8, val as u8))
+    }
+
     pub fn similar_to(&self, other: &Color, threshold: f32) -> bool {
         let (c1, c2) = (self.as_f32(), other.as_f32());
         let dr = c1.r - c2.r;
@@ -329,7 +339,8 @@ pub struct Cell {
     /// - `None` 表示什么都没有
     /// - `Some('\0')` 表示占位符，这之前应当有一个宽度大于 1 的字符
     pub c: Option<char>,
-    pub braille: char,
+    /// `c` 为 `None` 时，盲文、块字符等模式实际输出的字符
+    pub glyph: char,
     pub fg: Color,
     pub bg: Color,
 }
@@ -338,7 +349,7 @@ impl Cell {
     pub const fn none(fg: Color, bg: Color) -> Self {
         Cell {
             c: None,
-            braille: ' ',
+            glyph: ' ',
             fg,
             bg,
         }
@@ -347,7 +358,7 @@ impl Cell {
     pub const fn new(c: char, fg: Color, bg: Color) -> Self {
         Cell {
             c: Some(c),
-            braille: ' ',
+            glyph: ' ',
             fg,
             bg,
         }
@@ -356,7 +367,7 @@ impl Cell {
     pub const fn transparent() -> Self {
         Cell {
             c: Some(' '),
-            braille: ' ',
+            glyph: ' ',
             fg: Color::transparent(),
             bg: Color::transparent(),
         }
@@ -373,6 +384,33 @@ pub fn some_if_ne<T: PartialEq>(a: T, b: T) -> Option<T> {
     if a == b { None } else { Some(a) }
 }
 
+/// 简单的通配符匹配（忽略大小写）
+/// - `*` 匹配任意长度的字符（包括 `/`）
+/// - `?` 匹配单个字符
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let p: Vec<char> = pattern.to_lowercase().chars().collect();
+    let t: Vec<char> = text.to_lowercase().chars().collect();
+    let (mut pi, mut ti) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while ti < t.len() {
+        if pi < p.len() && (p[pi] == '?' || p[pi] == t[ti]) {
+            pi += 1;
+            ti += 1;
+        } else if pi < p.len() && p[pi] == '*' {
+            star = Some((pi, ti));
+            pi += 1;
+        } else if let Some((sp, st)) = star {
+            // 回溯：让上一个 `*` 多吃一个字符
+            pi = sp + 1;
+            ti = st + 1;
+            star = Some((sp, st + 1));
+        } else {
+            return false;
+        }
+    }
+    p[pi..].iter().all(|&c| c == '*')
+}
+
 // @ ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== @
 
 mod palette256 {
@@ -445,11 +483,9 @@ pub fn palette256_to_color(index: u8) -> Color {
     }
 }
 
+/// 在感知色彩空间中取最接近的 256 色，见 [`crate::dither::palette256_nearest`]
 pub fn palette256_from_color(c: Color) -> u8 {
-    let r = palette256::reverse(c.r);
-    let g = palette256::reverse(c.g);
-    let b = palette256::reverse(c.b);
-    r * 36 + g * 6 + b + 16
+    crate::dither::palette256_nearest(c)
 }
 
 pub fn try_palette256(c: Color) -> Option<u8> {
@@ -480,6 +516,9 @@ pub enum ColorMode {
     /// 直接显示图像
     #[cfg(feature = "osc1337")]
     OSC1337,
+    /// kitty 图像协议，终端复用器内使用 Unicode 占位符
+    #[cfg(feature = "kitty")]
+    Kitty,
     /// 真彩色模式，仅使用 24 位真彩色
     #[default]
     TrueColorOnly,
@@ -493,8 +532,16 @@ pub enum ColorMode {
     BlackWhite,
     /// ASCII 艺术模式
     AsciiArt,
+    /// 无颜色的 ASCII 艺术模式，只靠字符形状表现画面
+    AsciiMono,
     /// Unicode 盲文模式
     Braille,
+    /// 2×2 象限块字符模式
+    Quadrant,
+    /// 2×3 六分块字符模式（Unicode 13）
+    Sextant,
+    /// 2×4 八分块字符模式（Unicode 16）
+    Octant,
 }
 
 impl Display for ColorMode {
@@ -504,13 +551,19 @@ impl Display for ColorMode {
             ColorMode::Sixel => write!(f, "Sixel"),
             #[cfg(feature = "osc1337")]
             ColorMode::OSC1337 => write!(f, "OSC1337"),
+            #[cfg(feature = "kitty")]
+            ColorMode::Kitty => write!(f, "Kitty"),
             ColorMode::TrueColorOnly => write!(f, "{}", l10n!("True Color Mode")),
             ColorMode::Palette256Prefer => write!(f, "{}", l10n!("256 Color Palette Prefer")),
             ColorMode::Palette256Only => write!(f, "{}", l10n!("256 Color Palette Only")),
             ColorMode::GrayScale => write!(f, "{}", l10n!("Gray Scale Mode")),
             ColorMode::BlackWhite => write!(f, "{}", l10n!("Black and White Mode")),
             ColorMode::AsciiArt => write!(f, "{}", l10n!("ASCII Art Mode")),
+            ColorMode::AsciiMono => write!(f, "{}", l10n!("Monochrome ASCII Art Mode")),
             ColorMode::Braille => write!(f, "{}", l10n!("Unicode Braille Mode")),
+            ColorMode::Quadrant => write!(f, "{}", l10n!("Unicode Quadrant Mode")),
+            ColorMode::Sextant => write!(f, "{}", l10n!("Unicode Sextant Mode")),
+            ColorMode::Octant => write!(f, "{}", l10n!("Unicode Octant Mode")),
         }
     }
 }
@@ -524,19 +577,74 @@ impl ColorMode {
         ColorMode::TrueColorOnly
     }
 
+    /// 所有可用的颜色模式（按切换顺序）
+    pub const ALL: &[ColorMode] = &[
+        #[cfg(feature = "sixel")]
+        ColorMode::Sixel,
+        #[cfg(feature = "osc1337")]
+        ColorMode::OSC1337,
+        #[cfg(feature = "kitty")]
+        ColorMode::Kitty,
+        ColorMode::TrueColorOnly,
+        ColorMode::Palette256Prefer,
+        ColorMode::Palette256Only,
+        ColorMode::GrayScale,
+        ColorMode::BlackWhite,
+        ColorMode::AsciiArt,
+        ColorMode::AsciiMono,
+        ColorMode::Braille,
+        ColorMode::Quadrant,
+        ColorMode::Sextant,
+        ColorMode::Octant,
+    ];
+
+    /// 用于配置文件和命令行的名称
+    pub const fn name(&self) -> &'static str {
+        match self {
+            #[cfg(feature = "sixel")]
+            ColorMode::Sixel => "sixel",
+            #[cfg(feature = "osc1337")]
+            ColorMode::OSC1337 => "osc1337",
+            #[cfg(feature = "kitty")]
+            ColorMode::Kitty => "kitty",
+            ColorMode::TrueColorOnly => "truecolor",
+            ColorMode::Palette256Prefer => "palette256-prefer",
+            ColorMode::Palette256Only => "palette256",
+            ColorMode::GrayScale => "grayscale",
+            ColorMode::BlackWhite => "blackwhite",
+            ColorMode::AsciiArt => "ascii",
+            ColorMode::AsciiMono => "ascii-mono",
+            ColorMode::Braille => "braille",
+            ColorMode::Quadrant => "quadrant",
+            ColorMode::Sextant => "sextant",
+            ColorMode::Octant => "octant",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.trim().to_lowercase();
+        Self::ALL.iter().copied().find(|m| m.name() == name)
+    }
+
     pub const fn fppc(&self) -> (usize, usize) {
         match self {
             #[cfg(feature = "sixel")]
             ColorMode::Sixel => (0, 0),
             #[cfg(feature = "osc1337")]
             ColorMode::OSC1337 => (0, 0),
+            #[cfg(feature = "kitty")]
+            ColorMode::Kitty => (0, 0),
             ColorMode::TrueColorOnly => (1, 2),
             ColorMode::Palette256Prefer => (1, 2),
             ColorMode::Palette256Only => (1, 2),
             ColorMode::GrayScale => (1, 2),
             ColorMode::BlackWhite => (1, 2),
             ColorMode::AsciiArt => (1, 1),
+            ColorMode::AsciiMono => (1, 1),
             ColorMode::Braille => (2, 4),
+            ColorMode::Quadrant => (2, 2),
+            ColorMode::Sextant => (2, 3),
+            ColorMode::Octant => (2, 4),
         }
     }
 }
@@ -582,6 +690,39 @@ impl ChromaMode {
         ChromaMode::None
     }
 
+    /// 所有可用的绿幕模式（按切换顺序）
+    pub const ALL: &[ChromaMode] = &[
+        ChromaMode::None,
+        ChromaMode::Red,
+        ChromaMode::Green,
+        ChromaMode::Blue,
+        ChromaMode::Yellow,
+        ChromaMode::Magenta,
+        ChromaMode::Cyan,
+        ChromaMode::White,
+        ChromaMode::Black,
+    ];
+
+    /// 用于配置文件和命令行的名称
+    pub const fn name(&self) -> &'static str {
+        match self {
+            ChromaMode::None => "none",
+            ChromaMode::Red => "red",
+            ChromaMode::Green => "green",
+            ChromaMode::Blue => "blue",
+            ChromaMode::Yellow => "yellow",
+            ChromaMode::Magenta => "magenta",
+            ChromaMode::Cyan => "cyan",
+            ChromaMode::White => "white",
+            ChromaMode::Black => "black",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.trim().to_lowercase();
+        Self::ALL.iter().copied().find(|m| m.name() == name)
+    }
+
     pub const fn color(&self) -> Option<Color> {
         match self {
             ChromaMode::None => None,
@@ -626,13 +767,19 @@ pub fn escape_set_color(
         ColorMode::Sixel => escape_set_color_rgb(wr, fg, bg),
         #[cfg(feature = "osc1337")]
         ColorMode::OSC1337 => escape_set_color_rgb(wr, fg, bg),
+        #[cfg(feature = "kitty")]
+        ColorMode::Kitty => escape_set_color_rgb(wr, fg, bg),
         ColorMode::TrueColorOnly => escape_set_color_rgb(wr, fg, bg),
         ColorMode::Palette256Prefer => escape_set_color_256_prefer(wr, fg, bg),
         ColorMode::Palette256Only => escape_set_color_256(wr, fg, bg),
         ColorMode::GrayScale => escape_set_color_gray_scale(wr, fg, bg),
         ColorMode::BlackWhite => escape_set_color_black_white(wr, fg, bg),
         ColorMode::AsciiArt => escape_set_color_rgb(wr, fg, bg),
+        ColorMode::AsciiMono => (),
         ColorMode::Braille => escape_set_color_rgb(wr, fg, bg),
+        ColorMode::Quadrant => escape_set_color_rgb(wr, fg, bg),
+        ColorMode::Sextant => escape_set_color_rgb(wr, fg, bg),
+        ColorMode::Octant => escape_set_color_rgb(wr, fg, bg),
     }
 }
 
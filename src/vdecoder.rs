@@ -0,0 +1,370 @@
+use av::codec::discard::Discard;
+use av::decoder::{Decoder, Video as VideoDecoder};
+use av::ffi::{FF_THREAD_FRAME, FF_THREAD_SLICE};
+use parking_lot::Mutex;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::render;
+use crate::statistics;
+use crate::video::VIDEO_FRAMETIME;
+use crate::view;
+
+// @ ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== @
+
+/// 多线程解码方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThreadType {
+    /// 由 FFmpeg 选择帧级或片级多线程
+    Auto,
+    /// 帧级多线程，吞吐量高但会增加几帧延迟
+    Frame,
+    /// 片级多线程，没有额外延迟，但只对分片编码的视频有效
+    Slice,
+}
+
+impl ThreadType {
+    pub const ALL: &[ThreadType] = &[ThreadType::Auto, ThreadType::Frame, ThreadType::Slice];
+
+    pub const fn name(&self) -> &'static str {
+        match self {
+            ThreadType::Auto => "auto",
+            ThreadType::Frame => "frame",
+            ThreadType::Slice => "slice",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.trim().to_lowercase();
+        Self::ALL.iter().copied().find(|m| m.name() == name)
+    }
+
+    const fn flags(&self) -> i32 {
+        match self {
+            ThreadType::Auto => FF_THREAD_FRAME | FF_THREAD_SLICE,
+            ThreadType::Frame => FF_THREAD_FRAME,
+            ThreadType::Slice => FF_THREAD_SLICE,
+        }
+    }
+}
+
+/// 解码时跳过的帧，按跳过的程度从低到高排列
+/// - 名称与 FFmpeg 的 `skip_frame` / `skip_loop_filter` 选项一致
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Skip {
+    Default,
+    /// 非参考帧
+    NonRef,
+    /// 双向预测帧
+    Bidir,
+    /// 非帧内编码帧
+    NonIntra,
+    /// 非关键帧
+    NonKey,
+    All,
+}
+
+impl Skip {
+    pub const ALL: &[Skip] = &[
+        Skip::Default,
+        Skip::NonRef,
+        Skip::Bidir,
+        Skip::NonIntra,
+        Skip::NonKey,
+        Skip::All,
+    ];
+
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Skip::Default => "default",
+            Skip::NonRef => "noref",
+            Skip::Bidir => "bidir",
+            Skip::NonIntra => "nointra",
+            Skip::NonKey => "nokey",
+            Skip::All => "all",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.trim().to_lowercase();
+        Self::ALL.iter().copied().find(|m| m.name() == name)
+    }
+
+    const fn discard(&self) -> Discard {
+        match self {
+            Skip::Default => Discard::Default,
+            Skip::NonRef => Discard::NonReference,
+            Skip::Bidir => Discard::Bidirectional,
+            Skip::NonIntra => Discard::NonIntra,
+            Skip::NonKey => Discard::NonKey,
+            Skip::All => Discard::All,
+        }
+    }
+}
+
+// @ ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== @
+
+#[derive(Debug, Clone, Copy)]
+struct Settings {
+    /// 解码线程数，0 表示自动
+    threads: usize,
+    thread_type: ThreadType,
+    skip_loop_filter: Skip,
+    skip_frame: Skip,
+    /// 按 2 的幂缩小解码分辨率，None 表示按终端输出大小自动选择
+    lowres: Option<u8>,
+    /// 跳帧过多时自动降低解码质量
+    auto_degrade: bool,
+}
+
+impl Settings {
+    const fn new() -> Self {
+        Self {
+            threads: 0,
+            thread_type: ThreadType::Auto,
+            skip_loop_filter: Skip::Default,
+            skip_frame: Skip::Default,
+            lowres: None,
+            auto_degrade: true,
+        }
+    }
+}
+
+static SETTINGS: Mutex<Settings> = Mutex::new(Settings::new());
+
+pub fn set_threads(threads: usize) {
+    SETTINGS.lock().threads = threads;
+}
+
+pub fn set_thread_type(thread_type: ThreadType) {
+    SETTINGS.lock().thread_type = thread_type;
+}
+
+pub fn set_skip_loop_filter(skip: Skip) {
+    SETTINGS.lock().skip_loop_filter = skip;
+}
+
+pub fn set_skip_frame(skip: Skip) {
+    SETTINGS.lock().skip_frame = skip;
+}
+
+pub fn set_lowres(lowres: Option<u8>) {
+    SETTINGS.lock().lowres = lowres.map(|n| n.min(MAX_LOWRES));
+}
+
+pub fn set_auto_degrade(enabled: bool) {
+    SETTINGS.lock().auto_degrade = enabled;
+}
+
+/// 解析 `lowres` 配置，`auto` 或空字符串表示自动
+pub fn parse_lowres(value: &str) -> Option<Option<u8>> {
+    match value.trim() {
+        "" | "auto" => Some(None),
+        v => v.parse::<u8>().ok().filter(|&n| n <= MAX_LOWRES).map(Some),
+    }
+}
+
+/// FFmpeg 的解码器最多支持缩小到 1/8
+const MAX_LOWRES: u8 = 3;
+
+/// 当前文件实际使用的 lowres
+static LOWRES_IN_USE: AtomicU8 = AtomicU8::new(0);
+/// 当前视频流的原始分辨率
+static SOURCE_SIZE: Mutex<(usize, usize)> = Mutex::new((0, 0));
+
+pub fn lowres_in_use() -> u8 {
+    LOWRES_IN_USE.load(Ordering::SeqCst)
+}
+
+/// 打开解码器之前应用线程和 lowres 设置
+pub fn configure(decoder: &mut Decoder) {
+    let settings = *SETTINGS.lock();
+    let max_lowres = decoder
+        .codec()
+        .map_or(0, |c| c.max_lowres().clamp(0, MAX_LOWRES as i32) as u8);
+    let (width, height) = unsafe { ((*decoder.as_ptr()).width, (*decoder.as_ptr()).height) };
+    let (width, height) = (width.max(0) as usize, height.max(0) as usize);
+    let lowres = match settings.lowres {
+        Some(n) => n,
+        None => auto_lowres(width, height),
+    }
+    .min(max_lowres);
+    LOWRES_IN_USE.store(lowres, Ordering::SeqCst);
+    *SOURCE_SIZE.lock() = (width, height);
+
+    unsafe {
+        let ptr = decoder.as_mut_ptr();
+        (*ptr).thread_count = settings.threads as i32;
+        (*ptr).thread_type = settings.thread_type.flags();
+        (*ptr).lowres = lowres as i32;
+    }
+    decoder.skip_loop_filter(settings.skip_loop_filter.discard());
+    decoder.skip_frame(settings.skip_frame.discard());
+}
+
+/// 自动 lowres 下，放大后当前的解码分辨率不够用时需要重新打开解码器
+pub fn needs_reopen() -> bool {
+    let lowres = LOWRES_IN_USE.load(Ordering::SeqCst);
+    if lowres == 0 || SETTINGS.lock().lowres.is_some() {
+        return false;
+    }
+    let (width, height) = *SOURCE_SIZE.lock();
+    auto_lowres(width, height) < lowres
+}
+
+/// 在不低于输出画面像素数（放大时按放大后的大小算）的前提下尽量缩小解码分辨率
+fn auto_lowres(width: usize, height: usize) -> u8 {
+    let (pw, ph) = {
+        let ctx = render::RENDER_CONTEXT.lock();
+        if ctx.fppc_x == 0 || ctx.fppc_y == 0 {
+            (ctx.pixels_width, ctx.pixels_height)
+        } else {
+            (ctx.cells_width * ctx.fppc_x, ctx.cells_height * ctx.fppc_y)
+        }
+    };
+    if pw == 0 || ph == 0 || width == 0 || height == 0 {
+        return 0;
+    }
+    let zoom = view::zoom().max(1.0);
+    let (pw, ph) = (
+        (pw as f32 * zoom).ceil() as usize,
+        (ph as f32 * zoom).ceil() as usize,
+    );
+    (0..=MAX_LOWRES)
+        .rev()
+        .find(|&n| width >> n >= pw || height >> n >= ph)
+        .unwrap_or(0)
+}
+
+// @ ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== @
+
+/// 统计跳帧数的时间窗口
+const DEGRADE_WINDOW: Duration = Duration::from_secs(2);
+/// 窗口内跳帧超过此比例时提高降级等级
+const DEGRADE_UP_RATIO: f64 = 0.1;
+/// 连续这么多个窗口没有跳帧时降低降级等级
+const DEGRADE_DOWN_WINDOWS: u32 = 5;
+/// 最高降级等级
+const DEGRADE_MAX: u8 = 3;
+
+/// 当前的降级等级，0 表示没有降级
+static DEGRADE_LEVEL: AtomicU8 = AtomicU8::new(0);
+
+/// 降级等级对应的 (skip_loop_filter, skip_frame)
+/// - 先跳过环路滤波，画质损失在终端里几乎看不出来
+/// - 最后才丢弃非参考帧
+const fn degrade_skips(level: u8) -> (Skip, Skip) {
+    match level {
+        0 => (Skip::Default, Skip::Default),
+        1 => (Skip::NonRef, Skip::Default),
+        2 => (Skip::All, Skip::Default),
+        _ => (Skip::All, Skip::NonRef),
+    }
+}
+
+/// 根据跳帧统计自动调整解码质量
+pub struct Degrader {
+    window_start: Instant,
+    skipped_at_start: usize,
+    quiet_windows: u32,
+    applied: Option<(Skip, Skip)>,
+}
+
+impl Degrader {
+    pub fn new() -> Self {
+        DEGRADE_LEVEL.store(0, Ordering::SeqCst);
+        Self {
+            window_start: Instant::now(),
+            skipped_at_start: skipped_frames(),
+            quiet_windows: 0,
+            applied: None,
+        }
+    }
+
+    /// 解码器重新打开后，跳帧设置需要重新应用
+    pub fn decoder_reopened(&mut self) {
+        self.applied = None;
+    }
+
+    /// 在解码线程中周期性调用
+    pub fn update(&mut self, decoder: &mut Option<VideoDecoder>) {
+        let Some(decoder) = decoder.as_mut() else {
+            return;
+        };
+        let settings = *SETTINGS.lock();
+
+        let elapsed = self.window_start.elapsed();
+        if elapsed >= DEGRADE_WINDOW {
+            let skipped = skipped_frames();
+            let delta = skipped.saturating_sub(self.skipped_at_start);
+            self.window_start = Instant::now();
+            self.skipped_at_start = skipped;
+
+            let frametime = VIDEO_FRAMETIME.load(Ordering::SeqCst).max(1) as f64 / 1e6;
+            let expected = elapsed.as_secs_f64() / frametime;
+            let level = DEGRADE_LEVEL.load(Ordering::SeqCst);
+            if !settings.auto_degrade {
+                DEGRADE_LEVEL.store(0, Ordering::SeqCst);
+            } else if delta as f64 > expected * DEGRADE_UP_RATIO && level < DEGRADE_MAX {
+                self.quiet_windows = 0;
+                DEGRADE_LEVEL.store(level + 1, Ordering::SeqCst);
+                info_f16n!("Decoder under load, degrading to level {}", level + 1);
+            } else if delta == 0 && level > 0 {
+                self.quiet_windows += 1;
+                if self.quiet_windows >= DEGRADE_DOWN_WINDOWS {
+                    self.quiet_windows = 0;
+                    DEGRADE_LEVEL.store(level - 1, Ordering::SeqCst);
+                }
+            } else {
+                self.quiet_windows = 0;
+            }
+        }
+
+        // 取配置和降级等级中跳过更多的一方，配置修改后也能立即生效
+        let (loop_filter, frame) = degrade_skips(DEGRADE_LEVEL.load(Ordering::SeqCst));
+        let skips = (
+            loop_filter.max(settings.skip_loop_filter),
+            frame.max(settings.skip_frame),
+        );
+        if self.applied != Some(skips) {
+            decoder.skip_loop_filter(skips.0.discard());
+            decoder.skip_frame(skips.1.discard());
+            self.applied = Some(skips);
+        }
+    }
+}
+
+fn skipped_frames() -> usize {
+    statistics::get(0).lock().video_skipped_frames
+}
+
+/// 用于叠加文本的解码器状态
+pub fn status() -> String {
+    let settings = *SETTINGS.lock();
+    let mut parts = vec![format!(
+        "threads={}/{}",
+        match settings.threads {
+            0 => "auto".to_string(),
+            n => n.to_string(),
+        },
+        settings.thread_type.name()
+    )];
+    let lowres = LOWRES_IN_USE.load(Ordering::SeqCst);
+    if lowres > 0 {
+        parts.push(format!("lowres=1/{}", 1 << lowres));
+    }
+    if settings.skip_loop_filter != Skip::Default {
+        parts.push(format!(
+            "skip_loop_filter={}",
+            settings.skip_loop_filter.name()
+        ));
+    }
+    if settings.skip_frame != Skip::Default {
+        parts.push(format!("skip_frame={}", settings.skip_frame.name()));
+    }
+    let level = DEGRADE_LEVEL.load(Ordering::SeqCst);
+    if level > 0 {
+        parts.push(format!("degraded={level}"));
+    }
+    parts.join(" ")
+}
@@ -0,0 +1,549 @@
+use av::filter::Graph;
+use av::util::frame::video::Video as VideoFrame;
+use parking_lot::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use crate::ffmpeg::VIDEO_TIME_BASE;
+use crate::vdecoder;
+
+// @ ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== @
+
+/// 裁剪区域（像素）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub w: u32,
+    pub h: u32,
+    pub x: u32,
+    pub y: u32,
+}
+
+impl Rect {
+    /// 解析 `w:h:x:y`，省略 x、y 时居中
+    pub fn parse(value: &str) -> Option<Self> {
+        let parts = value
+            .split(':')
+            .map(|v| v.trim().parse::<u32>().ok())
+            .collect::<Option<Vec<_>>>()?;
+        match parts[..] {
+            [w, h] if w > 0 && h > 0 => Some(Self {
+                w,
+                h,
+                x: u32::MAX,
+                y: u32::MAX,
+            }),
+            [w, h, x, y] if w > 0 && h > 0 => Some(Self { w, h, x, y }),
+            _ => None,
+        }
+    }
+
+    /// 限制在 width × height 的画面内，未指定的位置居中
+    fn clamp(&self, width: u32, height: u32) -> Self {
+        let w = self.w.min(width);
+        let h = self.h.min(height);
+        let x = if self.x == u32::MAX {
+            (width - w) / 2
+        } else {
+            self.x.min(width - w)
+        };
+        let y = if self.y == u32::MAX {
+            (height - h) / 2
+        } else {
+            self.y.min(height - h)
+        };
+        Self { w, h, x, y }
+    }
+
+    /// 换算到按 1/2^shift 解码（lowres）的画面上
+    fn downscale(&self, shift: u8) -> Self {
+        let pos = |v: u32| if v == u32::MAX { v } else { v >> shift };
+        Self {
+            w: (self.w >> shift).max(1),
+            h: (self.h >> shift).max(1),
+            x: pos(self.x),
+            y: pos(self.y),
+        }
+    }
+
+    fn union(&self, other: &Self) -> Self {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        let w = (self.x + self.w).max(other.x + other.w) - x;
+        let h = (self.y + self.h).max(other.y + other.h) - y;
+        Self { w, h, x, y }
+    }
+}
+
+impl std::fmt::Display for Rect {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.x == u32::MAX || self.y == u32::MAX {
+            write!(f, "{}:{}", self.w, self.h)
+        } else {
+            write!(f, "{}:{}:{}:{}", self.w, self.h, self.x, self.y)
+        }
+    }
+}
+
+/// 裁剪模式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Crop {
+    Off,
+    /// 自动检测并裁掉黑边
+    Auto,
+    Manual(Rect),
+}
+
+impl Crop {
+    /// 解析 `off`、`auto` 或 `w:h[:x:y]`，空字符串表示关闭
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim() {
+            "" | "off" => Some(Crop::Off),
+            "auto" => Some(Crop::Auto),
+            v => Rect::parse(v).map(Crop::Manual),
+        }
+    }
+}
+
+/// 反交错模式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Deinterlace {
+    Off,
+    /// 只处理标记为隔行扫描的帧
+    Auto,
+    /// 处理所有帧
+    On,
+}
+
+impl Deinterlace {
+    pub const ALL: &[Deinterlace] = &[Deinterlace::Off, Deinterlace::Auto, Deinterlace::On];
+
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Deinterlace::Off => "off",
+            Deinterlace::Auto => "auto",
+            Deinterlace::On => "on",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.trim().to_lowercase();
+        Self::ALL.iter().copied().find(|m| m.name() == name)
+    }
+}
+
+/// 亮度、对比度、饱和度和伽马
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VideoEq {
+    pub brightness: f32,
+    pub contrast: f32,
+    pub saturation: f32,
+    pub gamma: f32,
+}
+
+impl VideoEq {
+    pub const DEFAULT: VideoEq = VideoEq {
+        brightness: 0.0,
+        contrast: 1.0,
+        saturation: 1.0,
+        gamma: 1.0,
+    };
+
+    /// 解析 `brightness=0.1:contrast=1.2` 形式的参数，空字符串表示默认值
+    /// - 取值范围与 FFmpeg 的 eq 滤镜一致
+    pub fn parse(value: &str) -> Option<Self> {
+        let mut eq = VideoEq::DEFAULT;
+        for item in value.split([':', ',', ' ']).filter(|s| !s.is_empty()) {
+            let (key, v) = item.split_once('=')?;
+            let v = v.trim().parse::<f32>().ok().filter(|v| v.is_finite())?;
+            match key.trim() {
+                "brightness" if (-1.0..=1.0).contains(&v) => eq.brightness = v,
+                "contrast" if (-1000.0..=1000.0).contains(&v) => eq.contrast = v,
+                "saturation" if (0.0..=3.0).contains(&v) => eq.saturation = v,
+                "gamma" if (0.1..=10.0).contains(&v) => eq.gamma = v,
+                _ => return None,
+            }
+        }
+        Some(eq)
+    }
+}
+
+impl std::fmt::Display for VideoEq {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "brightness={}:contrast={}:saturation={}:gamma={}",
+            self.brightness, self.contrast, self.saturation, self.gamma
+        )
+    }
+}
+
+// @ ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== @
+
+#[derive(Debug, Clone, Copy)]
+struct Settings {
+    crop: Crop,
+    /// 顺时针旋转角度，None 表示按流的显示矩阵旋转
+    rotate: Option<u32>,
+    hflip: bool,
+    vflip: bool,
+    deinterlace: Deinterlace,
+    eq: VideoEq,
+}
+
+impl Settings {
+    const fn new() -> Self {
+        Self {
+            crop: Crop::Off,
+            rotate: None,
+            hflip: false,
+            vflip: false,
+            deinterlace: Deinterlace::Auto,
+            eq: VideoEq::DEFAULT,
+        }
+    }
+}
+
+static SETTINGS: Mutex<Settings> = Mutex::new(Settings::new());
+/// 设置每次变化时递增，视频线程据此重建滤镜图
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+/// 视频帧当前是否经过了滤镜图
+static FILTERING: AtomicBool = AtomicBool::new(false);
+/// 当前视频流显示矩阵中的顺时针旋转角度
+static STREAM_ROTATION: Mutex<u32> = Mutex::new(0);
+
+fn update_settings(f: impl FnOnce(&mut Settings)) {
+    f(&mut SETTINGS.lock());
+    invalidate();
+}
+
+/// 让视频线程重建滤镜图，丢弃滤镜内部缓存的帧
+pub fn invalidate() {
+    GENERATION.fetch_add(1, Ordering::SeqCst);
+}
+
+pub fn set_crop(crop: Crop) {
+    update_settings(|s| s.crop = crop);
+}
+
+/// 设置旋转角度，只接受 90 的倍数
+pub fn set_rotate(rotate: Option<u32>) {
+    update_settings(|s| s.rotate = rotate.map(|r| r / 90 * 90 % 360));
+}
+
+pub fn set_hflip(hflip: bool) {
+    update_settings(|s| s.hflip = hflip);
+}
+
+pub fn set_vflip(vflip: bool) {
+    update_settings(|s| s.vflip = vflip);
+}
+
+pub fn toggle_hflip() {
+    update_settings(|s| s.hflip = !s.hflip);
+}
+
+pub fn toggle_vflip() {
+    update_settings(|s| s.vflip = !s.vflip);
+}
+
+pub fn set_deinterlace(deinterlace: Deinterlace) {
+    update_settings(|s| s.deinterlace = deinterlace);
+}
+
+pub fn set_eq(eq: VideoEq) {
+    update_settings(|s| s.eq = eq);
+}
+
+/// 关闭所有滤镜
+pub fn reset() {
+    update_settings(|s| *s = Settings::new());
+}
+
+/// 视频帧当前是否经过了滤镜（裁剪、旋转等），对比模式的 B 不经过滤镜
+pub fn active() -> bool {
+    FILTERING.load(Ordering::SeqCst)
+}
+
+/// 解析旋转角度，`auto` 或空字符串表示按显示矩阵旋转
+pub fn parse_rotate(value: &str) -> Option<Option<u32>> {
+    match value.trim() {
+        "" | "auto" => Some(None),
+        v => v
+            .parse::<u32>()
+            .ok()
+            .filter(|r| r % 90 == 0 && *r < 360)
+            .map(Some),
+    }
+}
+
+/// 从视频流的显示矩阵中读取旋转角度，打开文件时调用
+pub fn load_stream_rotation(stream: &av::format::stream::Stream) {
+    use av::packet::side_data::Type;
+    let rotation = stream
+        .side_data()
+        .find(|sd| sd.kind() == Type::DisplayMatrix)
+        .filter(|sd| sd.data().len() >= 36)
+        .map_or(0, |sd| {
+            // 显示矩阵中的角度是逆时针的
+            let theta =
+                unsafe { av::ffi::av_display_rotation_get(sd.data().as_ptr() as *const i32) };
+            let theta = -theta.round() as i64;
+            (((theta + 45).rem_euclid(360)) / 90 * 90) as u32
+        });
+    *STREAM_ROTATION.lock() = rotation;
+    invalidate();
+}
+
+// @ ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== @
+
+/// 至少检测这么多帧之后才应用自动裁剪
+const AUTOCROP_MIN_FRAMES: usize = 15;
+/// 检测结果的变化小于此像素数时不重建滤镜图
+const AUTOCROP_TOLERANCE: u32 = 4;
+
+/// 自动裁剪的检测结果，跨 seek 保留
+#[derive(Debug, Clone, Copy, Default)]
+struct AutoCrop {
+    /// 目前检测到的所有画面区域的并集，只会扩大，避免暗场景把画面裁掉
+    detected: Option<Rect>,
+    frames: usize,
+    applied: Option<Rect>,
+}
+
+impl AutoCrop {
+    fn update(&mut self, rect: Rect) -> bool {
+        self.frames += 1;
+        let detected = match self.detected {
+            Some(d) => d.union(&rect),
+            None => rect,
+        };
+        self.detected = Some(detected);
+        if self.frames < AUTOCROP_MIN_FRAMES {
+            return false;
+        }
+        let changed = match self.applied {
+            None => true,
+            Some(a) => {
+                a.x.abs_diff(detected.x) >= AUTOCROP_TOLERANCE
+                    || a.y.abs_diff(detected.y) >= AUTOCROP_TOLERANCE
+                    || a.w.abs_diff(detected.w) >= AUTOCROP_TOLERANCE
+                    || a.h.abs_diff(detected.h) >= AUTOCROP_TOLERANCE
+            }
+        };
+        if changed {
+            self.applied = Some(detected);
+        }
+        changed
+    }
+}
+
+/// 视频线程中的滤镜图
+pub struct VideoFilter {
+    graph: Option<Graph>,
+    generation: u64,
+    /// 滤镜图对应的输入格式和大小
+    input: Option<(av::format::Pixel, u32, u32)>,
+    /// 当前设置下滤镜图构建失败，跳过滤镜
+    failed: bool,
+    autocrop: AutoCrop,
+}
+
+impl VideoFilter {
+    pub fn new() -> Self {
+        Self {
+            graph: None,
+            generation: u64::MAX,
+            input: None,
+            failed: false,
+            autocrop: AutoCrop::default(),
+        }
+    }
+
+    /// 帧通过滤镜图，滤镜还需要更多输入时返回 None
+    pub fn process(&mut self, frame: VideoFrame) -> Option<VideoFrame> {
+        let generation = GENERATION.load(Ordering::SeqCst);
+        let input = Some((frame.format(), frame.width(), frame.height()));
+        if generation != self.generation || input != self.input {
+            // 解码分辨率变了（如重新打开解码器换了 lowres），检测到的裁剪区域不再适用
+            if self
+                .input
+                .is_some_and(|(_, w, h)| (w, h) != (frame.width(), frame.height()))
+            {
+                self.autocrop = AutoCrop::default();
+            }
+            self.generation = generation;
+            self.input = input;
+            self.failed = false;
+            self.graph = None;
+            match build_spec(&frame, self.autocrop.applied) {
+                None => (),
+                Some(spec) => match build_graph(&frame, &spec) {
+                    Ok(graph) => self.graph = Some(graph),
+                    Err(e) => {
+                        error_f16n!("Failed to build video filter ({}): {}", spec, e);
+                        self.failed = true;
+                    }
+                },
+            }
+            FILTERING.store(self.graph.is_some(), Ordering::SeqCst);
+        }
+
+        let Some(graph) = self.graph.as_mut() else {
+            return Some(frame);
+        };
+
+        let added = graph.get("in")?.source().add(&frame);
+        if let Err(e) = added {
+            error_f16n!("Video filter error: {}", e);
+            self.graph = None;
+            self.failed = true;
+            FILTERING.store(false, Ordering::SeqCst);
+            return None;
+        }
+        let mut filtered = VideoFrame::empty();
+        graph.get("out")?.sink().frame(&mut filtered).ok()?;
+
+        if let Some(rect) = detected_crop(&filtered) {
+            if self.autocrop.update(rect) {
+                // 下一帧用新的裁剪区域重建滤镜图
+                self.generation = u64::MAX;
+            }
+        }
+        Some(filtered)
+    }
+}
+
+/// 读取 cropdetect 写在帧元数据里的检测结果
+fn detected_crop(frame: &VideoFrame) -> Option<Rect> {
+    let tags = frame.metadata();
+    let get = |key: &str| tags.get(key).and_then(|v| v.parse::<i64>().ok());
+    let w = get("lavfi.cropdetect.w")?;
+    let h = get("lavfi.cropdetect.h")?;
+    let x = get("lavfi.cropdetect.x")?;
+    let y = get("lavfi.cropdetect.y")?;
+    if w <= 0 || h <= 0 || x < 0 || y < 0 {
+        return None;
+    }
+    Some(Rect {
+        w: w as u32,
+        h: h as u32,
+        x: x as u32,
+        y: y as u32,
+    })
+}
+
+/// 按当前设置生成滤镜描述，不需要任何滤镜时返回 None
+fn build_spec(frame: &VideoFrame, autocrop: Option<Rect>) -> Option<String> {
+    let settings = *SETTINGS.lock();
+    let (width, height) = (frame.width(), frame.height());
+    let mut chain = Vec::new();
+
+    match settings.deinterlace {
+        Deinterlace::Auto if frame.is_interlaced() => {
+            chain.push("yadif=mode=send_frame:deint=interlaced".to_string())
+        }
+        Deinterlace::On => chain.push("yadif=mode=send_frame:deint=all".to_string()),
+        _ => (),
+    }
+
+    let crop = match settings.crop {
+        Crop::Off => None,
+        Crop::Auto => {
+            // cropdetect 只添加元数据，不改变画面
+            chain.push("cropdetect=limit=24:round=2:reset=0".to_string());
+            autocrop.filter(|r| r.w < width || r.h < height)
+        }
+        // 手动裁剪按原始分辨率给出，解码器用了 lowres 时要换算
+        Crop::Manual(rect) => Some(rect.downscale(vdecoder::lowres_in_use())),
+    };
+    if let Some(rect) = crop {
+        let rect = rect.clamp(width, height);
+        chain.push(format!("crop={}:{}:{}:{}", rect.w, rect.h, rect.x, rect.y));
+    }
+
+    let rotate = settings.rotate.unwrap_or(*STREAM_ROTATION.lock());
+    match rotate {
+        90 => chain.push("transpose=clock".to_string()),
+        180 => chain.push("hflip,vflip".to_string()),
+        270 => chain.push("transpose=cclock".to_string()),
+        _ => (),
+    }
+    if settings.hflip {
+        chain.push("hflip".to_string());
+    }
+    if settings.vflip {
+        chain.push("vflip".to_string());
+    }
+
+    if settings.eq != VideoEq::DEFAULT {
+        chain.push(format!("eq={}", settings.eq));
+    }
+
+    (!chain.is_empty()).then(|| chain.join(","))
+}
+
+fn build_graph(frame: &VideoFrame, spec: &str) -> Result<Graph, av::Error> {
+    let time_base = VIDEO_TIME_BASE.lock().unwrap_or(av::Rational(1, 1_000_000));
+    let sar = frame.aspect_ratio();
+    let sar = if sar.0 > 0 && sar.1 > 0 {
+        sar
+    } else {
+        av::Rational(1, 1)
+    };
+    let args = format!(
+        "video_size={}x{}:pix_fmt={}:time_base={}/{}:pixel_aspect={}/{}",
+        frame.width(),
+        frame.height(),
+        av::ffi::AVPixelFormat::from(frame.format()) as i32,
+        time_base.0,
+        time_base.1,
+        sar.0,
+        sar.1
+    );
+
+    let find = |name| av::filter::find(name).ok_or(av::Error::FilterNotFound);
+    let mut graph = Graph::new();
+    graph.add(&find("buffer")?, "in", &args)?;
+    graph.add(&find("buffersink")?, "out", "")?;
+    graph.output("in", 0)?.input("out", 0)?.parse(spec)?;
+    graph.validate()?;
+    Ok(graph)
+}
+
+// @ ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== @
+
+/// 用于叠加文本和 `/vf` 的状态描述
+pub fn status() -> String {
+    let settings = *SETTINGS.lock();
+    let mut parts = Vec::new();
+    match settings.crop {
+        Crop::Off => (),
+        Crop::Auto => parts.push("crop=auto".to_string()),
+        Crop::Manual(rect) => parts.push(format!("crop={rect}")),
+    }
+    match settings.rotate {
+        None => {
+            let rotation = *STREAM_ROTATION.lock();
+            if rotation != 0 {
+                parts.push(format!("rotate=auto({rotation})"));
+            }
+        }
+        Some(rotate) => parts.push(format!("rotate={rotate}")),
+    }
+    if settings.hflip {
+        parts.push("hflip".to_string());
+    }
+    if settings.vflip {
+        parts.push("vflip".to_string());
+    }
+    if settings.deinterlace != Deinterlace::Auto {
+        parts.push(format!("deint={}", settings.deinterlace.name()));
+    }
+    if settings.eq != VideoEq::DEFAULT {
+        parts.push(format!("eq={}", settings.eq));
+    }
+    if parts.is_empty() {
+        "off".to_string()
+    } else {
+        parts.join(" ")
+    }
+}
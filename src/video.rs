@@ -4,11 +4,14 @@ use parking_lot::{Condvar, Mutex};
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::time::Duration;
 
-use crate::avsync::{self, played_time_or_zero};
+use crate::avsync::{self, MasterClock, played_time_or_zero};
 use crate::ffmpeg::{DECODER_WAKEUP, DECODER_WAKEUP_MUTEX, VIDEO_TIME_BASE};
 use crate::render;
 use crate::statistics::increment_video_skipped_frames;
 use crate::term::TERM_QUIT;
+use crate::tiles;
+use crate::vfilter::VideoFilter;
+use crate::view;
 
 pub static VIDEO_FRAMETIME: AtomicU64 = AtomicU64::new(1_000_000 / 30);
 
@@ -23,8 +26,93 @@ static HINT_SEEKED: AtomicBool = AtomicBool::new(false);
 /// 提示视频模块已经 seek 到指定时间点
 pub fn hint_seeked() {
     HINT_SEEKED.store(true, Ordering::SeqCst);
+    crate::vfilter::invalidate();
 }
 
+// @ ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== @
+
+/// 缩放算法
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScaleAlgo {
+    /// 按缩放比例自动选择
+    Auto,
+    Point,
+    FastBilinear,
+    Bilinear,
+    Bicubic,
+    /// 区域平均，大倍数缩小时又快又不容易出现摩尔纹
+    Area,
+    Lanczos,
+}
+
+impl ScaleAlgo {
+    pub const ALL: &[ScaleAlgo] = &[
+        ScaleAlgo::Auto,
+        ScaleAlgo::Point,
+        ScaleAlgo::FastBilinear,
+        ScaleAlgo::Bilinear,
+        ScaleAlgo::Bicubic,
+        ScaleAlgo::Area,
+        ScaleAlgo::Lanczos,
+    ];
+
+    pub const fn name(&self) -> &'static str {
+        match self {
+            ScaleAlgo::Auto => "auto",
+            ScaleAlgo::Point => "point",
+            ScaleAlgo::FastBilinear => "fast_bilinear",
+            ScaleAlgo::Bilinear => "bilinear",
+            ScaleAlgo::Bicubic => "bicubic",
+            ScaleAlgo::Area => "area",
+            ScaleAlgo::Lanczos => "lanczos",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.trim().to_lowercase();
+        Self::ALL.iter().copied().find(|m| m.name() == name)
+    }
+
+    /// 从 (src_w, src_h) 缩放到 (dst_w, dst_h) 时使用的 swscale 标志
+    fn flags(&self, src: (u32, u32), dst: (u32, u32)) -> Flags {
+        // 两个方向中缩小得较少的倍数
+        let ratio = (src.0 as f32 / dst.0.max(1) as f32).min(src.1 as f32 / dst.1.max(1) as f32);
+        let algo = match self {
+            ScaleAlgo::Auto if ratio >= 2.0 => ScaleAlgo::Area,
+            ScaleAlgo::Auto if ratio < 1.0 => ScaleAlgo::Bicubic,
+            ScaleAlgo::Auto => ScaleAlgo::Bilinear,
+            algo => *algo,
+        };
+        let flags = match algo {
+            ScaleAlgo::Point => Flags::POINT,
+            ScaleAlgo::FastBilinear => Flags::FAST_BILINEAR,
+            ScaleAlgo::Bicubic => Flags::BICUBIC,
+            ScaleAlgo::Area => Flags::AREA,
+            ScaleAlgo::Lanczos => Flags::LANCZOS,
+            _ => Flags::BILINEAR,
+        };
+        // 大倍数缩小时色度的垂直分辨率远高于输出，跳过一半色度行
+        if *self == ScaleAlgo::Auto && ratio >= 4.0 {
+            flags | Flags::from_bits_retain(1 << Flags::SRC_V_CHR_DROP_SHIFT.bits())
+        } else {
+            flags
+        }
+    }
+}
+
+static SCALE_ALGO: Mutex<ScaleAlgo> = Mutex::new(ScaleAlgo::Auto);
+
+pub fn set_scale_algo(algo: ScaleAlgo) {
+    *SCALE_ALGO.lock() = algo;
+}
+
+/// 按当前的缩放算法，从 `src` 缩放到 `dst` 时使用的 swscale 标志
+pub fn scale_flags(src: (u32, u32), dst: (u32, u32)) -> Flags {
+    SCALE_ALGO.lock().flags(src, dst)
+}
+
+// @ ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== @
+
 pub fn video_main() {
     let mut scaler = None;
     let mut scaler_format = None;
@@ -32,6 +120,8 @@ pub fn video_main() {
     let mut scaler_src_height = 0;
     let mut scaler_dst_width = 0;
     let mut scaler_dst_height = 0;
+    let mut scaler_algo = None;
+    let mut filter = VideoFilter::new();
 
     while TERM_QUIT.load(Ordering::SeqCst) == false {
         let frame = {
@@ -55,6 +145,11 @@ pub fn video_main() {
             continue;
         }
 
+        // 滤镜可能会缓存帧（如反交错），此时没有输出
+        let Some(frame) = filter.process(frame) else {
+            continue;
+        };
+
         let frametime = if let Some(pts) = frame.pts() {
             let base = VIDEO_TIME_BASE.lock().unwrap();
             Duration::new(
@@ -67,36 +162,46 @@ pub fn video_main() {
 
         // 为了防止视频卡死，seek 永远播放一帧旧的画面
         let seeked = HINT_SEEKED.swap(false, Ordering::SeqCst);
-        let played = played_time_or_zero();
-        if !seeked && frametime + Duration::from_millis(100) < played {
+        let frame_duration = Duration::from_micros(VIDEO_FRAMETIME.load(Ordering::SeqCst));
+        if !seeked && avsync::frame_too_late(frametime, frame_duration, MasterClock::Video) {
             debug_f16n!(
                 "Video frame too late: frame time {:?}, played time {:?}",
                 frametime,
-                played
+                played_time_or_zero()
             );
             increment_video_skipped_frames(0, 1);
             error_l10n!("Video frame too late, skipping");
             continue;
         }
 
-        {
-            let mut ctx = render::RENDER_CONTEXT.lock();
-            ctx.update_size(Some(frame.width() as usize), Some(frame.height() as usize));
-        }
-
         loop {
+            // 每次输出前按当前视图重新裁剪，暂停时修改视图也能生效
+            let view_generation = view::generation();
+            let Some((frame, display)) = view::apply(&frame) else {
+                error_l10n!("Could not crop video frame");
+                break;
+            };
+            let display = tiles::display_size(display);
+            {
+                let mut ctx = render::RENDER_CONTEXT.lock();
+                ctx.update_size(Some(display.0), Some(display.1));
+            }
+
             let ctx = render::RENDER_CONTEXT.lock();
             let ss = frame.width() != scaler_src_width || frame.height() != scaler_src_height;
             let ts = ctx.frame_width != scaler_dst_width || ctx.frame_height != scaler_dst_height;
-            if ss || ts || Some(frame.format()) != scaler_format {
+            let algo = *SCALE_ALGO.lock();
+            if ss || ts || Some(frame.format()) != scaler_format || Some(algo) != scaler_algo {
+                let src = (frame.width(), frame.height());
+                let dst = (ctx.frame_width as u32, ctx.frame_height as u32);
                 let Ok(sws) = Scaler::get(
                     frame.format(),
-                    frame.width(),
-                    frame.height(),
+                    src.0,
+                    src.1,
                     av::format::Pixel::RGBA,
-                    ctx.frame_width as u32,
-                    ctx.frame_height as u32,
-                    Flags::BILINEAR,
+                    dst.0,
+                    dst.1,
+                    algo.flags(src, dst),
                 ) else {
                     error_l10n!("Could not create scaler for video frame");
                     break;
@@ -107,12 +212,14 @@ pub fn video_main() {
                 scaler_src_height = frame.height();
                 scaler_dst_width = ctx.frame_width;
                 scaler_dst_height = ctx.frame_height;
+                scaler_algo = Some(algo);
             }
             drop(ctx);
 
             let scaler = scaler.as_mut().unwrap();
 
-            let mut scaled = VideoFrame::empty();
+            let mut scaled =
+                render::api_take_frame(scaler_dst_width as u32, scaler_dst_height as u32);
             if let Err(e) = scaler.run(&frame, &mut scaled) {
                 error_f16n!("Could not scale video frame: {}", e);
                 break;
@@ -125,13 +232,16 @@ pub fn video_main() {
                 let max = Duration::from_micros(VIDEO_FRAMETIME.load(Ordering::SeqCst) * 2);
                 if render::api_wait_frame_request_for(remaining.min(max)) {
                     let ctx = render::RENDER_CONTEXT.lock();
-                    if ctx.frame_width != scaler_dst_width || ctx.frame_height != scaler_dst_height
+                    if ctx.frame_width != scaler_dst_width
+                        || ctx.frame_height != scaler_dst_height
+                        || view::generation() != view_generation
                     {
                         continue;
                     }
                 }
             }
 
+            tiles::hint_frame_time(Some(frametime));
             render::api_send_frame(scaled);
             avsync::hint_video_played_time(frametime);
 
@@ -0,0 +1,337 @@
+use av::util::frame::video::Video as VideoFrame;
+use parking_lot::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::render;
+use crate::stdin::{self, Key, MouseAction};
+
+// @ ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== @
+
+/// 画面适配终端的方式
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Aspect {
+    /// 完整显示画面，保持宽高比，两侧留黑边
+    Fit,
+    /// 填满终端，保持宽高比，裁掉超出的部分
+    Fill,
+    /// 拉伸到终端大小，不保持宽高比
+    Stretch,
+    /// 强制按指定宽高比显示
+    Ratio(f32),
+}
+
+impl Aspect {
+    /// 按 `a` 键循环切换的顺序
+    const CYCLE: &[Aspect] = &[
+        Aspect::Fit,
+        Aspect::Fill,
+        Aspect::Stretch,
+        Aspect::Ratio(4.0 / 3.0),
+        Aspect::Ratio(16.0 / 9.0),
+        Aspect::Ratio(2.35),
+    ];
+
+    pub const NAMES: &[&str] = &["fit", "fill", "stretch", "4:3", "16:9", "2.35:1"];
+
+    /// 解析 `fit`、`fill`、`stretch`，或 `4:3`、`2.35:1`、`1.85` 形式的宽高比
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim().to_lowercase();
+        let ratio = match value.as_str() {
+            "fit" | "" => return Some(Aspect::Fit),
+            "fill" | "crop" => return Some(Aspect::Fill),
+            "stretch" => return Some(Aspect::Stretch),
+            v => match v.split_once(':') {
+                Some((w, h)) => w.trim().parse::<f32>().ok()? / h.trim().parse::<f32>().ok()?,
+                None => v.parse::<f32>().ok()?,
+            },
+        };
+        (ratio.is_finite() && (0.1..=10.0).contains(&ratio)).then_some(Aspect::Ratio(ratio))
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            Aspect::Fit => "fit".to_string(),
+            Aspect::Fill => "fill".to_string(),
+            Aspect::Stretch => "stretch".to_string(),
+            Aspect::Ratio(r) => {
+                // 常见比例用整数表示
+                for (w, h) in [(4, 3), (16, 9), (16, 10), (5, 4), (3, 2), (21, 9), (1, 1)] {
+                    if (r - w as f32 / h as f32).abs() < 0.005 {
+                        return format!("{w}:{h}");
+                    }
+                }
+                format!("{}:1", (r * 100.0).round() / 100.0)
+            }
+        }
+    }
+}
+
+// @ ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== @
+
+/// 最大缩放倍数
+const MAX_ZOOM: f32 = 8.0;
+/// 每次按键缩放的倍数
+const ZOOM_STEP: f32 = 1.25;
+/// 每次按键平移可见区域的比例
+const PAN_STEP: f32 = 0.1;
+
+#[derive(Debug, Clone, Copy)]
+struct View {
+    aspect: Aspect,
+    zoom: f32,
+    /// 可见区域中心在画面中的位置，范围 0 到 1
+    center: (f32, f32),
+    /// 上一帧可见区域占画面的比例，用于限制平移范围
+    visible: (f32, f32),
+}
+
+static VIEW: Mutex<View> = Mutex::new(View {
+    aspect: Aspect::Fit,
+    zoom: 1.0,
+    center: (0.5, 0.5),
+    visible: (1.0, 1.0),
+});
+
+/// 视图设置变化时递增，视频线程据此重新裁剪当前帧
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+pub fn generation() -> u64 {
+    GENERATION.load(Ordering::SeqCst)
+}
+
+/// 视图变化后请求重新输出当前帧，暂停时也能立即看到效果
+fn changed() {
+    GENERATION.fetch_add(1, Ordering::SeqCst);
+    render::api_request_frame();
+}
+
+pub fn set_aspect(aspect: Aspect) {
+    VIEW.lock().aspect = aspect;
+    changed();
+}
+
+pub fn cycle_aspect() {
+    let aspect = {
+        let mut view = VIEW.lock();
+        let index = Aspect::CYCLE.iter().position(|&a| a == view.aspect);
+        view.aspect = Aspect::CYCLE[index.map_or(0, |i| (i + 1) % Aspect::CYCLE.len())];
+        view.aspect
+    };
+    changed();
+    info_f16n!("Aspect: {}", aspect.name());
+}
+
+pub fn zoom() -> f32 {
+    VIEW.lock().zoom
+}
+
+pub fn set_zoom(zoom: f32) {
+    VIEW.lock().zoom = zoom.clamp(1.0, MAX_ZOOM);
+    changed();
+}
+
+pub fn zoom_by(factor: f32) {
+    let zoom = {
+        let mut view = VIEW.lock();
+        view.zoom = (view.zoom * factor).clamp(1.0, MAX_ZOOM);
+        // 接近 1 时对齐，避免浮点误差导致无法回到原始大小
+        if (view.zoom - 1.0).abs() < 0.01 {
+            view.zoom = 1.0;
+        }
+        view.zoom
+    };
+    changed();
+    info_f16n!("Zoom: {:.2}x", zoom);
+}
+
+/// 平移可见区域，`dx`、`dy` 以可见区域的大小为单位
+pub fn pan_by(dx: f32, dy: f32) {
+    {
+        let mut view = VIEW.lock();
+        let (vx, vy) = view.visible;
+        view.center.0 = (view.center.0 + dx * vx).clamp(vx / 2.0, 1.0 - vx / 2.0);
+        view.center.1 = (view.center.1 + dy * vy).clamp(vy / 2.0, 1.0 - vy / 2.0);
+    }
+    changed();
+}
+
+/// 恢复缩放和平移，保留适配方式
+pub fn reset() {
+    {
+        let mut view = VIEW.lock();
+        view.zoom = 1.0;
+        view.center = (0.5, 0.5);
+    }
+    changed();
+}
+
+/// 当前画面是否有被裁掉的部分可以平移
+fn can_pan() -> bool {
+    let view = VIEW.lock();
+    view.visible.0 < 1.0 || view.visible.1 < 1.0
+}
+
+// @ ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== @
+
+/// 按当前视图裁剪帧
+/// - 返回裁剪后的帧，和传给 `update_size` 的显示大小
+/// - 裁剪只修改数据指针，不复制像素
+pub fn apply(frame: &VideoFrame) -> Option<(VideoFrame, (usize, usize))> {
+    let (w, h) = (frame.width() as f32, frame.height() as f32);
+    let sar = frame.aspect_ratio();
+    let sar = if sar.numerator() > 0 && sar.denominator() > 0 {
+        sar.numerator() as f32 / sar.denominator() as f32
+    } else {
+        1.0
+    };
+    let box_aspect = {
+        let ctx = render::RENDER_CONTEXT.lock();
+        if ctx.pixels_width == 0 || ctx.pixels_height == 0 {
+            w * sar / h
+        } else {
+            ctx.pixels_width as f32 / ctx.pixels_height as f32
+        }
+    };
+
+    let mut view = VIEW.lock();
+    // 整个画面的显示宽高比
+    let dar = match view.aspect {
+        Aspect::Ratio(r) => r,
+        _ => w * sar / h,
+    };
+    // 可见区域占画面的比例
+    let (mut fx, mut fy) = (1.0f32, 1.0f32);
+    if view.aspect == Aspect::Fill {
+        if dar > box_aspect {
+            fx = box_aspect / dar;
+        } else {
+            fy = dar / box_aspect;
+        }
+    }
+    fx /= view.zoom;
+    fy /= view.zoom;
+    view.visible = (fx, fy);
+    view.center.0 = view.center.0.clamp(fx / 2.0, 1.0 - fx / 2.0);
+    view.center.1 = view.center.1.clamp(fy / 2.0, 1.0 - fy / 2.0);
+    let (aspect, zoom, center) = (view.aspect, view.zoom, view.center);
+    drop(view);
+
+    let vw = ((w * fx).round() as usize).clamp(1, w as usize);
+    let vh = ((h * fy).round() as usize).clamp(1, h as usize);
+    let x = ((center.0 * w) as usize)
+        .saturating_sub(vw / 2)
+        .min(w as usize - vw);
+    let y = ((center.1 * h) as usize)
+        .saturating_sub(vh / 2)
+        .min(h as usize - vh);
+
+    let mut cropped = VideoFrame::empty();
+    unsafe {
+        let ptr = cropped.as_mut_ptr();
+        if av::ffi::av_frame_ref(ptr, frame.as_ptr()) < 0 {
+            return None;
+        }
+        if (vw, vh) != (w as usize, h as usize) {
+            (*ptr).crop_left = x;
+            (*ptr).crop_top = y;
+            (*ptr).crop_right = w as usize - x - vw;
+            (*ptr).crop_bottom = h as usize - y - vh;
+            // AV_FRAME_CROP_UNALIGNED，按精确的像素位置裁剪
+            if av::ffi::av_frame_apply_cropping(ptr, 1) < 0 {
+                return None;
+            }
+        }
+    }
+
+    // 可见区域的显示宽高比，拉伸时直接使用终端的宽高比
+    let visible_aspect = match aspect {
+        Aspect::Stretch => box_aspect,
+        _ => dar * fx / fy,
+    };
+    // 放大时按放大前的分辨率输出，避免像素模式下被限制为裁剪后的大小
+    let height = (vh as f32 * zoom).round().max(1.0);
+    let width = (height * visible_aspect).round().max(1.0);
+    Some((cropped, (width as usize, height as usize)))
+}
+
+/// 用于叠加文本的视图状态
+pub fn status() -> String {
+    let view = *VIEW.lock();
+    let mut parts = vec![view.aspect.name()];
+    if view.zoom > 1.0 {
+        parts.push(format!("zoom={:.2}x", view.zoom));
+    }
+    if view.visible.0 < 1.0 || view.visible.1 < 1.0 {
+        parts.push(format!("center={:.2},{:.2}", view.center.0, view.center.1));
+    }
+    parts.join(" ")
+}
+
+// @ ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== @
+
+pub fn register_input_callbacks() {
+    stdin::register_keypress_callback(Key::Normal('a'), |_, _| {
+        cycle_aspect();
+        true
+    });
+    stdin::register_keypress_callback(Key::Normal('+'), |_, _| {
+        zoom_by(ZOOM_STEP);
+        true
+    });
+    stdin::register_keypress_callback(Key::Normal('-'), |_, _| {
+        zoom_by(1.0 / ZOOM_STEP);
+        true
+    });
+    stdin::register_keypress_callback(Key::Normal('0'), |_, _| {
+        reset();
+        true
+    });
+    stdin::register_keypress_callback(Key::ShiftUp, |_, _| {
+        pan_by(0.0, -PAN_STEP);
+        true
+    });
+    stdin::register_keypress_callback(Key::ShiftDown, |_, _| {
+        pan_by(0.0, PAN_STEP);
+        true
+    });
+    stdin::register_keypress_callback(Key::ShiftLeft, |_, _| {
+        pan_by(-PAN_STEP, 0.0);
+        true
+    });
+    stdin::register_keypress_callback(Key::ShiftRight, |_, _| {
+        pan_by(PAN_STEP, 0.0);
+        true
+    });
+
+    // 左键拖动画面平移，进度条区域的拖动由 UI 先处理
+    static DRAG_FROM: Mutex<Option<(i32, i32)>> = Mutex::new(None);
+    stdin::register_mouse_callback(|_, m| {
+        let mut drag = DRAG_FROM.lock();
+        match (*drag, m.action) {
+            (None, MouseAction::LeftDown) if can_pan() => {
+                *drag = Some(m.pos);
+                true
+            }
+            (Some(from), _) if m.left => {
+                let (cw, ch) = {
+                    let ctx = render::RENDER_CONTEXT.lock();
+                    (ctx.video_cells_width.max(1), ctx.video_cells_height.max(1))
+                };
+                // 画面跟随鼠标移动，所以可见区域反方向平移
+                let dx = (from.0 - m.pos.0) as f32 / cw as f32;
+                let dy = (from.1 - m.pos.1) as f32 / ch as f32;
+                *drag = Some(m.pos);
+                drop(drag);
+                if dx != 0.0 || dy != 0.0 {
+                    pan_by(dx, dy);
+                }
+                true
+            }
+            (Some(_), _) => {
+                *drag = None;
+                true
+            }
+            _ => false,
+        }
+    });
+}
@@ -0,0 +1,548 @@
+use parking_lot::{Mutex, MutexGuard};
+use std::collections::VecDeque;
+use std::f32::consts::PI;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::time::Instant;
+
+use crate::util::Color;
+
+// @ ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== @
+
+/// 音频可视化样式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Visualizer {
+    /// 音量峰值历史
+    Volume,
+    /// FFT 频谱柱状图
+    Spectrum,
+    /// 滚动频谱图
+    Spectrogram,
+    /// 示波器，每个声道一条波形
+    Oscilloscope,
+    /// 立体声矢量示波器（李萨如图形）
+    Vectorscope,
+    /// 各声道 VU 表
+    Vu,
+}
+
+impl Visualizer {
+    pub const ALL: &[Visualizer] = &[
+        Visualizer::Volume,
+        Visualizer::Spectrum,
+        Visualizer::Spectrogram,
+        Visualizer::Oscilloscope,
+        Visualizer::Vectorscope,
+        Visualizer::Vu,
+    ];
+
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Visualizer::Volume => "volume",
+            Visualizer::Spectrum => "spectrum",
+            Visualizer::Spectrogram => "spectrogram",
+            Visualizer::Oscilloscope => "oscilloscope",
+            Visualizer::Vectorscope => "vectorscope",
+            Visualizer::Vu => "vu",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.trim().to_lowercase();
+        Self::ALL.iter().copied().find(|m| m.name() == name)
+    }
+
+    /// 循环切换时的下一个样式，最后一个之后返回 `None`
+    pub fn next(&self) -> Option<Self> {
+        let i = Self::ALL.iter().position(|m| m == self).unwrap_or(0);
+        Self::ALL.get(i + 1).copied()
+    }
+}
+
+static MODE: AtomicU8 = AtomicU8::new(Visualizer::Volume as u8);
+
+pub fn set_mode(mode: Visualizer) {
+    MODE.store(mode as u8, Ordering::SeqCst);
+}
+
+pub fn mode() -> Visualizer {
+    let i = MODE.load(Ordering::SeqCst) as usize;
+    Visualizer::ALL
+        .get(i)
+        .copied()
+        .unwrap_or(Visualizer::Volume)
+}
+
+// @ ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== @
+
+/// PCM 缓冲保留的采样帧数
+const TAP_FRAMES: usize = 8192;
+
+/// 实际送往音频设备的 PCM 采样（交错的 f32）
+pub struct PcmTap {
+    channels: usize,
+    rate: u32,
+    /// 累计写入的采样数，用于在缓冲中找到帧边界
+    written: u64,
+    samples: VecDeque<f32>,
+}
+
+static PCM_TAP: Mutex<PcmTap> = Mutex::new(PcmTap {
+    channels: 0,
+    rate: 0,
+    written: 0,
+    samples: VecDeque::new(),
+});
+
+/// 获取 PCM 采样缓冲，声道数或采样率变化时清空
+///
+/// 在音频回调中调用，不能等待渲染线程：缓冲正被读取时返回 `None`，本次回调的采样不送入缓冲
+pub fn pcm_tap(channels: usize, rate: u32) -> Option<MutexGuard<'static, PcmTap>> {
+    let mut tap = PCM_TAP.try_lock()?;
+    if tap.channels != channels || tap.rate != rate {
+        tap.channels = channels;
+        tap.rate = rate;
+        tap.written = 0;
+        tap.samples.clear();
+    }
+    Some(tap)
+}
+
+impl PcmTap {
+    pub fn push(&mut self, v: f32) {
+        if self.samples.len() >= TAP_FRAMES * self.channels.max(1) {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(v);
+        self.written += 1;
+    }
+}
+
+/// 最近一段 PCM 采样的拷贝
+struct Snapshot {
+    channels: usize,
+    rate: u32,
+    /// 交错采样，不足的部分在开头补零
+    samples: Vec<f32>,
+}
+
+impl Snapshot {
+    fn frames(&self) -> usize {
+        self.samples.len() / self.channels
+    }
+
+    fn frame(&self, i: usize) -> &[f32] {
+        &self.samples[i * self.channels..(i + 1) * self.channels]
+    }
+
+    /// 第 `i` 帧的左右声道，单声道时两者相同
+    fn stereo(&self, i: usize) -> (f32, f32) {
+        let frame = self.frame(i);
+        (frame[0], frame[frame.len().min(2) - 1])
+    }
+
+    /// 第 `i` 帧各声道的平均值
+    fn mono(&self, i: usize) -> f32 {
+        self.frame(i).iter().sum::<f32>() / self.channels as f32
+    }
+}
+
+/// 当前 PCM 采样率，还没有音频输出时假定为 48kHz
+fn sample_rate() -> u32 {
+    match PCM_TAP.lock().rate {
+        0 => 48000,
+        rate => rate,
+    }
+}
+
+/// 拷贝最近 `frames` 帧采样
+fn snapshot(frames: usize) -> Snapshot {
+    let rate = sample_rate();
+    let tap = PCM_TAP.lock();
+    let channels = tap.channels.max(1);
+    // 缓冲满了之后按采样逐个丢弃，开头可能是半帧
+    let start = tap.written - tap.samples.len() as u64;
+    let skip = (channels - (start % channels as u64) as usize) % channels;
+    let available = (tap.samples.len() - skip.min(tap.samples.len())) / channels;
+    let take = available.min(frames);
+    let mut samples = vec![0.0; (frames - take) * channels];
+    samples.extend(tap.samples.range(tap.samples.len() - take * channels..));
+    Snapshot {
+        channels,
+        rate,
+        samples,
+    }
+}
+
+// @ ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== @
+
+const FFT_SIZE: usize = 2048;
+/// 显示范围的下限（dBFS）
+const MIN_DB: f32 = -80.0;
+const MIN_FREQ: f32 = 30.0;
+const MAX_FREQ: f32 = 16000.0;
+
+/// 原地基 2 FFT，长度必须是 2 的幂
+fn fft(re: &mut [f32], im: &mut [f32]) {
+    let n = re.len();
+    assert!(n >= 2 && n.is_power_of_two() && im.len() == n);
+    let bits = n.trailing_zeros();
+    for i in 0..n {
+        let j = i.reverse_bits() >> (usize::BITS - bits);
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+    let mut len = 2;
+    while len <= n {
+        let angle = -2.0 * PI / len as f32;
+        let (wr, wi) = (angle.cos(), angle.sin());
+        for start in (0..n).step_by(len) {
+            let (mut cr, mut ci) = (1.0f32, 0.0f32);
+            for k in 0..len / 2 {
+                let a = start + k;
+                let b = a + len / 2;
+                let tr = re[b] * cr - im[b] * ci;
+                let ti = re[b] * ci + im[b] * cr;
+                re[b] = re[a] - tr;
+                im[b] = im[a] - ti;
+                re[a] += tr;
+                im[a] += ti;
+                let nr = cr * wr - ci * wi;
+                ci = cr * wi + ci * wr;
+                cr = nr;
+            }
+        }
+        len <<= 1;
+    }
+}
+
+/// 幅度映射到 0..1 的显示电平
+fn level(amp: f32) -> f32 {
+    let db = 20.0 * amp.max(1e-9).log10();
+    ((db - MIN_DB) / -MIN_DB).clamp(0.0, 1.0)
+}
+
+/// 最近 `FFT_SIZE` 帧的频谱（各频点的显示电平）
+struct Spectrum {
+    rate: u32,
+    bins: Vec<f32>,
+}
+
+impl Spectrum {
+    fn compute() -> Self {
+        let snap = snapshot(FFT_SIZE);
+        let mut re = vec![0.0; FFT_SIZE];
+        let mut im = vec![0.0; FFT_SIZE];
+        let mut wsum = 0.0;
+        for (i, v) in re.iter_mut().enumerate() {
+            // Hann 窗
+            let w = 0.5 - 0.5 * (2.0 * PI * i as f32 / (FFT_SIZE - 1) as f32).cos();
+            *v = snap.mono(i) * w;
+            wsum += w;
+        }
+        fft(&mut re, &mut im);
+        let bins = (0..FFT_SIZE / 2)
+            .map(|i| level((re[i] * re[i] + im[i] * im[i]).sqrt() * 2.0 / wsum))
+            .collect();
+        Self {
+            rate: snap.rate,
+            bins,
+        }
+    }
+
+    /// 把 0..1 按对数刻度映射到频率
+    fn freq(&self, t: f32) -> f32 {
+        let max = (self.rate as f32 / 2.0).min(MAX_FREQ);
+        MIN_FREQ * (max / MIN_FREQ).powf(t)
+    }
+
+    /// 频率区间内的最大电平，区间不足一个频点时取最近的频点
+    fn band(&self, lo: f32, hi: f32) -> f32 {
+        let hz_per_bin = self.rate as f32 / FFT_SIZE as f32;
+        let last = self.bins.len() - 1;
+        let i0 = ((lo / hz_per_bin).round() as usize).clamp(1, last);
+        let i1 = ((hi / hz_per_bin).round() as usize).clamp(i0, last);
+        self.bins[i0..=i1].iter().copied().fold(0.0, f32::max)
+    }
+}
+
+// @ ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== ===== @
+
+const VOLUME_HISTORY_LEN: usize = 128;
+/// 频谱柱每秒下落的高度（占满高的比例）
+const BAR_FALL: f32 = 1.5;
+/// 峰值标记每秒下落的高度
+const PEAK_FALL: f32 = 0.4;
+/// VU 表的显示范围（dBFS）
+const VU_MIN_DB: f32 = -60.0;
+/// VU 表的积分时间（秒）
+const VU_WINDOW: f32 = 0.05;
+
+const GREEN: Color = Color::new(64, 192, 128);
+const YELLOW: Color = Color::new(230, 200, 64);
+const RED: Color = Color::new(230, 64, 64);
+const WHITE: Color = Color::new(255, 255, 255);
+const GRID: Color = Color::new(48, 48, 48);
+
+/// 频谱图的热度色板
+const HEAT: &[Color] = &[
+    Color::new(0, 0, 0),
+    Color::new(32, 0, 96),
+    Color::new(128, 0, 128),
+    Color::new(224, 64, 32),
+    Color::new(255, 192, 0),
+    Color::new(255, 255, 255),
+];
+
+/// 在色板上按 0..1 插值
+fn palette(stops: &[Color], t: f32) -> Color {
+    let t = t.clamp(0.0, 1.0) * (stops.len() - 1) as f32;
+    let i = (t.floor() as usize).min(stops.len() - 2);
+    Color::mix(stops[i + 1], stops[i], t - i as f32)
+}
+
+/// 电平表颜色：绿、黄、红
+fn meter_color(t: f32) -> Color {
+    palette(&[GREEN, GREEN, YELLOW, RED], t)
+}
+
+/// 渲染线程保留的可视化状态
+struct State {
+    last: Option<Instant>,
+    volume: VecDeque<f32>,
+    bars: Vec<f32>,
+    peaks: Vec<f32>,
+    /// 频谱图画面
+    image: Vec<Color>,
+    image_size: (usize, usize),
+    /// 矢量示波器的余辉
+    scope: Vec<f32>,
+    vu: Vec<f32>,
+    vu_peaks: Vec<f32>,
+}
+
+static STATE: Mutex<State> = Mutex::new(State {
+    last: None,
+    volume: VecDeque::new(),
+    bars: Vec::new(),
+    peaks: Vec::new(),
+    image: Vec::new(),
+    image_size: (0, 0),
+    scope: Vec::new(),
+    vu: Vec::new(),
+    vu_peaks: Vec::new(),
+});
+
+/// 把当前样式的可视化画到 `frame`（`w * h` 像素）上
+pub fn render(frame: &mut [Color], w: usize, h: usize) {
+    if w == 0 || h == 0 {
+        return;
+    }
+    let mut state = STATE.lock();
+    let now = Instant::now();
+    let dt = state
+        .last
+        .map_or(0.0, |t| now.duration_since(t).as_secs_f32())
+        .min(0.25);
+    state.last = Some(now);
+    match mode() {
+        Visualizer::Volume => render_volume(&mut state, frame, w, h),
+        Visualizer::Spectrum => render_spectrum(&mut state, frame, w, h, dt),
+        Visualizer::Spectrogram => render_spectrogram(&mut state, frame, w, h),
+        Visualizer::Oscilloscope => render_oscilloscope(frame, w, h),
+        Visualizer::Vectorscope => render_vectorscope(&mut state, frame, w, h),
+        Visualizer::Vu => render_vu(&mut state, frame, w, h, dt),
+    }
+}
+
+fn render_volume(state: &mut State, frame: &mut [Color], w: usize, h: usize) {
+    // 取最近一个渲染周期的采样峰值
+    let snap = snapshot(sample_rate() as usize / 30);
+    let peak = snap.samples.iter().fold(0.0f32, |m, v| m.max(v.abs()));
+    while state.volume.len() >= VOLUME_HISTORY_LEN {
+        state.volume.pop_front();
+    }
+    while state.volume.len() < VOLUME_HISTORY_LEN - 1 {
+        state.volume.push_front(0.0);
+    }
+    state.volume.push_back(peak * peak);
+
+    let stat = &state.volume;
+    let max = (h as f32 * 0.8).round() as usize;
+    for x in 0..w {
+        frame[(h - max) / 2 * w + x] = GREEN;
+        frame[((h + max) / 2).min(h - 1) * w + x] = GREEN;
+        let i0 = x as f32 / w as f32 * stat.len() as f32;
+        let i1 = (i0.floor() as usize).min(stat.len() - 1);
+        let i2 = (i0.ceil() as usize).min(stat.len() - 1);
+        let k = i0 - i0.floor();
+        let vol = stat[i1] * (1.0 - k) + stat[i2] * k;
+        let filled = (vol * h as f32 * 0.8).round().clamp(0.0, h as f32) as usize;
+        for y in (h - filled) / 2..(h + filled) / 2 {
+            frame[y * w + x] = WHITE;
+        }
+    }
+}
+
+fn render_spectrum(state: &mut State, frame: &mut [Color], w: usize, h: usize, dt: f32) {
+    let spectrum = Spectrum::compute();
+    let count = (w / 4).clamp(1, 64);
+    state.bars.resize(count, 0.0);
+    state.peaks.resize(count, 0.0);
+    let bar_w = w / count;
+    let gap = if bar_w > 2 { 1 } else { 0 };
+    let offset = (w - bar_w * count) / 2;
+    for i in 0..count {
+        let lo = spectrum.freq(i as f32 / count as f32);
+        let hi = spectrum.freq((i + 1) as f32 / count as f32);
+        let bar = spectrum.band(lo, hi).max(state.bars[i] - BAR_FALL * dt);
+        let peak = bar.max(state.peaks[i] - PEAK_FALL * dt);
+        state.bars[i] = bar;
+        state.peaks[i] = peak;
+
+        let x0 = offset + i * bar_w;
+        let filled = (bar * h as f32).round() as usize;
+        for y in h - filled..h {
+            let color = meter_color((h - y) as f32 / h as f32);
+            frame[y * w + x0..y * w + x0 + bar_w - gap].fill(color);
+        }
+        if peak > 0.0 {
+            let y = h - 1 - (peak * (h - 1) as f32).round() as usize;
+            frame[y * w + x0..y * w + x0 + bar_w - gap].fill(WHITE);
+        }
+    }
+}
+
+fn render_spectrogram(state: &mut State, frame: &mut [Color], w: usize, h: usize) {
+    if state.image_size != (w, h) {
+        state.image = vec![Color::new(0, 0, 0); w * h];
+        state.image_size = (w, h);
+    }
+    let spectrum = Spectrum::compute();
+    for y in 0..h {
+        let row = &mut state.image[y * w..(y + 1) * w];
+        row.copy_within(1.., 0);
+        // 低频在下
+        let lo = spectrum.freq((h - 1 - y) as f32 / h as f32);
+        let hi = spectrum.freq((h - y) as f32 / h as f32);
+        row[w - 1] = palette(HEAT, spectrum.band(lo, hi));
+    }
+    frame.copy_from_slice(&state.image);
+}
+
+fn render_oscilloscope(frame: &mut [Color], w: usize, h: usize) {
+    let snap = snapshot(TAP_FRAMES);
+    let channels = snap.channels.min(2).min(h);
+    // 显示 20ms，从过零点开始以稳定波形
+    let span = (snap.rate as usize / 50).clamp(1, snap.frames() / 2);
+    let latest = snap.frames() - span;
+    let start = (latest.saturating_sub(span)..latest)
+        .rev()
+        .find(|&i| i > 0 && snap.mono(i - 1) < 0.0 && snap.mono(i) >= 0.0)
+        .unwrap_or(latest);
+    let lane = h / channels;
+    for ch in 0..channels {
+        let top = ch * lane;
+        let mid = top + lane / 2;
+        frame[mid * w..(mid + 1) * w].fill(GRID);
+        let mut prev = None;
+        for x in 0..w {
+            let i = start + x * span / w;
+            let v = match (channels, ch) {
+                (1, _) => snap.mono(i),
+                (_, 0) => snap.stereo(i).0,
+                _ => snap.stereo(i).1,
+            };
+            let y = (mid as f32 - v.clamp(-1.0, 1.0) * (lane / 2) as f32).round() as usize;
+            let y = y.clamp(top, top + lane - 1);
+            let (y0, y1) = prev.map_or((y, y), |p: usize| (p.min(y), p.max(y)));
+            for yy in y0..=y1 {
+                frame[yy * w + x] = GREEN;
+            }
+            prev = Some(y);
+        }
+    }
+}
+
+fn render_vectorscope(state: &mut State, frame: &mut [Color], w: usize, h: usize) {
+    let size = w.min(h);
+    if state.scope.len() != size * size {
+        state.scope = vec![0.0; size * size];
+    }
+    for v in state.scope.iter_mut() {
+        *v *= 0.6;
+    }
+    let snap = snapshot(FFT_SIZE);
+    let half = (size / 2) as f32;
+    for i in 0..snap.frames() {
+        let (l, r) = snap.stereo(i);
+        // 旋转 45°：中间信号竖直，侧信号水平
+        let x = half + (r - l) * std::f32::consts::FRAC_1_SQRT_2 * half;
+        let y = half - (l + r) * std::f32::consts::FRAC_1_SQRT_2 * half;
+        if x < 0.0 || y < 0.0 || x >= size as f32 || y >= size as f32 {
+            continue;
+        }
+        let idx = y as usize * size + x as usize;
+        state.scope[idx] = (state.scope[idx] + 0.25).min(1.0);
+    }
+
+    let (ox, oy) = ((w - size) / 2, (h - size) / 2);
+    for i in 0..size {
+        frame[(oy + size / 2) * w + ox + i] = GRID;
+        frame[(oy + i) * w + ox + size / 2] = GRID;
+    }
+    for y in 0..size {
+        for x in 0..size {
+            let v = state.scope[y * size + x];
+            if v > 0.02 {
+                frame[(oy + y) * w + ox + x] = Color::mix(GREEN, Color::new(0, 0, 0), v.sqrt());
+            }
+        }
+    }
+}
+
+fn render_vu(state: &mut State, frame: &mut [Color], w: usize, h: usize, dt: f32) {
+    let snap = snapshot((sample_rate() as f32 * VU_WINDOW) as usize);
+    let channels = snap.channels.min(w);
+    state.vu.resize(channels, 0.0);
+    state.vu_peaks.resize(channels, 0.0);
+
+    // 刻度：每 6dB 一条
+    for db in (1..(-VU_MIN_DB / 6.0) as usize).map(|i| -6.0 * i as f32) {
+        let y = ((db / VU_MIN_DB) * (h - 1) as f32).round() as usize;
+        frame[y * w..(y + 1) * w].fill(GRID);
+    }
+
+    let lane = w / channels;
+    let bar_w = (lane * 3 / 4).max(1);
+    for ch in 0..channels {
+        let (mut sum, mut peak) = (0.0f32, 0.0f32);
+        for i in 0..snap.frames() {
+            let v = snap.frame(i)[ch];
+            sum += v * v;
+            peak = peak.max(v.abs());
+        }
+        let rms = (sum / snap.frames().max(1) as f32).sqrt();
+        let to_level = |amp: f32| {
+            let db = 20.0 * amp.max(1e-9).log10();
+            (1.0 - db / VU_MIN_DB).clamp(0.0, 1.0)
+        };
+        // 表针有惯性，峰值保持后缓慢回落
+        let vu = state.vu[ch] + (to_level(rms) - state.vu[ch]) * (dt / 0.3).min(1.0);
+        let vu_peak = to_level(peak).max(state.vu_peaks[ch] - PEAK_FALL * dt);
+        state.vu[ch] = vu;
+        state.vu_peaks[ch] = vu_peak;
+
+        let x0 = ch * lane + (lane - bar_w) / 2;
+        let filled = (vu * h as f32).round() as usize;
+        for y in h - filled..h {
+            let color = meter_color((h - y) as f32 / h as f32);
+            frame[y * w + x0..y * w + x0 + bar_w].fill(color);
+        }
+        if vu_peak > 0.0 {
+            let y = h - 1 - (vu_peak * (h - 1) as f32).round() as usize;
+            frame[y * w + x0..y * w + x0 + bar_w].fill(WHITE);
+        }
+    }
+}